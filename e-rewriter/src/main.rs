@@ -1,7 +1,10 @@
 use egg::*;
 use egraph_serialize::EGraph as SerializedEGraph;
 use rand::random;
+use rand::rngs::StdRng;
+use rand::Rng;
 use rayon::iter::ParallelDrainRange;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::json;
 use std::f32::consts::E;
@@ -18,14 +21,15 @@ use std::path::Path;
 use std::time::Instant;
 mod utils;
 use crate::utils::cost::*;
-use crate::utils::random_gen;
+use crate::utils::random_gen::task_rng;
 use crate::utils::runner_modified;
 use serde_json::Value;
 use std::collections::BTreeMap;
 use std::path::PathBuf;
-use utils::{extract_new::*, language::*, preprocess::*};
+use utils::{eqn::*, explain::*, extract_new::*, language::*, preprocess::*, simulation::*, fraig::*, npn::*, rule_file::*, synth::*};
 use std::collections::HashSet;
 use log::LevelFilter;
+use clap::{Parser, ValueEnum};
 
 // fn print_usage(program_name: &str) {
 //     println!(
@@ -34,7 +38,11 @@ use log::LevelFilter;
 //     );
 // }
 
-fn save_egraph_to_json(egraph: &EGraph<Prop, ()>, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+fn save_egraph_to_json<N>(egraph: &EGraph<Prop, N>, file_path: &PathBuf) -> Result<(), Box<dyn std::error::Error>>
+where
+    N: Analysis<Prop> + Serialize,
+    N::Data: Serialize,
+{
     let json_rep = serde_json::to_string_pretty(&egraph).unwrap();
     fs::write(&file_path, json_rep)?;
     Ok(())
@@ -61,54 +69,499 @@ fn save_serialized_egraph_to_json(serialized_egraph: &SerializedEGraph, file_pat
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    let input_path = &args[1];
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum RulePreset {
+    /// Boolean algebra rules used by E-Syn
+    Esyn,
+    /// E-Syn rules plus XOR, MUX and majority rules
+    Xmg,
+    /// The original Boolean algebra rule set, superseded by `esyn`
+    Basic,
+    /// Rules that only shrink the circuit
+    Simplify,
+}
 
-    // Set up timer to measure time for eqn2egraph
-    let start = Instant::now();
+impl RulePreset {
+    fn rules<N: Analysis<Prop>>(self) -> Vec<Rewrite<Prop, N>> {
+        match self {
+            RulePreset::Esyn => make_rules_esyn(),
+            RulePreset::Xmg => make_rules_xmg(),
+            RulePreset::Basic => make_rules(),
+            RulePreset::Simplify => make_rules_simplify(),
+        }
+    }
+}
 
-    // Preprocess input file
-    preprocess_file_concat(&input_path)?;
-    preprocess_file_order(&input_path)?;
-    println!("Finished preprocessing input file");
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SchedulerChoice {
+    /// Apply every rule on every iteration
+    Simple,
+    /// Exponential rule backoff, bans rules that match too often
+    Backoff,
+}
 
-    // Transfer eqn file into egraph format in egg
-    let (root_id, input_vec_id, input_vec_symbol) = process_file(input_path);
+#[derive(Parser, Debug)]
+#[command(name = "e-rewriter")]
+#[command(version)]
+#[command(about = "Rewrite an ABC eqn circuit with equality saturation", long_about = None)]
+struct Args {
+    #[arg(
+        value_name = "FILE",
+        required_unless_present_any = ["synthesize_rules", "batch"],
+        help = "Input circuit in ABC eqn format"
+    )]
+    input: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        value_name = "DIR",
+        default_value = "rewritten_circuit",
+        help = "Directory the egraph json files are written to"
+    )]
+    output_dir: PathBuf,
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["input", "proof", "report", "rule_stats", "checkpoint", "resume"],
+        help = "Rewrite every .eqn partition in DIR concurrently, each into its own subdirectory of the output directory"
+    )]
+    batch: Option<PathBuf>,
+    #[arg(short, long, help = "Threads used by --batch [default: one per core]")]
+    jobs: Option<usize>,
+    #[arg(short, long, value_enum, default_value_t = RulePreset::Esyn, help = "Rewrite rules to saturate with")]
+    rules: RulePreset,
+    #[arg(long = "rule-file", value_name = "FILE", help = "Add the rules of a rule file to the preset, can be repeated")]
+    rule_files: Vec<PathBuf>,
+    #[arg(
+        long,
+        value_name = "OUT",
+        help = "Instead of rewriting, synthesize rules not derivable from the preset and write them to OUT"
+    )]
+    synthesize_rules: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u64).range(1..=6),
+        help = "Variables of the terms enumerated by --synthesize-rules"
+    )]
+    synth_vars: u64,
+    #[arg(long, default_value_t = 5, help = "Largest term size enumerated by --synthesize-rules")]
+    synth_size: usize,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "!,*,+",
+        help = "Operators enumerated by --synthesize-rules"
+    )]
+    synth_ops: Vec<String>,
+    #[arg(short, long, default_value_t = 10, help = "Runner iteration limit")]
+    iter_limit: usize,
+    #[arg(
+        short,
+        long,
+        value_name = "SECONDS",
+        default_value_t = 10,
+        help = "Runner time limit, per partition with --batch"
+    )]
+    time_limit: u64,
+    #[arg(short, long, default_value_t = 200000000, help = "Runner egraph node limit")]
+    node_limit: usize,
+    #[arg(long, value_enum, default_value_t = SchedulerChoice::Backoff, help = "Rewrite scheduler")]
+    scheduler: SchedulerChoice,
+    #[arg(long, help = "Initial number of matches after which the backoff scheduler bans a rule [default: 1000]")]
+    match_limit: Option<usize>,
+    #[arg(long, value_name = "ITERATIONS", help = "Initial number of iterations a rule stays banned [default: 5]")]
+    ban_length: Option<usize>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "JSON file with backoff scheduler limits, globally and per rule"
+    )]
+    scheduler_config: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write matches, time and nodes added per rule and iteration to FILE as csv"
+    )]
+    rule_stats: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Check every merge against random simulation signatures and reject rules that simulate unsound"
+    )]
+    check_signatures: bool,
+    #[arg(
+        long,
+        help = "After saturation, merge classes that simulate equal and are proven equal by exhaustive simulation"
+    )]
+    fraig: bool,
+    #[arg(
+        long,
+        value_name = "INPUTS",
+        default_value_t = 12,
+        value_parser = clap::value_parser!(u64).range(1..=16),
+        help = "Largest joint cone support --fraig proves exhaustively"
+    )]
+    fraig_max_inputs: u64,
+    #[arg(
+        long,
+        help = "After saturation, add the bundled structure of each 4-input cut's NPN class to its class"
+    )]
+    npn_cuts: bool,
+    #[arg(long, default_value_t = 8, help = "Cuts kept per class by --npn-cuts")]
+    max_cuts: usize,
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        value_parser = parse_prune_factor,
+        help = "Before every iteration, drop e-nodes costing more than FACTOR times the best e-node of their class"
+    )]
+    prune_factor: Option<f64>,
+    #[arg(long, help = "Seed for the random choices made during fraiging and extraction, mixed with the circuit index in batch mode")]
+    seed: Option<u64>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write the equivalence proof of each root to FILE (json if it ends in .json, s-expressions otherwise)"
+    )]
+    proof: Option<PathBuf>,
+    #[arg(long, value_name = "FILE", help = "Write the runner report to FILE as json")]
+    report: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Save the egraph, scheduler state and iteration statistics to FILE during and after saturation"
+    )]
+    checkpoint: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "ITERATIONS",
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Iterations between two --checkpoint saves"
+    )]
+    checkpoint_every: u64,
+    #[arg(
+        long,
+        value_name = "CHECKPOINT",
+        conflicts_with = "proof",
+        help = "Continue saturating the same circuit from a checkpoint, with this run's rules and limits"
+    )]
+    resume: Option<PathBuf>,
+}
 
-    println!("root: {:?}", root_id);
-    println!("input_vec_symbol: {:?}", input_vec_symbol);
-    let duration_eqn2egraph_initial = start.elapsed();
-    println!("eqn2egraph initially finished in: {:?}.", duration_eqn2egraph_initial);
+fn parse_prune_factor(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(factor) if factor >= 1.0 => Ok(factor),
+        Ok(_) => Err("must be at least 1".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
 
+// Saturates a copy of the input egraph under analysis `N` and writes the
+// rewritten egraph files into the output directory
+fn rewrite<N>(
+    args: &Args,
+    analysis: N,
+    rules: &[Rewrite<Prop, N>],
+    input_egraph: &EGraph<Prop, ()>,
+    root_ids: &[usize],
+    output_dir: &Path,
+    rng: &mut StdRng,
+) -> Result<runner_modified::Report, Box<dyn std::error::Error>>
+where
+    N: Analysis<Prop> + RuleTracking + Serialize + Clone + 'static,
+    N::Data: Serialize,
+    EGraph<Prop, N>: Serialize + DeserializeOwned,
+{
+    let proof_path = args.proof.clone();
+    let runner_egraph = EGraph::new(analysis.clone());
+    let runner_egraph = if proof_path.is_some() {
+        runner_egraph.with_explanations_enabled()
+    } else {
+        runner_egraph
+    };
+    let runner_egraph = replay_egraph(input_egraph, runner_egraph);
+    let start = Instant::now();
+    
+    let mut runner = runner_modified::Runner::new(analysis)
+        .with_egraph(runner_egraph)
+        .with_time_limit(std::time::Duration::from_secs(args.time_limit))
+        .with_iter_limit(args.iter_limit)
+        .with_node_limit(args.node_limit);
+    runner = match args.scheduler {
+        SchedulerChoice::Simple => runner.with_scheduler(TrackRules(runner_modified::SimpleScheduler)),
+        SchedulerChoice::Backoff => {
+            let config = match &args.scheduler_config {
+                Some(path) => runner_modified::BackoffConfig::from_file(path)
+                    .map_err(|err| format!("{}: {}", path.display(), err))?,
+                None => runner_modified::BackoffConfig::default(),
+            };
+            for name in config.rules.keys() {
+                if !rules.iter().any(|rw| rw.name.as_str() == name.as_str()) {
+                    eprintln!("WARNING: scheduler config names unknown rule '{}'", name);
+                }
+            }
+            let mut scheduler = runner_modified::BackoffScheduler::default();
+            if let Some(limit) = args.match_limit.or(config.match_limit) {
+                scheduler = scheduler.with_initial_match_limit(limit);
+            }
+            if let Some(length) = args.ban_length.or(config.ban_length) {
+                scheduler = scheduler.with_ban_length(length);
+            }
+            runner.with_scheduler(TrackRules(config.apply(scheduler)))
+        }
+    };
+
+        runner.roots = root_ids.iter().cloned().map(Id::from).collect();
+        println!("runner.roots: {:?}", runner.roots);
+    if let Some(factor) = args.prune_factor {
+        runner = runner.with_cost_pruning(wight_size, factor);
+    }
+    if let Some(checkpoint_path) = &args.checkpoint {
+        runner = runner.with_checkpoint(checkpoint_path.clone(), args.checkpoint_every as usize);
+    }
+    if let Some(resume_path) = &args.resume {
+        let checkpoint: runner_modified::Checkpoint<Prop, N, ()> = runner_modified::Checkpoint::load(resume_path)
+            .map_err(|err| format!("{}: {}", resume_path.display(), err))?;
+        if checkpoint.roots != runner.roots {
+            return Err(format!("{}: checkpoint was saved for a different circuit", resume_path.display()).into());
+        }
+        println!(
+            "Resuming from {:?} after {} iterations, {} nodes",
+            resume_path,
+            checkpoint.iterations.len(),
+            checkpoint.egraph.total_size()
+        );
+        runner = runner.resume(checkpoint);
+    }
+    let mut runner_result = runner.run(rules);
+
+    let duration = start.elapsed();
+    println!(
+        "Runner stopped: {:?}. Time taken for runner: {:?}, Classes: {}, Nodes: {}, Size: {} \n\n",
+        runner_result.stop_reason,
+        duration,
+        runner_result.egraph.number_of_classes(),
+        runner_result.egraph.total_number_of_nodes(),
+        runner_result.egraph.total_size()
+    );
+       // println!("root{:?}", runner_result.roots);
+    runner_result.print_report();
+    let report = runner_result.report();
+    if let Some(report_path) = &args.report {
+        fs::write(report_path, serde_json::to_string_pretty(&report)?)?;
+        println!("Runner report written to {:?}", report_path);
+    }
+    if let Some(rule_stats_path) = &args.rule_stats {
+        runner_result.write_rule_stats_csv(rule_stats_path)?;
+        println!("Per-rule statistics written to {:?}", rule_stats_path);
+    }
+    if let Some(checkpoint_path) = &args.checkpoint {
+        runner_result.save_checkpoint(checkpoint_path)?;
+        println!("Checkpoint written to {:?}", checkpoint_path);
+    }
+    if args.npn_cuts {
+        let npn_start = Instant::now();
+        let stats = npn_rewrite(&mut runner_result.egraph, args.max_cuts);
+        println!(
+            "npn cuts: {} cuts, {} new structures merged, in {:?}",
+            stats.cuts,
+            stats.merged,
+            npn_start.elapsed()
+        );
+    }
+    if args.fraig {
+        let fraig_start = Instant::now();
+        let stats = fraig(&mut runner_result.egraph, rng.gen(), args.fraig_max_inputs as usize);
+        println!(
+            "fraig: {} candidate pairs, {} merged, {} refuted, {} skipped, in {:?}",
+            stats.candidates,
+            stats.merged,
+            stats.refuted,
+            stats.skipped,
+            fraig_start.elapsed()
+        );
+    }
+    let root = runner_result.roots.clone();
+    println!("root{:?}", root);
+
+    if let Some(proof_path) = &proof_path {
+        let extracted: Vec<RecExpr<Prop>> = {
+            let extractor = Extractor::new(&runner_result.egraph, wight_size);
+            root.iter().map(|&id| extractor.find_best(id).1).collect()
+        };
+        let inputs = input_exprs(input_egraph, &root);
+        let mut proofs = explain_roots(&mut runner_result.egraph, &root, inputs, extracted);
+        write_proofs(&mut proofs, proof_path, ProofFormat::from_path(proof_path))?;
+        println!("{} equivalence proofs written to {:?}", proofs.len(), proof_path);
+    }
+    // Save output egraph from runner (input for extraction gym)
+    let output_egraph_json_path = output_dir.join("rewritten_egraph_internal.json");
+    save_egraph_to_json(&runner_result.egraph, &output_egraph_json_path)?;
+
+    println!("egraph after runner");
+    println!("egraph node: {}", runner_result.egraph.total_size());
+    println!("egraph class: {}", runner_result.egraph.number_of_classes());
+
+    // Save serialized output egraph to json with root nodes
+    let serialized_output_egraph = egg_to_serialized_egraph(&runner_result.egraph);
+    let serialized_output_egraph_json_path = output_dir.join("rewritten_egraph_internal_serd.json");
+    save_serialized_egraph_to_json(&serialized_output_egraph, &serialized_output_egraph_json_path, root_ids)?;
+
+    println!("------------------assign cost of enode-----------------");
+    let json_string = serde_json::to_string(&serialized_output_egraph).unwrap();
+    let cost_string = process_json_prop_cost(&json_string);
+
+    let output_egraph_cost_json_path = output_dir.join("rewritten_egraph_with_weight_cost_serd.json");
+    let mut json_data: serde_json::Value = serde_json::from_str(&cost_string)?;
+    let nodes = json_data["nodes"].as_object().ok_or("Missing 'nodes' field")?;
+    let mut found_roots = HashSet::new();
+    
+    for (node_key, node_value) in nodes {
+        if let Some(eclass) = node_value.get("eclass").and_then(|e| e.as_str()) {
+            // 在匹配时将 root_ids 的元素转换为 &str
+            if root_ids.iter().map(|id| id.to_string()).any(|id| id == eclass) {
+                println!(
+                    "Found root eclass '{}' in node '{}': {:?}",
+                    eclass, node_key, node_value
+                );
+                found_roots.insert(eclass.to_string());
+            }
+        }
+    }
     
-    let mut root_ids: Vec<usize> = vec![root_id.into()];
+    // 找出未匹配的 root_ids
+    let missing_roots: Vec<_> = root_ids
+    .iter()
+    .filter(|id| !found_roots.contains(&id.to_string()))
+    .cloned()
+    .collect();
     
-    let json_file = format!("{}.json", input_path);
-
-    // format with base name+json
-    // let path = Path::new(input_path);
-    // let Some(stem) = path.file_stem() else {
-    //     panic!("Invalid input file path")
-    // };
-    // let json_file =  format!("{}.json", stem.to_string_lossy());
-    // println!("json_file: {}", json_file);
-
-    // Transfer egg::egraph symbol language's json into my defined language's json
-    let modified_json_file = process_json_prop(&json_file);
-    //let modified_json_file = process_json_prop_prallel(&json_file);
-    let duration_eqn2egraph_handling_base_language = start.elapsed();
-    let converted_json_data = fs::read_to_string(&modified_json_file).expect("Unable to read the JSON file");
-    let mut input_egraph: egg::EGraph<Prop, ()> = serde_json::from_str(&converted_json_data).unwrap();
+    println!("Missing root eclasses: {:?}", missing_roots);
+
+
+    let serialized_input_egraph_json_content = fs::read_to_string(output_dir.join("egraph2egraph_serd.json"))?;
+    let serialized_input_egraph: Value = serde_json::from_str(&serialized_input_egraph_json_content)?;
+      let nodes = json_data["nodes"]
+    .as_object_mut()
+    .ok_or("Missing 'nodes' field in output JSON")?;
+
+    // 遍历 root_ids，检查是否在 serialized_input_egraph 的 nodes 中
+    if let Some(input_nodes) = serialized_input_egraph["nodes"].as_object() {
+    for root_id in root_ids {
+        // 将 root_id 转换为字符串，并加上 ".0"
+        let root_id_str = format!("{}.0", root_id);
+        if let Some(node) = input_nodes.get(&root_id_str) {
+            // 将整个节点对象插入到当前 JSON 的 nodes 中
+            nodes.insert(root_id_str.clone(), node.clone());
+            println!("Copied node with root '{}' to output JSON.", root_id_str);
+        }
+    }
+    }
+
+    // 添加 root_eclasses 字段
+    json_data["root_eclasses"] = Value::Array(
+    root_ids
+        .iter()
+        .map(|id| Value::String(id.to_string()))
+        .collect(),
+    );
+
+    // 写入文件，保持格式
+    let file = File::create(&output_egraph_cost_json_path)?;
+    let writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(writer, &json_data)?;
+
+    println!("done");
+
+    Ok(report)
+}
+
+// Enumerates small terms and writes the rules the preset and rule files can't derive
+fn synthesize(args: &Args, out: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut known = args.rules.rules();
+    for path in &args.rule_files {
+        known.extend(read_rule_file(path).map_err(|err| format!("{}: {}", path.display(), err))?);
+    }
+    let config = SynthConfig {
+        vars: args.synth_vars as usize,
+        max_size: args.synth_size,
+        ops: args.synth_ops.clone(),
+        iter_limit: args.iter_limit,
+        node_limit: args.node_limit,
+        time_limit: std::time::Duration::from_secs(args.time_limit),
+    };
+    let start = Instant::now();
+    let specs = synthesize_rules(known, &config)?;
+    let header = format!(
+        "Synthesized by e-rewriter over {} variables, terms up to size {}, operators {}\nNot derivable from the {:?} rules",
+        config.vars,
+        config.max_size,
+        config.ops.join(" "),
+        args.rules
+    );
+    write_rule_file(out, &header, &specs)?;
+    println!("{} rules synthesized in {:?}, written to {:?}", specs.len(), start.elapsed(), out);
+    Ok(())
+}
+
+// Preset rules plus the rule files, built once and shared by every circuit
+fn load_rules<N: Analysis<Prop>>(args: &Args) -> Result<Vec<Rewrite<Prop, N>>, Box<dyn std::error::Error>> {
+    let mut rules = args.rules.rules();
+    for path in &args.rule_files {
+        rules.extend(read_rule_file(path).map_err(|err| format!("{}: {}", path.display(), err))?);
+    }
+    if args.check_signatures {
+        let unsound = unsound_rules(&rules, args.seed.unwrap_or(0));
+        if !unsound.is_empty() {
+            return Err(format!("rules fail random simulation: {:?}", unsound).into());
+        }
+    }
+    Ok(rules)
+}
+
+// Parses one eqn circuit, writes its input egraph files and rewrites it,
+// everything goes to `output_dir`
+fn process_circuit<N>(
+    args: &Args,
+    analysis: N,
+    rules: &[Rewrite<Prop, N>],
+    input: &Path,
+    output_dir: &Path,
+    rng: &mut StdRng,
+) -> Result<runner_modified::Report, Box<dyn std::error::Error>>
+where
+    N: Analysis<Prop> + RuleTracking + Serialize + Clone + 'static,
+    N::Data: Serialize,
+    EGraph<Prop, N>: Serialize + DeserializeOwned,
+{
+    fs::create_dir_all(output_dir)?;
+
+    // Set up timer to measure time for eqn2egraph
+    let start = Instant::now();
+
+    // Parse the eqn file straight into the Prop egraph, the input file is left untouched
+    let eqn = read_eqn(input).map_err(|err| format!("{}: {}", input.display(), err))?;
+    println!("Finished parsing input file");
+
+    let mut input_egraph: egg::EGraph<Prop, ()> = EGraph::default();
+    let eqn_ids = eqn
+        .add_to_egraph(&mut input_egraph)
+        .map_err(|err| format!("{}: {}", input.display(), err))?;
     input_egraph.rebuild();//eqn2egraph finished
 
+    println!("root: {:?}", eqn_ids.root);
+    println!("inputs: {:?}", eqn.inputs);
+    println!("outputs: {:?}", eqn.outputs);
+
+    let root_ids: Vec<usize> = vec![eqn_ids.root.into()];
+
     // print the time taken for eqn2egraph
     let eqn2egraph_all_duration = start.elapsed();
-    println!("eqn2egraph with handling of base language finished in: {:?}.", duration_eqn2egraph_handling_base_language - duration_eqn2egraph_initial);
     println!("eqn2egraph finished in: {:?}.", eqn2egraph_all_duration);
 
     // Save input_egraph into json file
-    let input_egraph_json_path = env::current_dir().unwrap().join("rewritten_circuit/eqn2egraph.json"); 
+    let input_egraph_json_path = output_dir.join("eqn2egraph.json"); 
     save_egraph_to_json(&input_egraph, &input_egraph_json_path)?;
 
     // Read from json file and print info
@@ -124,126 +577,166 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Transfer egg::egraph to serialized_egraph and save it into json file
     let serialized_input_egraph = egg_to_serialized_egraph(&converted_egg);
-    let serialized_input_egraph_json_path = env::current_dir().unwrap().join("rewritten_circuit/egraph2egraph_serd.json"); // egraph to serialized_egraph finished
+    let serialized_input_egraph_json_path = output_dir.join("egraph2egraph_serd.json"); // egraph to serialized_egraph finished
     save_serialized_egraph_to_json(&serialized_input_egraph, &serialized_input_egraph_json_path, &root_ids)?;
 
 
     // Rewrite time!
-    {
-        let runner_iteration_limit = env::args()
-            .nth(2)
-            .unwrap_or("10".to_string())
-            .parse()
-            .unwrap_or(20);
-        let egraph_node_limit = 200000000;
-        let start = Instant::now();
-        
-        let mut runner = Runner::default()
-            .with_explanations_enabled()
-            .with_egraph(input_egraph.clone())
-            .with_time_limit(std::time::Duration::from_secs(10))
-            .with_iter_limit(runner_iteration_limit)
-            .with_node_limit(egraph_node_limit);
-
-            runner.roots = root_ids.iter().cloned().map(Id::from).collect();
-            println!("runner.roots: {:?}", runner.roots);
-        let runner_result = runner.run(&make_rules_esyn());
-
-        let duration = start.elapsed();
-        println!(
-            "Runner stopped: {:?}. Time taken for runner: {:?}, Classes: {}, Nodes: {}, Size: {} \n\n",
-            runner_result.stop_reason,
-            duration,
-            runner_result.egraph.number_of_classes(),
-            runner_result.egraph.total_number_of_nodes(),
-            runner_result.egraph.total_size()
-        );
-       // println!("root{:?}", runner_result.roots);
-        runner_result.print_report();
-        let root = runner_result.roots;
-        println!("root{:?}", root);
-        // Save output egraph from runner (input for extraction gym)
-        let output_egraph_json_path = env::current_dir().unwrap().join("rewritten_circuit/rewritten_egraph_internal.json");
-        save_egraph_to_json(&runner_result.egraph, &output_egraph_json_path)?;
-
-        println!("egraph after runner");
-        println!("egraph node: {}", runner_result.egraph.total_size());
-        println!("egraph class: {}", runner_result.egraph.number_of_classes());
-
-        // Save serialized output egraph to json with root nodes
-        let serialized_output_egraph = egg_to_serialized_egraph(&runner_result.egraph);
-        let serialized_output_egraph_json_path = env::current_dir().unwrap().join("rewritten_circuit/rewritten_egraph_internal_serd.json");
-        save_serialized_egraph_to_json(&serialized_output_egraph, &serialized_output_egraph_json_path, &root_ids)?;
-
-        println!("------------------assign cost of enode-----------------");
-        let json_string = serde_json::to_string(&serialized_output_egraph).unwrap();
-        let cost_string = process_json_prop_cost(&json_string);
-
-        let output_egraph_cost_json_path = env::current_dir().unwrap().join("rewritten_circuit/rewritten_egraph_with_weight_cost_serd.json");
-        let mut json_data: serde_json::Value = serde_json::from_str(&cost_string)?;
-        let nodes = json_data["nodes"].as_object().ok_or("Missing 'nodes' field")?;
-        let mut found_roots = HashSet::new();
-    
-        for (node_key, node_value) in nodes {
-            if let Some(eclass) = node_value.get("eclass").and_then(|e| e.as_str()) {
-                // 在匹配时将 root_ids 的元素转换为 &str
-                if root_ids.iter().map(|id| id.to_string()).any(|id| id == eclass) {
-                    println!(
-                        "Found root eclass '{}' in node '{}': {:?}",
-                        eclass, node_key, node_value
-                    );
-                    found_roots.insert(eclass.to_string());
-                }
-            }
-        }
-    
-        // 找出未匹配的 root_ids
-        let missing_roots: Vec<_> = root_ids
-        .iter()
-        .filter(|id| !found_roots.contains(&id.to_string()))
-        .cloned()
-        .collect();
-    
-        println!("Missing root eclasses: {:?}", missing_roots);
-
+    rewrite(
+        args,
+        analysis,
+        rules,
+        &input_egraph,
+        &root_ids,
+        output_dir,
+        rng,
+    )
+}
 
-        let serialized_input_egraph_json_path = env::current_dir()
-        .unwrap()
-        .join("rewritten_circuit/egraph2egraph_serd.json");
-        let serialized_input_egraph_json_content = fs::read_to_string(serialized_input_egraph_json_path)?;
-        let serialized_input_egraph: Value = serde_json::from_str(&serialized_input_egraph_json_content)?;
-      let nodes = json_data["nodes"]
-        .as_object_mut()
-        .ok_or("Missing 'nodes' field in output JSON")?;
+// One line of the batch manifest
+#[derive(Serialize)]
+struct PartitionResult {
+    input: PathBuf,
+    output_dir: PathBuf,
+    seconds: f64,
+    report: Option<runner_modified::Report>,
+    error: Option<String>,
+}
 
-    // 遍历 root_ids，检查是否在 serialized_input_egraph 的 nodes 中
-    if let Some(input_nodes) = serialized_input_egraph["nodes"].as_object() {
-        for root_id in &root_ids {
-            // 将 root_id 转换为字符串，并加上 ".0"
-            let root_id_str = format!("{}.0", root_id);
-            if let Some(node) = input_nodes.get(&root_id_str) {
-                // 将整个节点对象插入到当前 JSON 的 nodes 中
-                nodes.insert(root_id_str.clone(), node.clone());
-                println!("Copied node with root '{}' to output JSON.", root_id_str);
-            }
-        }
+// Rewrites every eqn file of `dir` on a rayon pool, each into its own
+// subdirectory of the output directory, and writes manifest.json
+fn run_batch<N>(args: &Args, analysis: N, rules: &[Rewrite<Prop, N>], dir: &Path) -> Result<(), Box<dyn std::error::Error>>
+where
+    N: Analysis<Prop> + RuleTracking + Serialize + Clone + Send + Sync + 'static,
+    N::Data: Serialize,
+    EGraph<Prop, N>: Serialize + DeserializeOwned,
+{
+    let mut inputs: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    inputs.retain(|path| path.extension().map_or(false, |ext| ext == "eqn"));
+    inputs.sort();
+    if inputs.is_empty() {
+        return Err(format!("{}: no .eqn files", dir.display()).into());
     }
+    fs::create_dir_all(&args.output_dir)?;
 
-    // 添加 root_eclasses 字段
-    json_data["root_eclasses"] = Value::Array(
-        root_ids
-            .iter()
-            .map(|id| Value::String(id.to_string()))
-            .collect(),
+    let mut pool = rayon::ThreadPoolBuilder::new();
+    if let Some(jobs) = args.jobs {
+        pool = pool.num_threads(jobs);
+    }
+    let pool = pool.build()?;
+    let start = Instant::now();
+    let seed = args.seed.unwrap_or(0);
+    let results: Vec<PartitionResult> = pool.install(|| {
+        inputs
+            .par_iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let stem = input.file_stem().unwrap_or_default();
+                let output_dir = args.output_dir.join(stem);
+                let partition_start = Instant::now();
+                let result = process_circuit(args, analysis.clone(), rules, input, &output_dir, &mut task_rng(seed, index))
+                    .map_err(|err| err.to_string());
+                if let Err(err) = &result {
+                    eprintln!("ERROR: {}: {}", input.display(), err);
+                }
+                PartitionResult {
+                    input: input.clone(),
+                    output_dir,
+                    seconds: partition_start.elapsed().as_secs_f64(),
+                    report: result.as_ref().ok().cloned(),
+                    error: result.err(),
+                }
+            })
+            .collect()
+    });
+
+    let failed = results.iter().filter(|result| result.error.is_some()).count();
+    let manifest_path = args.output_dir.join("manifest.json");
+    let manifest = json!({
+        "partitions": results,
+        "failed": failed,
+        "seconds": start.elapsed().as_secs_f64(),
+    });
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+    println!(
+        "Rewrote {} of {} partitions in {:?}, manifest written to {:?}",
+        results.len() - failed,
+        results.len(),
+        start.elapsed(),
+        manifest_path
     );
+    Ok(())
+}
 
-    // 写入文件，保持格式
-    let file = File::create(&output_egraph_cost_json_path)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &json_data)?;
+fn run<N>(args: &Args, analysis: N) -> Result<(), Box<dyn std::error::Error>>
+where
+    N: Analysis<Prop> + RuleTracking + Serialize + Clone + Send + Sync + 'static,
+    N::Data: Serialize,
+    EGraph<Prop, N>: Serialize + DeserializeOwned,
+{
+    let rules = load_rules(args)?;
+    if let Some(dir) = &args.batch {
+        return run_batch(args, analysis, &rules, dir);
+    }
+    let input = args.input.as_ref().expect("required unless synthesizing rules or in batch mode");
+    process_circuit(args, analysis, &rules, input, &args.output_dir, &mut task_rng(args.seed.unwrap_or(0), 0))?;
+    Ok(())
+}
 
-        println!("done");
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    if let Some(out) = &args.synthesize_rules {
+        return synthesize(&args, out);
     }
 
-    Ok(())
-}
\ No newline at end of file
+    if args.check_signatures {
+        run(&args, Simulation::new(args.seed.unwrap_or(0)))
+    } else {
+        run(&args, ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_manifest_records_every_outcome() {
+        let dir = std::env::temp_dir().join(format!("e-rewriter-batch-{}", std::process::id()));
+        let input_dir = dir.join("input");
+        let output_dir = dir.join("output");
+        fs::create_dir_all(&input_dir).unwrap();
+        fs::write(input_dir.join("good.eqn"), "INORDER = a b c;\nOUTORDER = f;\nf = a * b + a * c;\n").unwrap();
+        fs::write(input_dir.join("malformed.eqn"), "INORDER = a b;\nOUTORDER = f;\nf = a * ;\n").unwrap();
+        fs::write(input_dir.join("notes.txt"), "not a circuit").unwrap();
+
+        let args = Args::parse_from([
+            "e-rewriter".as_ref(),
+            "--batch".as_ref(),
+            input_dir.as_os_str(),
+            "--output-dir".as_ref(),
+            output_dir.as_os_str(),
+            "--iter-limit".as_ref(),
+            "2".as_ref(),
+        ]);
+        run(&args, ()).unwrap();
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.join("manifest.json")).unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(manifest["failed"], 1);
+        let partitions = manifest["partitions"].as_array().unwrap();
+        assert_eq!(partitions.len(), 2);
+        let (good, malformed) = (&partitions[0], &partitions[1]);
+        assert_eq!(good["input"], input_dir.join("good.eqn").to_str().unwrap());
+        assert_eq!(good["output_dir"], output_dir.join("good").to_str().unwrap());
+        assert!(good["error"].is_null());
+        assert_eq!(good["report"]["iterations"], 2);
+        assert_eq!(malformed["input"], input_dir.join("malformed.eqn").to_str().unwrap());
+        assert!(malformed["report"].is_null());
+        assert!(malformed["error"].as_str().unwrap().contains("3:9: expected an operand"));
+    }
+}
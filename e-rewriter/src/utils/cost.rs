@@ -66,6 +66,9 @@ impl CostFunction<Prop> for wight_size{
             "!" => 1 ,
             "+" => 2,
             "*"=> 6,
+            "^" => 14,
+            "mux" => 14,
+            "maj" => 20,
             //"&" => 0.0 as  f32,
             _=> 1 
         };
@@ -104,6 +107,7 @@ impl CostFunction<Prop> for wight_depth{
             Not(a) => 2 + enode.fold(0, |max, id| max.max(costs(id))),  // 将常量改为浮点数
             And([a,b]) => 4  + enode.fold(0, |max, id| max.max(costs(id))),  // 将常量改为浮点数
             Or([a,b]) =>  6 + enode.fold(0, |max, id| max.max(costs(id))),  // 将常量改为浮点数
+            Xor(_) | Mux(_) | Maj(_) => 8 + enode.fold(0, |max, id| max.max(costs(id))),
             _=>0  + enode.fold(0, |max, id| max.max(costs(id))),  
 
 
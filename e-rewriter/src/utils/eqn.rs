@@ -0,0 +1,513 @@
+use egg::*;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::utils::language::*;
+
+// Tokenizer, parser and egraph builder for ABC's eqn format:
+//
+//   # comment
+//   INORDER = a b c;
+//   OUTORDER = f;
+//   new_n5_ = a * !b;
+//   f = (new_n5_ + c) ^ 1;
+//
+// Precedence from tightest to loosest is `!`, `*` (or `&`), `^`, `+` (or `|`).
+// Statements end with `;` and may span several lines.
+
+#[derive(Debug, Error)]
+pub enum EqnError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{line}:{column}: {message}")]
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+fn syntax_error<T>(line: usize, column: usize, message: impl Into<String>) -> Result<T, EqnError> {
+    Err(EqnError::Syntax {
+        line,
+        column,
+        message: message.into(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Ident(String),
+    Assign,
+    Semicolon,
+    LParen,
+    RParen,
+    Not,
+    And,
+    Or,
+    Xor,
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+fn is_ident_char(c: char) -> bool {
+    !c.is_whitespace() && !"=;()!*&+|^#".contains(c)
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, EqnError> {
+    let mut tokens = Vec::new();
+    let mut chars = src.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+
+    while let Some(&c) = chars.peek() {
+        let (start_line, start_column) = (line, column);
+        let kind = match c {
+            '\n' => {
+                chars.next();
+                line += 1;
+                column = 1;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+                column += 1;
+                continue;
+            }
+            '#' => {
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                    column += 1;
+                }
+                continue;
+            }
+            '=' => TokenKind::Assign,
+            ';' => TokenKind::Semicolon,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '!' => TokenKind::Not,
+            '*' | '&' => TokenKind::And,
+            '+' | '|' => TokenKind::Or,
+            '^' => TokenKind::Xor,
+            _ => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !is_ident_char(c) {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                    column += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Ident(name),
+                    line: start_line,
+                    column: start_column,
+                });
+                continue;
+            }
+        };
+        chars.next();
+        column += 1;
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        line,
+        column,
+    });
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Var {
+        name: String,
+        line: usize,
+        column: usize,
+    },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn for_each_var<'a>(&'a self, f: &mut impl FnMut(&'a str)) {
+        match self {
+            Expr::Var { name, .. } => f(name),
+            Expr::Not(a) => a.for_each_var(f),
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Xor(a, b) => {
+                a.for_each_var(f);
+                b.for_each_var(f);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Equation {
+    pub name: String,
+    pub expr: Expr,
+    pub line: usize,
+    pub column: usize,
+}
+
+// Line and column in the source, both starting at 1
+pub type Position = (usize, usize);
+
+#[derive(Debug, Clone, Default)]
+pub struct Eqn {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    // Line and column of every name in OUTORDER, parallel to `outputs`
+    pub output_positions: Vec<Position>,
+    // Line and column of the OUTORDER keyword
+    pub outorder: Position,
+    pub equations: Vec<Equation>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind, what: &str) -> Result<Token, EqnError> {
+        let token = self.next();
+        if token.kind == kind {
+            Ok(token)
+        } else {
+            syntax_error(token.line, token.column, format!("expected {}, found {}", what, describe(&token.kind)))
+        }
+    }
+
+    fn parse_eqn(&mut self) -> Result<Eqn, EqnError> {
+        let mut eqn = Eqn::default();
+        let (mut seen_inorder, mut seen_outorder) = (false, false);
+
+        loop {
+            let token = self.next();
+            let name = match token.kind {
+                TokenKind::Eof if !seen_outorder => return syntax_error(token.line, token.column, "missing OUTORDER"),
+                TokenKind::Eof => break,
+                TokenKind::Ident(name) => name,
+                other => {
+                    return syntax_error(token.line, token.column, format!("expected a statement, found {}", describe(&other)))
+                }
+            };
+            self.expect(TokenKind::Assign, "`=`")?;
+
+            match name.as_str() {
+                "INORDER" | "OUTORDER" => {
+                    let seen = if name == "INORDER" { &mut seen_inorder } else { &mut seen_outorder };
+                    if *seen {
+                        return syntax_error(token.line, token.column, format!("duplicate {}", name));
+                    }
+                    *seen = true;
+                    let (names, positions) = self.parse_name_list()?;
+                    if name == "INORDER" {
+                        eqn.inputs = names;
+                    } else {
+                        eqn.outputs = names;
+                        eqn.output_positions = positions;
+                        eqn.outorder = (token.line, token.column);
+                    }
+                }
+                _ => {
+                    let expr = self.parse_or()?;
+                    self.expect(TokenKind::Semicolon, "`;` or an operator")?;
+                    eqn.equations.push(Equation {
+                        name,
+                        expr,
+                        line: token.line,
+                        column: token.column,
+                    });
+                }
+            }
+        }
+
+        Ok(eqn)
+    }
+
+    // Names up to the closing `;`, with the line and column of each
+    fn parse_name_list(&mut self) -> Result<(Vec<String>, Vec<Position>), EqnError> {
+        let (mut names, mut positions) = (Vec::new(), Vec::new());
+        loop {
+            let token = self.next();
+            match token.kind {
+                TokenKind::Ident(name) => {
+                    names.push(name);
+                    positions.push((token.line, token.column));
+                }
+                TokenKind::Semicolon => return Ok((names, positions)),
+                other => {
+                    return syntax_error(token.line, token.column, format!("expected a signal name or `;`, found {}", describe(&other)))
+                }
+            }
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, EqnError> {
+        let mut lhs = self.parse_xor()?;
+        while self.peek().kind == TokenKind::Or {
+            self.next();
+            let rhs = self.parse_xor()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_xor(&mut self) -> Result<Expr, EqnError> {
+        let mut lhs = self.parse_and()?;
+        while self.peek().kind == TokenKind::Xor {
+            self.next();
+            let rhs = self.parse_and()?;
+            lhs = Expr::Xor(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, EqnError> {
+        let mut lhs = self.parse_unary()?;
+        while self.peek().kind == TokenKind::And {
+            self.next();
+            let rhs = self.parse_unary()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, EqnError> {
+        let token = self.next();
+        match token.kind {
+            TokenKind::Not => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            TokenKind::LParen => {
+                let expr = self.parse_or()?;
+                self.expect(TokenKind::RParen, "`)`")?;
+                Ok(expr)
+            }
+            TokenKind::Ident(name) => Ok(Expr::Var {
+                name,
+                line: token.line,
+                column: token.column,
+            }),
+            other => syntax_error(token.line, token.column, format!("expected an operand, found {}", describe(&other))),
+        }
+    }
+}
+
+fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Ident(name) => format!("`{}`", name),
+        TokenKind::Assign => "`=`".to_string(),
+        TokenKind::Semicolon => "`;`".to_string(),
+        TokenKind::LParen => "`(`".to_string(),
+        TokenKind::RParen => "`)`".to_string(),
+        TokenKind::Not => "`!`".to_string(),
+        TokenKind::And => "`*`".to_string(),
+        TokenKind::Or => "`+`".to_string(),
+        TokenKind::Xor => "`^`".to_string(),
+        TokenKind::Eof => "end of file".to_string(),
+    }
+}
+
+pub fn parse_eqn(src: &str) -> Result<Eqn, EqnError> {
+    let tokens = tokenize(src)?;
+    Parser { tokens, pos: 0 }.parse_eqn()
+}
+
+pub fn read_eqn(path: impl AsRef<Path>) -> Result<Eqn, EqnError> {
+    parse_eqn(&fs::read_to_string(path)?)
+}
+
+// Egraph ids of a circuit added by `Eqn::add_to_egraph`
+#[derive(Debug, Clone)]
+pub struct EqnIds {
+    // Single output, or the left-nested `&` concatenation of all outputs in OUTORDER order
+    pub root: Id,
+    pub inputs: Vec<Id>,
+    pub outputs: Vec<Id>,
+}
+
+impl Eqn {
+    // Equation indices in an order where every equation comes after the ones it refers to
+    fn topological_order(&self, defined: &HashMap<&str, usize>) -> Result<Vec<usize>, EqnError> {
+        let mut pending = vec![0usize; self.equations.len()];
+        let mut users: Vec<Vec<usize>> = vec![Vec::new(); self.equations.len()];
+        for (i, equation) in self.equations.iter().enumerate() {
+            equation.expr.for_each_var(&mut |name| {
+                if let Some(&dep) = defined.get(name) {
+                    pending[i] += 1;
+                    users[dep].push(i);
+                }
+            });
+        }
+
+        let mut ready: Vec<usize> = (0..self.equations.len()).filter(|&i| pending[i] == 0).rev().collect();
+        let mut order = Vec::with_capacity(self.equations.len());
+        while let Some(i) = ready.pop() {
+            order.push(i);
+            for &user in users[i].iter().rev() {
+                pending[user] -= 1;
+                if pending[user] == 0 {
+                    ready.push(user);
+                }
+            }
+        }
+
+        if let Some(i) = (0..self.equations.len()).find(|&i| pending[i] > 0) {
+            let equation = &self.equations[i];
+            return syntax_error(equation.line, equation.column, format!("`{}` depends on itself", equation.name));
+        }
+        Ok(order)
+    }
+
+    pub fn add_to_egraph<N: Analysis<Prop>>(&self, egraph: &mut EGraph<Prop, N>) -> Result<EqnIds, EqnError> {
+        let mut defined: HashMap<&str, usize> = HashMap::new();
+        for (i, equation) in self.equations.iter().enumerate() {
+            if self.inputs.contains(&equation.name) {
+                return syntax_error(equation.line, equation.column, format!("input `{}` is assigned", equation.name));
+            }
+            if defined.insert(equation.name.as_str(), i).is_some() {
+                return syntax_error(equation.line, equation.column, format!("`{}` is assigned twice", equation.name));
+            }
+        }
+
+        let mut vars: HashMap<&str, Id> = HashMap::new();
+        let inputs: Vec<Id> = self
+            .inputs
+            .iter()
+            .map(|name| {
+                let id = egraph.add(Prop::Symbol(name.as_str().into()));
+                vars.insert(name.as_str(), id);
+                id
+            })
+            .collect();
+
+        for i in self.topological_order(&defined)? {
+            let equation = &self.equations[i];
+            let id = add_expr(egraph, &vars, &equation.expr)?;
+            vars.insert(equation.name.as_str(), id);
+        }
+
+        let mut outputs = Vec::with_capacity(self.outputs.len());
+        for (name, &(line, column)) in self.outputs.iter().zip(&self.output_positions) {
+            match vars.get(name.as_str()) {
+                Some(&id) => outputs.push(id),
+                None => return syntax_error(line, column, format!("output `{}` is never assigned", name)),
+            }
+        }
+
+        let root = match outputs.split_first() {
+            Some((&first, rest)) => rest
+                .iter()
+                .fold(first, |acc, &output| egraph.add(Prop::Concat([acc, output]))),
+            None => return syntax_error(self.outorder.0, self.outorder.1, "OUTORDER lists no outputs"),
+        };
+
+        Ok(EqnIds { root, inputs, outputs })
+    }
+}
+
+fn add_expr<N: Analysis<Prop>>(
+    egraph: &mut EGraph<Prop, N>,
+    vars: &HashMap<&str, Id>,
+    expr: &Expr,
+) -> Result<Id, EqnError> {
+    Ok(match expr {
+        Expr::Var { name, line, column } => match vars.get(name.as_str()) {
+            Some(&id) => id,
+            None if name == "0" || name == "1" => egraph.add(Prop::Symbol(name.as_str().into())),
+            None => return syntax_error(*line, *column, format!("`{}` is neither an input nor assigned", name)),
+        },
+        Expr::Not(a) => {
+            let a = add_expr(egraph, vars, a)?;
+            egraph.add(Prop::Not(a))
+        }
+        Expr::And(a, b) => {
+            let (a, b) = (add_expr(egraph, vars, a)?, add_expr(egraph, vars, b)?);
+            egraph.add(Prop::And([a, b]))
+        }
+        Expr::Or(a, b) => {
+            let (a, b) = (add_expr(egraph, vars, a)?, add_expr(egraph, vars, b)?);
+            egraph.add(Prop::Or([a, b]))
+        }
+        Expr::Xor(a, b) => {
+            let (a, b) = (add_expr(egraph, vars, a)?, add_expr(egraph, vars, b)?);
+            egraph.add(Prop::Xor([a, b]))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(expr: &Expr) -> String {
+        match expr {
+            Expr::Var { name, .. } => name.clone(),
+            Expr::Not(a) => format!("(! {})", show(a)),
+            Expr::And(a, b) => format!("(* {} {})", show(a), show(b)),
+            Expr::Or(a, b) => format!("(+ {} {})", show(a), show(b)),
+            Expr::Xor(a, b) => format!("(^ {} {})", show(a), show(b)),
+        }
+    }
+
+    fn error(src: &str) -> String {
+        let mut egraph = EGraph::<Prop, ()>::default();
+        match parse_eqn(src).and_then(|eqn| eqn.add_to_egraph(&mut egraph)) {
+            Ok(_) => panic!("{:?} was accepted", src),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        let eqn = parse_eqn("INORDER = a b c d;\nOUTORDER = f g;\nf = a + !b * c ^ d;\ng = !(a | b) & c + d;\n").unwrap();
+        assert_eq!(show(&eqn.equations[0].expr), "(+ a (^ (* (! b) c) d))");
+        assert_eq!(show(&eqn.equations[1].expr), "(+ (* (! (+ a b)) c) d)");
+
+        let mut egraph = EGraph::<Prop, ()>::default();
+        let ids = eqn.add_to_egraph(&mut egraph).unwrap();
+        assert_eq!(ids.inputs.len(), 4);
+        assert_eq!(ids.root, egraph.lookup(Prop::Concat([ids.outputs[0], ids.outputs[1]])).unwrap());
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error("INORDER = a;\nOUTORDER = f;\nf = a * ;\n"), "3:9: expected an operand, found `;`");
+        assert_eq!(error("INORDER = a;\nOUTORDER = f g;\nf = a;\n"), "2:14: output `g` is never assigned");
+        assert_eq!(error("INORDER = a;\n  OUTORDER = ;\n"), "2:3: OUTORDER lists no outputs");
+        assert_eq!(error("INORDER = a;\nf = a;\n"), "3:1: missing OUTORDER");
+        assert_eq!(error("INORDER = a;\nOUTORDER = f;\nf = g;\ng = !f;\n"), "3:1: `f` depends on itself");
+    }
+}
@@ -0,0 +1,253 @@
+use egg::*;
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::utils::language::*;
+
+// Proof output format, chosen from the file extension of the output path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofFormat {
+    Json,
+    Sexp,
+}
+
+impl ProofFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => ProofFormat::Json,
+            _ => ProofFormat::Sexp,
+        }
+    }
+}
+
+// Equivalence proof between the input expression of one root and the expression extracted for it
+pub struct RootProof {
+    pub root: Id,
+    pub input: RecExpr<Prop>,
+    pub extracted: RecExpr<Prop>,
+    pub explanation: Explanation<Prop>,
+}
+
+// egg only records explanations for nodes added after explanations are enabled,
+// and a different analysis needs its data computed from scratch, so the runner
+// can't start from the deserialized input egraph. Replay it into `egraph` instead.
+// The input egraph is the hash-consed circuit straight out of eqn2egraph (one node
+// per class, children always have smaller ids), so adding the classes in id order
+// gives the same ids.
+pub fn replay_egraph<N: Analysis<Prop>>(input_egraph: &EGraph<Prop, ()>, mut egraph: EGraph<Prop, N>) -> EGraph<Prop, N> {
+    let mut classes: Vec<_> = input_egraph.classes().collect();
+    classes.sort_by_key(|class| class.id);
+
+    for class in classes {
+        for node in &class.nodes {
+            let id = egraph.add(node.clone());
+            assert_eq!(
+                id, class.id,
+                "input egraph is not a plain DAG, class {} was replayed as {}",
+                class.id, id
+            );
+        }
+    }
+    egraph.rebuild();
+    egraph
+}
+
+// The original output expression of each root, taken from the input egraph before rewriting
+pub fn input_exprs(input_egraph: &EGraph<Prop, ()>, roots: &[Id]) -> Vec<RecExpr<Prop>> {
+    let extractor = Extractor::new(input_egraph, egg::AstSize);
+    roots
+        .iter()
+        .map(|&root| extractor.find_best(root).1)
+        .collect()
+}
+
+pub fn explain_roots<N: Analysis<Prop>>(
+    egraph: &mut EGraph<Prop, N>,
+    roots: &[Id],
+    inputs: Vec<RecExpr<Prop>>,
+    extracted: Vec<RecExpr<Prop>>,
+) -> Vec<RootProof> {
+    assert_eq!(roots.len(), inputs.len());
+    assert_eq!(roots.len(), extracted.len());
+
+    roots
+        .iter()
+        .zip(inputs.into_iter().zip(extracted))
+        .map(|(&root, (input, extracted))| {
+            let explanation = egraph.explain_equivalence(&input, &extracted);
+            RootProof {
+                root,
+                input,
+                extracted,
+                explanation,
+            }
+        })
+        .collect()
+}
+
+// The rewrite applied between the previous step and `term`, if any, with its direction
+fn step_rule(term: &FlatTerm<Prop>) -> Option<(Symbol, &'static str)> {
+    if let Some(rule) = term.forward_rule {
+        return Some((rule, "forward"));
+    }
+    if let Some(rule) = term.backward_rule {
+        return Some((rule, "backward"));
+    }
+    term.children.iter().find_map(step_rule)
+}
+
+fn proof_to_json(proof: &mut RootProof) -> Value {
+    let steps: Vec<Value> = proof
+        .explanation
+        .make_flat_explanation()
+        .iter()
+        .map(|term| {
+            let (rule, direction) = match step_rule(term) {
+                Some((rule, direction)) => (Value::String(rule.to_string()), json!(direction)),
+                None => (Value::Null, Value::Null),
+            };
+            json!({
+                "rule": rule,
+                "direction": direction,
+                "term": term.get_string(),
+            })
+        })
+        .collect();
+
+    json!({
+        "root": proof.root.to_string(),
+        "input": proof.input.to_string(),
+        "extracted": proof.extracted.to_string(),
+        "length": steps.len().saturating_sub(1),
+        "steps": steps,
+    })
+}
+
+fn proof_to_sexp(proof: &mut RootProof) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("; root {}\n", proof.root));
+    out.push_str(&format!("; input: {}\n", proof.input));
+    out.push_str(&format!("; extracted: {}\n", proof.extracted));
+    for line in proof.explanation.get_flat_strings() {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+pub fn write_proofs(proofs: &mut [RootProof], path: &Path, format: ProofFormat) -> io::Result<()> {
+    let contents = match format {
+        ProofFormat::Json => {
+            let proofs: Vec<Value> = proofs.iter_mut().map(proof_to_json).collect();
+            serde_json::to_string_pretty(&json!({ "proofs": proofs }))?
+        }
+        ProofFormat::Sexp => proofs
+            .iter_mut()
+            .map(proof_to_sexp)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::runner_modified::{Runner, SimpleScheduler};
+
+    fn proofs() -> Vec<RootProof> {
+        let mut input_egraph = EGraph::<Prop, ()>::default();
+        let root = input_egraph.add_expr(&"(! (! (* a (+ b 0))))".parse().unwrap());
+        input_egraph.rebuild();
+        let egraph = replay_egraph(&input_egraph, EGraph::default().with_explanations_enabled());
+
+        let mut runner = Runner::<Prop, ()>::new(())
+            .with_egraph(egraph)
+            .with_iter_limit(5)
+            .with_scheduler(SimpleScheduler)
+            .run(&make_rules::<()>());
+        let extracted = Extractor::new(&runner.egraph, egg::AstSize).find_best(root).1;
+        assert_eq!(extracted.to_string(), "(* a b)");
+        let inputs = input_exprs(&input_egraph, &[root]);
+        explain_roots(&mut runner.egraph, &[root], inputs, vec![extracted])
+    }
+
+    // The term of a proof step with its `(Rewrite=> rule term)` annotations unwrapped
+    fn strip_rewrites(step: &str) -> String {
+        let mut step = step.to_string();
+        while let Some(start) = step.find("(Rewrite") {
+            let term = start + step[start..].find(' ').unwrap() + 1;
+            let term = term + step[term..].find(' ').unwrap() + 1;
+            let mut depth = 0;
+            let end = start
+                + step[start..]
+                    .find(|c| {
+                        depth += match c {
+                            '(' => 1,
+                            ')' => -1,
+                            _ => 0,
+                        };
+                        depth == 0
+                    })
+                    .unwrap();
+            step = format!("{}{}{}", &step[..start], &step[term..end], &step[end + 1..]);
+        }
+        step
+    }
+
+    // The plain terms of the proof of the first root, from input to extracted
+    fn expected_terms(proofs: &mut [RootProof]) -> Vec<String> {
+        let flat = proofs[0].explanation.make_flat_explanation();
+        flat.iter().map(|term| term.remove_rewrites().get_string()).collect()
+    }
+
+    #[test]
+    fn json_proof_round_trips() {
+        let path = std::env::temp_dir().join(format!("e-rewriter-proof-{}.json", std::process::id()));
+        assert_eq!(ProofFormat::from_path(&path), ProofFormat::Json);
+        let mut proofs = proofs();
+        write_proofs(&mut proofs, &path, ProofFormat::Json).unwrap();
+        let json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let proof = &json["proofs"][0];
+        let steps = proof["steps"].as_array().unwrap();
+        assert!(proof["length"].as_u64().unwrap() > 0);
+        assert_eq!(steps.len() as u64, proof["length"].as_u64().unwrap() + 1);
+        assert!(steps[0]["rule"].is_null());
+        assert!(steps[1..].iter().all(|step| step["rule"].is_string()));
+
+        let terms: Vec<String> = steps
+            .iter()
+            .map(|step| strip_rewrites(step["term"].as_str().unwrap()))
+            .collect();
+        assert_eq!(terms, expected_terms(&mut proofs));
+        let term = |term: &str| term.parse::<RecExpr<Prop>>().unwrap().to_string();
+        assert_eq!(term(&terms[0]), proof["input"]);
+        assert_eq!(term(terms.last().unwrap()), proof["extracted"]);
+    }
+
+    #[test]
+    fn sexp_proof_round_trips() {
+        let path = std::env::temp_dir().join(format!("e-rewriter-proof-{}.txt", std::process::id()));
+        assert_eq!(ProofFormat::from_path(&path), ProofFormat::Sexp);
+        let mut proofs = proofs();
+        write_proofs(&mut proofs, &path, ProofFormat::Sexp).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(contents.contains("; input: (! (! (* a (+ b 0))))"));
+        assert!(contents.contains("; extracted: (* a b)"));
+        let steps: Vec<&str> = contents.lines().filter(|line| !line.starts_with(';')).collect();
+        assert!(steps.len() > 1);
+        assert!(steps[1..].iter().all(|step| step.contains("(Rewrite")));
+
+        let terms: Vec<String> = steps.iter().map(|step| strip_rewrites(step)).collect();
+        assert_eq!(terms, expected_terms(&mut proofs));
+        let term = |term: &str| term.parse::<RecExpr<Prop>>().unwrap().to_string();
+        assert_eq!(term(&terms[0]), proofs[0].input.to_string());
+        assert_eq!(term(terms.last().unwrap()), proofs[0].extracted.to_string());
+    }
+}
@@ -2,11 +2,13 @@ use crate::utils::language::*;
 use crate::utils::random_gen::*;
 use egg::*;
 use rand::prelude::SliceRandom;
+use rand::rngs::StdRng;
 use rand::Rng;
 use rayon::prelude::*;
 use rustc_hash::{FxHashMap, FxHashSet};
 use serde::de::value;
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
@@ -20,6 +22,7 @@ pub struct Extractor2<'a, CF: CostFunction<L>, L: Language, N: Analysis<L>> {
     //costs: HashMap<Id, (CF::Cost, usize, L)>,
     costs: FxHashMap<Id, (CF::Cost, usize, L)>,
     egraph: &'a EGraph<L, N>,
+    rng: RefCell<StdRng>,
 }
 
 pub struct ParallelWrapper<'a, T>(&'a [T]);
@@ -64,7 +67,7 @@ where
     /// The extraction does all the work on creation, so this function
     /// performs the greedy search for cheapest representative of each
     /// eclass.
-    pub fn new(egraph: &'a EGraph<L, N>, cost_function: CF) -> Self
+    pub fn new(egraph: &'a EGraph<L, N>, cost_function: CF, rng: StdRng) -> Self
     where
         <CF as CostFunction<L>>::Cost: Ord,
     {
@@ -74,6 +77,7 @@ where
             costs,
             egraph,
             cost_function,
+            rng: RefCell::new(rng),
         };
         extractor.find_costs();
         extractor.costs.iter().for_each(|(id, (cost, index, l))| {
@@ -82,7 +86,7 @@ where
         extractor
     }
 
-    pub fn new_random(egraph: &'a EGraph<L, N>, cost_function: CF) -> Self
+    pub fn new_random(egraph: &'a EGraph<L, N>, cost_function: CF, rng: StdRng) -> Self
     where
         <CF as CostFunction<L>>::Cost: Ord,
     {
@@ -92,6 +96,7 @@ where
             costs,
             egraph,
             cost_function,
+            rng: RefCell::new(rng),
         };
         extractor.find_costs_random();
         extractor.costs.iter().for_each(|(id, (cost, index, l))| {
@@ -101,12 +106,12 @@ where
     }
 
     pub fn get_node(&self, id: Id) -> &L {
-        let random_num = generate_random_float1();
+        let mut rng = self.rng.borrow_mut();
+        let random_num = generate_random_float1(&mut *rng);
         //println!("random_num{}",random_num);
         if random_num > (0.5 as f64) {
             let eclass = &self.egraph[id];
             let nodes: Vec<&L> = eclass.iter().collect();
-            let mut rng = rand::thread_rng();
             let random_index = rng.gen_range(0..nodes.len());
             let random_node = nodes[random_index];
             random_node
@@ -157,6 +162,7 @@ where
         root: L,
     ) -> BTreeMap<u32, RecExpr<L>> {
         let mut rec_expr_map: BTreeMap<u32, RecExpr<L>> = BTreeMap::new();
+        let mut rng = self.rng.borrow_mut();
         for num in 0..num_runs {
             // let mut result: HashMap<String, String> = HashMap::new();
             // let mut result1: HashMap<String, L> = HashMap::new();
@@ -168,8 +174,6 @@ where
             for (id, (_, index, _)) in self.costs.iter() {
                 let eclass = &self.egraph[*id];
                 let nodes: Vec<&L> = eclass.iter().collect();
-                let mut rng = rand::thread_rng();
-
                 //  println!("eclass: {:?}", eclass);  // 打印 eclass
 
                 if input_vec_id.contains(id) {
@@ -346,8 +350,7 @@ where
                 .filter(|(cost, _, _)| cost == &min_cost)
                 .cloned()
                 .collect();
-            let mut rng = rand::thread_rng();
-            if let Some(selected_tuple) = min_cost_tuples.choose(&mut rng) {
+            if let Some(selected_tuple) = min_cost_tuples.choose(self.rng.get_mut()) {
                 //    println!("Selected Tuple: {:?}", selected_tuple);
                 return Some(selected_tuple.clone());
             }
@@ -401,10 +404,8 @@ where
             })
             .collect();
 
-        let mut rng = rand::thread_rng();
-
-        if rng.gen::<f64>() < random_ratio {
-            if let Some(selected_tuple) = result.choose(&mut rng) {
+        if self.rng.get_mut().gen::<f64>() < random_ratio {
+            if let Some(selected_tuple) = result.choose(self.rng.get_mut()) {
                 // println!("Selected Tuple: {:?}", selected_tuple);
                 return Some(selected_tuple.clone());
             }
@@ -417,8 +418,7 @@ where
                     .filter(|(cost, _, _)| cost == &min_cost)
                     .cloned()
                     .collect();
-                let mut rng = rand::thread_rng();
-                if let Some(selected_tuple) = min_cost_tuples.choose(&mut rng) {
+                if let Some(selected_tuple) = min_cost_tuples.choose(self.rng.get_mut()) {
                     //    println!("Selected Tuple: {:?}", selected_tuple);
                     return Some(selected_tuple.clone());
                 }
@@ -443,6 +443,7 @@ pub struct Extractor1<'a, CF: CostFunction<L>, L: Language, N: Analysis<L>> {
     cost_function: CF,
     costs: HashMap<Id, (CF::Cost, L)>,
     egraph: &'a EGraph<L, N>,
+    rng: RefCell<StdRng>,
 }
 
 impl<'a, CF, L, N> Extractor1<'a, CF, L, N>
@@ -457,7 +458,7 @@ where
     /// The extraction does all the work on creation, so this function
     /// performs the greedy search for cheapest representative of each
     /// eclass.
-    pub fn new(egraph: &'a EGraph<L, N>, cost_function: CF) -> Self
+    pub fn new(egraph: &'a EGraph<L, N>, cost_function: CF, rng: StdRng) -> Self
     where
         <CF as CostFunction<L>>::Cost: Ord,
     {
@@ -466,18 +467,19 @@ where
             costs,
             egraph,
             cost_function,
+            rng: RefCell::new(rng),
         };
         extractor.find_costs();
 
         extractor
     }
     pub fn get_node(&self, id: Id) -> &L {
-        let random_num = generate_random_float1();
+        let mut rng = self.rng.borrow_mut();
+        let random_num = generate_random_float1(&mut *rng);
         //println!("random_num{}",random_num);
         if random_num > (0.5 as f64) {
             let eclass = &self.egraph[id];
             let nodes: Vec<&L> = eclass.iter().collect();
-            let mut rng = rand::thread_rng();
             let random_index = rng.gen_range(0..nodes.len());
             let random_node = nodes[random_index];
             random_node
@@ -585,8 +587,7 @@ where
                 .filter(|(cost, _)| cost == &min_cost)
                 .cloned()
                 .collect();
-            let mut rng = rand::thread_rng();
-            if let Some(selected_tuple) = min_cost_tuples.choose(&mut rng) {
+            if let Some(selected_tuple) = min_cost_tuples.choose(self.rng.get_mut()) {
                 return Some(selected_tuple.clone());
             }
         }
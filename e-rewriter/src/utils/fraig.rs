@@ -0,0 +1,300 @@
+use egg::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::utils::language::*;
+use crate::utils::simulation::*;
+
+// FRAIG-style merging inside the egraph. Classes are bucketed by their random
+// simulation signature over the primary inputs (up to complement), and each
+// candidate pair whose cones depend on at most `max_inputs` primary inputs is
+// proven equal with exhaustive truth tables before it is merged. Since the
+// comparison is over the primary inputs, satisfiability don't-cares of the
+// cones are exploited, which the algebraic rules never do.
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FraigStats {
+    // Pairs of classes with equal signatures
+    pub candidates: usize,
+    // Pairs proven equal and merged
+    pub merged: usize,
+    // Pairs that simulated equal but differ on some exhaustive pattern
+    pub refuted: usize,
+    // Pairs whose joint support was too large to prove exhaustively
+    pub skipped: usize,
+}
+
+// Node used to evaluate each class, chosen so that the choices form a DAG
+struct Cones {
+    node: HashMap<Id, Prop>,
+    signature: HashMap<Id, Signature>,
+    // Sorted input classes of the cone, `None` once it exceeds the limit
+    support: HashMap<Id, Option<Vec<Id>>>,
+}
+
+pub fn is_boolean(node: &Prop) -> bool {
+    !matches!(node, Prop::Let(_) | Prop::Concat(_) | Prop::Rooting(_))
+}
+
+fn merge_support(a: &[Id], b: &[Id], max_inputs: usize) -> Option<Vec<Id>> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let next = match (a.get(i), b.get(j)) {
+            (Some(&x), Some(&y)) if x == y => {
+                i += 1;
+                j += 1;
+                x
+            }
+            (Some(&x), Some(&y)) if x < y => {
+                i += 1;
+                x
+            }
+            (Some(&x), None) => {
+                i += 1;
+                x
+            }
+            (_, Some(&y)) => {
+                j += 1;
+                y
+            }
+            (None, None) => unreachable!(),
+        };
+        merged.push(next);
+        if merged.len() > max_inputs {
+            return None;
+        }
+    }
+    Some(merged)
+}
+
+// Picks one Boolean node per class such that the picks form a DAG, and returns
+// them children first. Each class takes the first node whose children are all
+// picked, classes only reachable through cycles are left out.
+pub fn evaluation_order<N: Analysis<Prop>>(egraph: &EGraph<Prop, N>) -> Vec<(Id, Prop)> {
+    let mut nodes: Vec<(Id, Prop)> = Vec::new();
+    for class in egraph.classes() {
+        for node in &class.nodes {
+            if is_boolean(node) {
+                nodes.push((class.id, node.clone().map_children(|child| egraph.find(child))));
+            }
+        }
+    }
+
+    let mut pending: Vec<usize> = nodes.iter().map(|(_, node)| node.children().len()).collect();
+    let mut users: HashMap<Id, Vec<usize>> = HashMap::new();
+    for (k, (_, node)) in nodes.iter().enumerate() {
+        for &child in node.children() {
+            users.entry(child).or_default().push(k);
+        }
+    }
+
+    let mut picked: HashSet<Id> = HashSet::new();
+    let mut order = Vec::new();
+    let mut ready: Vec<usize> = (0..nodes.len()).filter(|&k| pending[k] == 0).collect();
+    while let Some(k) = ready.pop() {
+        let class = nodes[k].0;
+        if !picked.insert(class) {
+            continue;
+        }
+        order.push(nodes[k].clone());
+
+        for &user in users.get(&class).map(Vec::as_slice).unwrap_or(&[]) {
+            pending[user] -= 1;
+            if pending[user] == 0 {
+                ready.push(user);
+            }
+        }
+    }
+    order
+}
+
+fn build_cones<N: Analysis<Prop>>(egraph: &EGraph<Prop, N>, seed: u64, max_inputs: usize) -> Cones {
+    let mut cones = Cones {
+        node: HashMap::new(),
+        signature: HashMap::new(),
+        support: HashMap::new(),
+    };
+    for (class, node) in evaluation_order(egraph) {
+        let signature = simulate_node(&node, seed, |child| cones.signature[&child]);
+        let support = match &node {
+            Prop::Symbol(name) if name.as_str() != "0" && name.as_str() != "1" => Some(vec![class]),
+            _ => node.children().iter().try_fold(vec![], |support, child| {
+                merge_support(&support, cones.support[child].as_ref()?, max_inputs)
+            }),
+        };
+        cones.node.insert(class, node);
+        cones.signature.insert(class, signature);
+        cones.support.insert(class, support);
+    }
+    cones
+}
+
+// Truth table of input `index` out of `inputs` over `words` 64-bit words
+fn projection(index: usize, words: usize) -> Vec<u64> {
+    const MASKS: [u64; 6] = [
+        0xaaaa_aaaa_aaaa_aaaa,
+        0xcccc_cccc_cccc_cccc,
+        0xf0f0_f0f0_f0f0_f0f0,
+        0xff00_ff00_ff00_ff00,
+        0xffff_0000_ffff_0000,
+        0xffff_ffff_0000_0000,
+    ];
+    (0..words)
+        .map(|w| {
+            if index < 6 {
+                MASKS[index]
+            } else if (w >> (index - 6)) & 1 == 1 {
+                !0
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+// Exhaustive truth table of `root` over the inputs in `support`
+fn truth_table(cones: &Cones, root: Id, support: &[Id]) -> Vec<u64> {
+    let words = 1usize << support.len().saturating_sub(6);
+    let mut tables: HashMap<Id, Vec<u64>> = support
+        .iter()
+        .enumerate()
+        .map(|(index, &input)| (input, projection(index, words)))
+        .collect();
+
+    // Post-order over the chosen nodes without recursion, cones can be deep
+    let mut stack = vec![(root, false)];
+    while let Some((class, expanded)) = stack.pop() {
+        if tables.contains_key(&class) {
+            continue;
+        }
+        let node = &cones.node[&class];
+        if expanded {
+            let table = (0..words)
+                .map(|w| eval_word(node, |child| tables[&child][w]).expect("inputs are looked up, not evaluated"))
+                .collect();
+            tables.insert(class, table);
+        } else {
+            stack.push((class, true));
+            for &child in node.children() {
+                if !tables.contains_key(&child) {
+                    stack.push((child, false));
+                }
+            }
+        }
+    }
+
+    let mut table = tables.remove(&root).unwrap();
+    if support.len() < 6 {
+        table[0] &= (1u64 << (1 << support.len())) - 1;
+    }
+    table
+}
+
+fn complement(table: &[u64], inputs: usize) -> Vec<u64> {
+    let mut table: Vec<u64> = table.iter().map(|w| !w).collect();
+    if inputs < 6 {
+        table[0] &= (1u64 << (1 << inputs)) - 1;
+    }
+    table
+}
+
+pub fn fraig<N: Analysis<Prop>>(egraph: &mut EGraph<Prop, N>, seed: u64, max_inputs: usize) -> FraigStats {
+    // Make sure constant classes exist so constant cones can be merged into them
+    egraph.add(Prop::Symbol("0".into()));
+    egraph.rebuild();
+
+    let cones = build_cones(egraph, seed, max_inputs);
+    let mut stats = FraigStats::default();
+
+    // Bucket by signature up to complement, remembering the phase of each class
+    let mut buckets: HashMap<Signature, Vec<(Id, bool)>> = HashMap::new();
+    let mut classes: Vec<Id> = cones.signature.keys().copied().collect();
+    classes.sort();
+    for class in classes {
+        let signature = cones.signature[&class];
+        let phase = signature[0] & 1 == 1;
+        let key = if phase { signature.map(|w| !w) } else { signature };
+        buckets.entry(key).or_default().push((class, phase));
+    }
+
+    for bucket in buckets.values().filter(|bucket| bucket.len() > 1) {
+        let (repr, repr_phase) = bucket[0];
+        for &(class, phase) in &bucket[1..] {
+            if egraph.find(class) == egraph.find(repr) {
+                continue;
+            }
+            // Every class shares its bucket with its own complement, that pair
+            // is already related and merging it would only add `(! (! x))`
+            let negates = |a: Id, b: Id| egraph.lookup(Prop::Not(b)).map(|id| egraph.find(id)) == Some(egraph.find(a));
+            if phase != repr_phase && (negates(repr, class) || negates(class, repr)) {
+                continue;
+            }
+            stats.candidates += 1;
+
+            let support = match (&cones.support[&repr], &cones.support[&class]) {
+                (Some(a), Some(b)) => merge_support(a, b, max_inputs),
+                _ => None,
+            };
+            let Some(support) = support else {
+                stats.skipped += 1;
+                continue;
+            };
+
+            let repr_table = truth_table(&cones, repr, &support);
+            let mut class_table = truth_table(&cones, class, &support);
+            if phase != repr_phase {
+                class_table = complement(&class_table, support.len());
+            }
+            if repr_table != class_table {
+                stats.refuted += 1;
+                continue;
+            }
+
+            let target = if phase != repr_phase {
+                egraph.add(Prop::Not(class))
+            } else {
+                class
+            };
+            egraph.union_trusted(repr, target, "fraig");
+            stats.merged += 1;
+        }
+    }
+
+    egraph.rebuild();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commuted_and_is_merged() {
+        let mut egraph = EGraph::<Prop, ()>::default();
+        let ab = egraph.add_expr(&"(* a b)".parse().unwrap());
+        let ba = egraph.add_expr(&"(* b a)".parse().unwrap());
+        egraph.add(Prop::Concat([ab, ba]));
+        let stats = fraig(&mut egraph, 7, 8);
+        assert_eq!(stats.merged, 1);
+        assert_eq!(egraph.find(ab), egraph.find(ba));
+    }
+
+    #[test]
+    fn complemented_and_unequal_cones() {
+        let mut egraph = EGraph::<Prop, ()>::default();
+        let and = egraph.add_expr(&"(* a b)".parse().unwrap());
+        let nand = egraph.add_expr(&"(+ (! a) (! b))".parse().unwrap());
+        let xor = egraph.add_expr(&"(^ a b)".parse().unwrap());
+        egraph.add_expr(&"(& (* a b) (& (+ (! a) (! b)) (^ a b)))".parse().unwrap());
+        let stats = fraig(&mut egraph, 7, 8);
+        // Only the AND with the NAND, not a with !!a or b with !!b
+        assert_eq!(stats.merged, 1);
+        let not_a = egraph.lookup_expr(&"(! a)".parse().unwrap()).unwrap();
+        assert!(egraph.lookup(Prop::Not(not_a)).is_none());
+        // The class merged second is unioned with the complement of the first
+        let complement_of = |x: Id, y: Id| egraph.lookup(Prop::Not(x)) == Some(egraph.find(y));
+        assert!(complement_of(and, nand) || complement_of(nand, and));
+        assert_ne!(egraph.find(xor), egraph.find(and));
+        assert_ne!(egraph.find(xor), egraph.find(nand));
+    }
+}
@@ -12,6 +12,10 @@ define_language! {
         "->" = Implies([Id; 2]),
         "let" = Let([Id; 2]),
         "&" = Concat([Id; 2]),
+        "^" = Xor([Id; 2]),
+        // (mux s t e) = s ? t : e
+        "mux" = Mux([Id; 3]),
+        "maj" = Maj([Id; 3]),
         "root"= Rooting([Id; 2]),
         Symbol(Symbol),
     }
@@ -74,8 +78,8 @@ define_language! {
 
 //pub fn make_rules() -> Vec<Rewrite<Prop, ConstantFold>> {
 //    let mut rws: Vec<Rewrite<Prop, ConstantFold>> = vec![
-    pub fn make_rules() -> Vec<Rewrite<Prop, ()>> {
-        let mut rws: Vec<Rewrite<Prop, ()>> = vec![
+    pub fn make_rules<N: Analysis<Prop>>() -> Vec<Rewrite<Prop, N>> {
+        let mut rws: Vec<Rewrite<Prop, N>> = vec![
         rewrite!("null-element1"; "(* ?b 0)" => "0"),
         rewrite!("null-element2"; "(+ ?b 1)" => "1"),
         rewrite!("complements1"; "(* ?b (! ?b))" => "0"),
@@ -109,8 +113,8 @@ define_language! {
     rws.extend(rewrite!("de-morgan2"; "(! (+ ?b ?c))" <=> "(* (! ?b) (! ?c))"));
     rws
 }
-pub fn make_rules_esyn() -> Vec<Rewrite<Prop, ()>> {
-    let mut rws: Vec<Rewrite<Prop, ()>> = vec![
+pub fn make_rules_esyn<N: Analysis<Prop>>() -> Vec<Rewrite<Prop, N>> {
+    let mut rws: Vec<Rewrite<Prop, N>> = vec![
         // Boolean theorems of one variable (Table 2.2 pg 62)
         rewrite!("null-element1"; "(* ?b 0)" => "0"),
         rewrite!("null-element2"; "(+ ?b 1)" => "1"),
@@ -143,7 +147,53 @@ pub fn make_rules_esyn() -> Vec<Rewrite<Prop, ()>> {
 }
 
 
-pub fn make_rules_simplify() -> Vec<Rewrite<Prop, ()>> {
+// XOR, MUX and majority algebra on top of make_rules_esyn.
+// The *-intro rules recognize the operators in AND/OR/INV structure, the
+// *-expand rules turn them back into AND/INV so every choice stays writable as an AIG.
+pub fn make_rules_xmg<N: Analysis<Prop>>() -> Vec<Rewrite<Prop, N>> {
+    let mut rws = make_rules_esyn();
+    rws.extend(vec![
+        // XAG
+        rewrite!("xor-commutativity"; "(^ ?a ?b)" => "(^ ?b ?a)"),
+        rewrite!("xor-self"; "(^ ?a ?a)" => "0"),
+        rewrite!("xor-complement"; "(^ ?a (! ?a))" => "1"),
+        rewrite!("xor-identity"; "(^ ?a 0)" => "?a"),
+        rewrite!("xor-one"; "(^ ?a 1)" => "(! ?a)"),
+        rewrite!("xor-intro-sop"; "(+ (* ?a (! ?b)) (* (! ?a) ?b))" => "(^ ?a ?b)"),
+        rewrite!("xor-intro-pos"; "(* (+ ?a ?b) (+ (! ?a) (! ?b)))" => "(^ ?a ?b)"),
+        rewrite!("xor-expand"; "(^ ?a ?b)" => "(! (* (! (* ?a (! ?b))) (! (* (! ?a) ?b))))"),
+        // MUX
+        rewrite!("mux-same"; "(mux ?s ?t ?t)" => "?t"),
+        rewrite!("mux-true"; "(mux 1 ?t ?e)" => "?t"),
+        rewrite!("mux-false"; "(mux 0 ?t ?e)" => "?e"),
+        rewrite!("mux-not-select"; "(mux (! ?s) ?t ?e)" => "(mux ?s ?e ?t)"),
+        rewrite!("mux-xor"; "(mux ?s (! ?t) ?t)" => "(^ ?s ?t)"),
+        rewrite!("mux-intro"; "(+ (* ?s ?t) (* (! ?s) ?e))" => "(mux ?s ?t ?e)"),
+        rewrite!("mux-expand"; "(mux ?s ?t ?e)" => "(! (* (! (* ?s ?t)) (! (* (! ?s) ?e))))"),
+        // MIG (majority axioms)
+        rewrite!("maj-commutativity1"; "(maj ?a ?b ?c)" => "(maj ?b ?a ?c)"),
+        rewrite!("maj-commutativity2"; "(maj ?a ?b ?c)" => "(maj ?a ?c ?b)"),
+        rewrite!("maj-majority"; "(maj ?a ?a ?b)" => "?a"),
+        rewrite!("maj-complement"; "(maj ?a (! ?a) ?b)" => "?b"),
+        rewrite!("maj-associativity"; "(maj ?x ?u (maj ?y ?u ?z))" => "(maj ?z ?u (maj ?y ?u ?x))"),
+        rewrite!("maj-distributivity"; "(maj ?x ?y (maj ?u ?v ?z))" => "(maj (maj ?x ?y ?u) (maj ?x ?y ?v) ?z)"),
+        rewrite!("maj-intro"; "(+ (* ?a ?b) (* ?c (+ ?a ?b)))" => "(maj ?a ?b ?c)"),
+        rewrite!("maj-expand"; "(maj ?a ?b ?c)" => "(! (* (! (* ?a ?b)) (! (* ?c (! (* (! ?a) (! ?b)))))))"),
+    ]);
+
+    rws.extend(rewrite!("xor-associativity"; "(^ (^ ?a ?b) ?c)" <=> "(^ ?a (^ ?b ?c))"));
+    rws.extend(rewrite!("xor-not"; "(^ (! ?a) ?b)" <=> "(! (^ ?a ?b))"));
+    rws.extend(rewrite!("and-xor-distributivity"; "(* ?a (^ ?b ?c))" <=> "(^ (* ?a ?b) (* ?a ?c))"));
+    // Only towards AND/OR: the reverse direction adds a majority node next to every
+    // AND and OR of the circuit, and each of those feeds the majority axioms above
+    rws.push(rewrite!("maj-and"; "(maj ?a ?b 0)" => "(* ?a ?b)"));
+    rws.push(rewrite!("maj-or"; "(maj ?a ?b 1)" => "(+ ?a ?b)"));
+    rws.extend(rewrite!("maj-inverter-propagation"; "(! (maj ?a ?b ?c))" <=> "(maj (! ?a) (! ?b) (! ?c))"));
+
+    rws
+}
+
+pub fn make_rules_simplify<N: Analysis<Prop>>() -> Vec<Rewrite<Prop, N>> {
     vec![
         //version 1
         //rewrite!("a"; "(-> ?a ?b)"      =>       "(+ (! ?a) ?b)"          ),
@@ -1,7 +1,14 @@
 pub mod cost;
+pub mod eqn;
 pub mod language;
+pub mod npn;
 pub mod order_trait;
 pub mod preprocess;
 pub mod random_gen;
+pub mod rule_file;
 pub mod extract_new;
-pub mod runner_modified;
\ No newline at end of file
+pub mod fraig;
+pub mod explain;
+pub mod runner_modified;
+pub mod simulation;
+pub mod synth;
\ No newline at end of file
@@ -0,0 +1,300 @@
+use egg::*;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+use crate::utils::fraig::evaluation_order;
+use crate::utils::language::*;
+use crate::utils::simulation::eval_word;
+
+// Cut-based NPN rewriting, the egraph counterpart of ABC's `rewrite`.
+// Every class gets up to `max_cuts` 4-feasible cuts with their truth tables.
+// Each cut's function is mapped to its NPN class, and the bundled structure for
+// that class is instantiated over the cut leaves and merged into the class.
+
+pub const CUT_SIZE: usize = 4;
+
+// Truth tables of the 4 cut inputs, input 0 is the least significant
+const PROJECTIONS: [u16; CUT_SIZE] = [0xaaaa, 0xcccc, 0xf0f0, 0xff00];
+
+const PATTERN_VARS: [&str; CUT_SIZE] = ["?a", "?b", "?c", "?d"];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NpnStats {
+    // Non-trivial cuts enumerated
+    pub cuts: usize,
+    // Cuts whose structure was new to its class
+    pub merged: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Cut {
+    // Sorted, leaf i is input i of `truth`
+    leaves: Vec<Id>,
+    truth: u16,
+}
+
+// f(x) = out ^ rep(y) with y_i = x_perm[i] ^ neg_i
+#[derive(Debug, Clone, Copy)]
+struct Transform {
+    perm: [usize; CUT_SIZE],
+    neg: u8,
+    out: bool,
+}
+
+impl Transform {
+    fn apply(&self, rep: u16) -> u16 {
+        let mut truth = 0;
+        for x in 0..16 {
+            let mut y = 0;
+            for i in 0..CUT_SIZE {
+                y |= (((x >> self.perm[i]) ^ (self.neg as usize >> i)) & 1) << i;
+            }
+            truth |= (((rep >> y) & 1) ^ self.out as u16) << x;
+        }
+        truth
+    }
+}
+
+fn transforms() -> Vec<Transform> {
+    let mut perms = vec![];
+    for a in 0..CUT_SIZE {
+        for b in 0..CUT_SIZE {
+            for c in 0..CUT_SIZE {
+                // The remaining input, the four sum to 0 + 1 + 2 + 3
+                let Some(d) = 6usize.checked_sub(a + b + c) else {
+                    continue;
+                };
+                if a != b && a != c && b != c && d < CUT_SIZE && d != a && d != b && d != c {
+                    perms.push([a, b, c, d]);
+                }
+            }
+        }
+    }
+    let mut transforms = vec![];
+    for perm in perms {
+        for neg in 0..16 {
+            for out in [false, true] {
+                transforms.push(Transform { perm, neg, out });
+            }
+        }
+    }
+    transforms
+}
+
+// For every function, the smallest member of its NPN class and a transform
+// producing the function from it
+static NPN_CLASSES: Lazy<Vec<(u16, Transform)>> = Lazy::new(|| {
+    let transforms = transforms();
+    let mut classes: Vec<Option<(u16, Transform)>> = vec![None; 1 << 16];
+    for rep in 0..=u16::MAX {
+        if classes[rep as usize].is_some() {
+            continue;
+        }
+        for transform in &transforms {
+            classes[transform.apply(rep) as usize].get_or_insert((rep, *transform));
+        }
+    }
+    classes.into_iter().map(Option::unwrap).collect()
+});
+
+static DATABASE: Lazy<HashMap<u16, PatternAst<Prop>>> = Lazy::new(|| {
+    include_str!("npn4.txt")
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let truth = u16::from_str_radix(fields.next().unwrap(), 16).unwrap();
+            let _size = fields.next().unwrap();
+            let pattern = fields.next().unwrap().parse().unwrap();
+            (truth, pattern)
+        })
+        .collect()
+});
+
+// Re-expresses `truth` over the leaves `from` as a function over `to`, a superset
+fn expand(truth: u16, from: &[Id], to: &[Id]) -> u16 {
+    let positions: Vec<usize> = from.iter().map(|leaf| to.binary_search(leaf).unwrap()).collect();
+    let mut expanded = 0;
+    for x in 0..16 {
+        let mut y = 0;
+        for (i, &position) in positions.iter().enumerate() {
+            y |= ((x >> position) & 1) << i;
+        }
+        expanded |= ((truth >> y) & 1) << x;
+    }
+    expanded
+}
+
+fn merge_leaves(cuts: &[&Cut]) -> Option<Vec<Id>> {
+    let mut leaves: Vec<Id> = cuts.iter().flat_map(|cut| cut.leaves.iter().copied()).collect();
+    leaves.sort();
+    leaves.dedup();
+    if leaves.len() > CUT_SIZE {
+        None
+    } else {
+        Some(leaves)
+    }
+}
+
+// A child class can appear twice with different cuts, so children are
+// renumbered by position before evaluating
+fn node_truth(node: &Prop, children: &[u16]) -> u16 {
+    let mut position = 0;
+    let node = node.clone().map_children(|_| {
+        position += 1;
+        Id::from(position - 1)
+    });
+    let truth = eval_word(&node, |child| children[usize::from(child)] as u64);
+    truth.unwrap_or_else(|| unreachable!("not a Boolean node: {:?}", node)) as u16
+}
+
+// Cuts of every class, children before parents, using every node whose
+// children come earlier in the evaluation order
+fn enumerate_cuts<N: Analysis<Prop>>(egraph: &EGraph<Prop, N>, max_cuts: usize) -> Vec<(Id, Vec<Cut>)> {
+    let order = evaluation_order(egraph);
+    let rank: HashMap<Id, usize> = order.iter().enumerate().map(|(rank, (class, _))| (*class, rank)).collect();
+    let mut cuts: HashMap<Id, Vec<Cut>> = HashMap::new();
+    let mut result = Vec::with_capacity(order.len());
+
+    for (rank_of_class, (class, _)) in order.iter().enumerate() {
+        let class = *class;
+        let mut class_cuts: Vec<Cut> = vec![];
+        for node in &egraph[class].nodes {
+            let node = node.clone().map_children(|child| egraph.find(child));
+            let usable = node
+                .children()
+                .iter()
+                .all(|child| rank.get(child).is_some_and(|&r| r < rank_of_class));
+            if !usable || matches!(node, Prop::Let(_) | Prop::Concat(_) | Prop::Rooting(_)) {
+                continue;
+            }
+            if let Prop::Symbol(name) = &node {
+                if name.as_str() != "0" && name.as_str() != "1" {
+                    continue;
+                }
+            }
+
+            // Cartesian product of the children's cuts
+            let child_cuts: Vec<&Vec<Cut>> = node.children().iter().map(|child| &cuts[child]).collect();
+            let mut index = vec![0; child_cuts.len()];
+            loop {
+                let chosen: Vec<&Cut> = child_cuts.iter().zip(&index).map(|(cuts, &i)| &cuts[i]).collect();
+                if let Some(leaves) = merge_leaves(&chosen) {
+                    if !class_cuts.iter().any(|cut| cut.leaves == leaves) {
+                        let truths: Vec<u16> = chosen
+                            .iter()
+                            .map(|cut| expand(cut.truth, &cut.leaves, &leaves))
+                            .collect();
+                        let truth = node_truth(&node, &truths);
+                        class_cuts.push(Cut { leaves, truth });
+                    }
+                }
+
+                // Next combination, odometer style
+                let mut digit = 0;
+                while digit < index.len() {
+                    index[digit] += 1;
+                    if index[digit] < child_cuts[digit].len() {
+                        break;
+                    }
+                    index[digit] = 0;
+                    digit += 1;
+                }
+                if digit == index.len() {
+                    break;
+                }
+            }
+        }
+
+        class_cuts.sort_by_key(|cut| cut.leaves.len());
+        class_cuts.truncate(max_cuts);
+        result.push((class, class_cuts.clone()));
+
+        // The trivial cut lets parents use this class as a leaf
+        class_cuts.push(Cut {
+            leaves: vec![class],
+            truth: PROJECTIONS[0],
+        });
+        cuts.insert(class, class_cuts);
+    }
+    result
+}
+
+pub fn npn_rewrite<N: Analysis<Prop>>(egraph: &mut EGraph<Prop, N>, max_cuts: usize) -> NpnStats {
+    let mut stats = NpnStats::default();
+    let vars: Vec<Var> = PATTERN_VARS.iter().map(|var| var.parse().unwrap()).collect();
+    let zero = egraph.add(Prop::Symbol("0".into()));
+
+    for (class, cuts) in enumerate_cuts(egraph, max_cuts) {
+        for cut in cuts {
+            stats.cuts += 1;
+            let (rep, transform) = NPN_CLASSES[cut.truth as usize];
+            let pattern = &DATABASE[&rep];
+
+            let mut subst = Subst::with_capacity(CUT_SIZE);
+            for (i, &var) in vars.iter().enumerate() {
+                // Inputs beyond the cut are don't-cares of the function
+                let leaf = cut.leaves.get(transform.perm[i]).copied().unwrap_or(zero);
+                let leaf = if (transform.neg >> i) & 1 == 1 {
+                    egraph.add(Prop::Not(leaf))
+                } else {
+                    leaf
+                };
+                subst.insert(var, leaf);
+            }
+
+            let id = egraph.add_instantiation(pattern, &subst);
+            let id = if transform.out { egraph.add(Prop::Not(id)) } else { id };
+            if egraph.union_trusted(class, id, "npn-cut") {
+                stats.merged += 1;
+            }
+        }
+    }
+
+    egraph.rebuild();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::simulation::{simulate_node, Simulation};
+
+    // x ^ y over AND and INV
+    fn xor(x: &str, y: &str) -> String {
+        format!("(! (* (! (* {x} (! {y}))) (! (* (! {x}) {y}))))")
+    }
+
+    #[test]
+    fn xor3_gets_the_database_structure() {
+        let mut egraph = EGraph::<Prop, ()>::default();
+        let root = egraph.add_expr(&xor("a", &xor("b", "c")).parse().unwrap());
+        egraph.rebuild();
+        let stats = npn_rewrite(&mut egraph, 8);
+        assert!(stats.merged > 0);
+
+        // XOR3 is the complement of class 3cc3 with ?a unused and ?b, ?c, ?d
+        // on a, b, c
+        let (rep, transform) = NPN_CLASSES[0x9696];
+        assert_eq!((rep, transform.perm, transform.neg, transform.out), (0x3cc3, [3, 0, 1, 2], 0, true));
+        let expected = "(! (! (* (! (* a (* (! (* b c)) (! (* (! b) (! c)))))) (! (* (! a) (! (* (! (* b c)) (! (* (! b) (! c))))))))))";
+        assert_eq!(egraph.lookup_expr(&expected.parse().unwrap()), Some(egraph.find(root)));
+    }
+
+    #[test]
+    fn rewriting_is_sound_under_simulation() {
+        let mut egraph = EGraph::<Prop, Simulation>::new(Simulation::new(7));
+        let majority = "(+ (* a b) (+ (* a c) (* b c)))";
+        let mux = "(+ (* s (! (* a (! d)))) (* (! s) (* c d)))";
+        egraph.add_expr(&format!("(& {} (& {} {}))", xor("a", &xor("b", "c")), majority, mux).parse().unwrap());
+        egraph.rebuild();
+        // A union of classes with different signatures panics in pre_union
+        let stats = npn_rewrite(&mut egraph, 8);
+        assert!(stats.merged > 0);
+        for class in egraph.classes() {
+            for node in &class.nodes {
+                assert_eq!(simulate_node(node, 7, |id| egraph[id].data), class.data);
+            }
+        }
+    }
+}
@@ -2,16 +2,14 @@ use std::cmp::Ordering;
 use rand::Rng;
 
 pub trait OrdRandom {
-    fn max_random(self, other: i32) -> i32;
-    fn min_random(self, other: i32) -> i32;
+    fn max_random(self, other: i32, rng: &mut impl Rng) -> i32;
+    fn min_random(self, other: i32, rng: &mut impl Rng) -> i32;
 }
 
 impl OrdRandom for i32 {
-    fn max_random(self, other: i32) -> i32 {
+    fn max_random(self, other: i32, rng: &mut impl Rng) -> i32 {
         match self.cmp(&other) {
             Ordering::Equal => {
-                let mut rng = rand::thread_rng();
-
                 if rng.gen::<bool>() {
                     self
                 } else {
@@ -27,10 +25,9 @@ impl OrdRandom for i32 {
             }
         }
     }
-    fn min_random(self, other: i32) -> i32 {
+    fn min_random(self, other: i32, rng: &mut impl Rng) -> i32 {
         match self.cmp(&other) {
             Ordering::Equal => {
-                let mut rng = rand::thread_rng();
                 if rng.gen::<bool>() {
                     self
                 } else {
@@ -48,13 +45,12 @@ impl OrdRandom for i32 {
     }
 }
 
-pub fn min_random_cmp<T, F>(v1: T, v2: T, compare: F) -> T
+pub fn min_random_cmp<T, F>(v1: T, v2: T, compare: F, rng: &mut impl Rng) -> T
 where
     F: FnOnce(&T, &T) -> Ordering,
 {
     match compare(&v1, &v2) {
         Ordering::Less | Ordering::Equal => {
-            let mut rng = rand::thread_rng();
             if rng.gen::<bool>() {
                 v1
             } else {
@@ -67,7 +63,7 @@ where
 
 
 pub trait MyIteratorExt: Iterator {
-    fn min_by_random<F>(self, compare: F) -> Option<Self::Item>
+    fn min_by_random<F>(self, compare: F, rng: &mut impl Rng) -> Option<Self::Item>
     where
         Self: Sized,
         F: FnMut(&Self::Item, &Self::Item) -> std::cmp::Ordering;
@@ -78,17 +74,12 @@ impl<I> MyIteratorExt for I
 where
     I: Iterator,
 {
-    fn min_by_random<F>(self, compare: F) -> Option<Self::Item>
+    fn min_by_random<F>(self, mut compare: F, rng: &mut impl Rng) -> Option<Self::Item>
     where
         Self: Sized,
         F: FnMut(&Self::Item, &Self::Item) -> Ordering,
     {
-        #[inline]
-        fn fold<T>(mut compare: impl FnMut(&T, &T) -> Ordering) -> impl FnMut(T, T) -> T {
-            move |x, y| min_random_cmp(x, y, &mut compare)
-        }
-    
-        self.reduce(fold(compare))
+        self.reduce(|x, y| min_random_cmp(x, y, &mut compare, &mut *rng))
     }
 }
 
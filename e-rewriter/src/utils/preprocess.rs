@@ -165,6 +165,10 @@ pub fn process_json_prop_cost(json_str: &str) -> String {
                 "+" => 3.0,
                 "!" => 2.0,
                 "*" => 4.0,
+                // priced close to their AND/INV expansion, which is what gets written out
+                "^" => 10.0,
+                "mux" => 10.0,
+                "maj" => 14.0,
                 // "+" => 1.0,
                 // "!" => 1.0,
                 // "*" => 1.0,
@@ -178,399 +182,6 @@ pub fn process_json_prop_cost(json_str: &str) -> String {
     serde_json::to_string_pretty(&data).unwrap()
 }
 
-pub fn process_file(file_name: &str) -> (egg::Id, Vec<Id>, i32) {
-    let file = File::open(file_name).expect("Unable to open the eqn file");
-    let reader = BufReader::new(file);
-    let mut egraph: egg::EGraph<SymbolLang, ()> = EGraph::default();
-    let mut vars = HashMap::new();
-    let mut out = HashMap::new();
-    let mut count_out = 0;
-    let mut id2concat = Vec::new();
-    let mut input_id: Vec<Id> = Vec::new();
-    let mut one_out_sig = 0;
-    fn string_to_unique_id(s: &str) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        s.hash(&mut hasher);
-        hasher.finish()
-    }
-
-    let id0 = egraph.add(SymbolLang::leaf("0"));
-    vars.insert("0".to_string(), id0);
-    let id1 = egraph.add(SymbolLang::leaf("1"));
-    vars.insert("1".to_string(), id1);
-    for line in reader.lines() {
-        let line = line.expect("Unable to read line");
-        let line = line.trim().trim_end_matches(';');
-        //print!("line:  {}\n",line);
-        if line.starts_with('#') || line.is_empty() {
-            continue;
-        } else if line.starts_with("INORDER") {
-            let inputs = line.trim_start_matches("INORDER = ").split_whitespace();
-            for input in inputs {
-                let id = egraph.add(SymbolLang::leaf(input)); // 将 "NOT" 替换为每个输入的字符串
-                vars.insert(input.to_string(), id);
-                input_id.push(id);
-            }
-        } else if line.starts_with("OUTORDER") {
-            let output = line.trim_start_matches("OUTORDER = ").trim();
-            for output in output.split_whitespace() {
-                let id_u64 = string_to_unique_id(output);
-                out.insert(output.to_string(), id_u64);
-            }
-        } else {
-            let parts: Vec<&str> = line.split('=').map(str::trim).collect();
-            let left = parts[0];
-            let right = parts[1];
-            // print!("right {}\n",right);
-            let id = if right.contains('+') {
-                let operands: Vec<&str> = right.split('+').map(str::trim).collect();
-                let lhs = if operands[0].starts_with('!') {
-                    let var = &operands[0][1..];
-                    let id = vars[var];
-                    egraph.add(SymbolLang::new("Not", vec![id]))
-                } else {
-                    vars[operands[0]]
-                };
-                let rhs = if operands[1].starts_with('!') {
-                    let var = &operands[1][1..];
-                    let id = vars[var];
-                    egraph.add(SymbolLang::new("Not", vec![id]))
-                } else {
-                    vars[operands[1]]
-                };
-                egraph.add(SymbolLang::new("Or", vec![lhs, rhs]))
-            } else if right.contains('*') {
-                let operands: Vec<&str> = right.split('*').map(str::trim).collect();
-                let lhs = if operands[0].starts_with('!') {
-                    let var = &operands[0][1..];
-                    let id = vars[var];
-                    egraph.add(SymbolLang::new("Not", vec![id]))
-                } else {
-                    vars[operands[0]]
-                };
-                let rhs = if operands[1].starts_with('!') {
-                    let var = &operands[1][1..];
-                    let id = vars[var];
-                    egraph.add(SymbolLang::new("Not", vec![id]))
-                } else {
-                    vars[operands[1]]
-                };
-
-                egraph.add(SymbolLang::new("And", vec![lhs, rhs]))
-            } else if right.starts_with('!') {
-                let var = &right[1..];
-                let id = vars[var];
-                egraph.add(SymbolLang::new("Not", vec![id]))
-            } else {
-                vars[right]
-            };
-
-            if out.contains_key(left) {
-                id2concat.push(id);
-                count_out += 1;
-            }
-            vars.insert(left.to_string(), id);
-        }
-    }
-
-    let mut concat = Vec::new();
-
-    for i in 0..count_out - 1 {
-        if i == 0 {
-            let id = egraph.add(SymbolLang::new(
-                "Concat",
-                vec![id2concat[i as usize], id2concat[(i + 1) as usize]],
-            ));
-            concat.push(id);
-        } else {
-            let id = egraph.add(SymbolLang::new(
-                "Concat",
-                vec![concat[(i - 1) as usize], id2concat[(i + 1) as usize]],
-            ));
-            concat.push(id);
-        }
-    }
-    let last_element: Id = if let Some(element) = concat.pop() {
-        element
-    } else {
-        one_out_sig = 1;
-        let id: Id = id2concat.pop().unwrap().into();
-        vars.insert(out.keys().next().unwrap().to_string(), id.into());
-        println!("one_out_id: {}", id) ;   
-        id
-        //print id
-   
-    };
-    egraph.rebuild();
-    let json_str = serde_json::to_string_pretty(&egraph).unwrap();
-
-    let output_dir = Path::new(file_name).parent().unwrap_or(Path::new(""));
-    let output_file = format!(
-        "{}.json",
-        PathBuf::from(file_name)
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-    );
-    let output_path = output_dir.join(output_file);
-    fs::write(output_path, json_str).expect("Failed to write JSON file");
-    (last_element, input_id, one_out_sig)
-}
-
-// pub fn process_file_new(file_name: &str) -> (egg::EGraph<Prop,ConstantFold>,egg::Id, Vec<Id>, i32) {
-//     let file = File::open(file_name).expect("Unable to open the eqn file");
-//     let reader = BufReader::new(file);
-//     let mut egraph: egg::EGraph<Prop,ConstantFold > = EGraph::default();
-//     let mut vars = HashMap::new();
-//     let mut out = HashMap::new();
-//     let mut count_out = 0;
-//     let mut id2concat = Vec::new();
-//     let mut input_id: Vec<Id> = Vec::new();
-//     let mut one_out_sig = 0;
-//     fn string_to_unique_id(s: &str) -> u64 {
-//         let mut hasher = DefaultHasher::new();
-//         s.hash(&mut hasher);
-//         hasher.finish()
-//     }
-
-//     let id0 = egraph.add(Prop::from_op("false",vec![]).expect("REASON"));
-//     vars.insert("0".to_string(), id0);
-//     let id1 = egraph.add(Prop::from_op("true",vec![]).expect("REASON"));
-//     vars.insert("1".to_string(), id1);
-//     for line in reader.lines() {
-//         let line = line.expect("Unable to read line");
-//         let line = line.trim().trim_end_matches(';');
-//         //print!("line:  {}\n",line);
-//         if line.starts_with('#') || line.is_empty() {
-//             continue;
-//         } else if line.starts_with("INORDER") {
-//             let inputs = line.trim_start_matches("INORDER = ").split_whitespace();
-//             for input in inputs {
-//                 let id = egraph.add(Prop::from_op(input, vec![]).expect("REASON"));
-//                 vars.insert(input.to_string(), id);
-//                 input_id.push(id);
-//             }
-//         } else if line.starts_with("OUTORDER") {
-//             let output = line.trim_start_matches("OUTORDER = ").trim();
-//             for output in output.split_whitespace() {
-//                 let id_u64 = string_to_unique_id(output);
-//                 out.insert(output.to_string(), id_u64);
-//             }
-//         } else {
-//             let parts: Vec<&str> = line.split('=').map(str::trim).collect();
-//             let left = parts[0];
-//             let right = parts[1];
-//             // print!("right {}\n",right);
-//             let id = if right.contains('+') {
-//                 let operands: Vec<&str> = right.split('+').map(str::trim).collect();
-//                 let lhs = if operands[0].starts_with('!') {
-//                     let var = &operands[0][1..];
-//                     let id = vars[var];
-//                     egraph.add(Prop::from_op("!", vec![id]).expect("REASON"))
-//                 } else {
-//                     vars[operands[0]]
-//                 };
-//                 let rhs = if operands[1].starts_with('!') {
-//                     let var = &operands[1][1..];
-//                     let id = vars[var];
-//                     egraph.add(Prop::from_op("!", vec![id]).expect("REASON"))
-//                 } else {
-//                     vars[operands[1]]
-//                 };
-//                 egraph.add(Prop::from_op("+", vec![lhs, rhs]).expect("REASON"))
-//             } else if right.contains('*') {
-//                 let operands: Vec<&str> = right.split('*').map(str::trim).collect();
-//                 let lhs = if operands[0].starts_with('!') {
-//                     let var = &operands[0][1..];
-//                     let id = vars[var];
-//                     egraph.add(Prop::from_op("!", vec![id]).expect("REASON"))
-//                 } else {
-//                     vars[operands[0]]
-//                 };
-//                 let rhs = if operands[1].starts_with('!') {
-//                     let var = &operands[1][1..];
-//                     let id = vars[var];
-//                     egraph.add(Prop::from_op("!", vec![id]).expect("REASON"))
-//                 } else {
-//                     vars[operands[1]]
-//                 };
-
-//                 egraph.add(Prop::from_op("*", vec![lhs, rhs]).expect("REASON"))
-//             } else if right.starts_with('!') {
-//                 let var = &right[1..];
-//                 let id = vars[var];
-//                 egraph.add(Prop::from_op("!", vec![id]).expect("REASON"))
-//             } else {
-//                 vars[right]
-//             };
-
-//             if out.contains_key(left) {
-//                 id2concat.push(id);
-//                 count_out += 1;
-//             }
-//             vars.insert(left.to_string(), id);
-//         }
-//     }
-
-//     let mut concat = Vec::new();
-
-//     for i in 0..count_out - 1 {
-//         if i == 0 {
-            
-//             let id = egraph.add(Prop::from_op("&", vec![id2concat[i as usize], id2concat[(i + 1) as usize]]).expect("REASON"));
-//             concat.push(id);
-//         } else {
-//             let id = egraph.add(Prop::from_op("&", vec![concat[(i - 1) as usize], id2concat[(i + 1) as usize]]).expect("REASON"));
-//             concat.push(id);
-//         }
-//     }
-//     let last_element: Id = if let Some(element) = concat.pop() {
-//         element
-//     } else {
-//         one_out_sig = 1;
-//         let id: Id = id2concat.pop().unwrap().into();
-//         vars.insert(out.keys().next().unwrap().to_string(), id.into());
-//         println!("one_out_id: {}", id) ;   
-//         id
-//         //print id
-   
-//     };
-//     egraph.rebuild();
-//     let json_str = serde_json::to_string_pretty(&egraph).unwrap();
-
-//     let output_dir = Path::new(file_name).parent().unwrap_or(Path::new(""));
-//     let output_file = format!(
-//         "{}.json",
-//         PathBuf::from(file_name)
-//             .file_name()
-//             .unwrap()
-//             .to_string_lossy()
-//     );
-//     let output_path = output_dir.join(output_file);
-//     fs::write(output_path, json_str).expect("Failed to write JSON file");
-//     (egraph,last_element, input_id, one_out_sig)
-// }
-
-pub fn preprocess_file_concat(file_name: &str) -> Result<(), io::Error> {
-    // Open the file for reading
-    let file = File::open(file_name)?;
-    let reader = BufReader::new(file);
-
-    // Prepare a string to hold the new contents of the file
-    let mut new_contents = String::new();
-
-    // Flags to detect INORDER and OUTORDER sections
-    let mut in_inorder_section = false;
-    let mut in_outorder_section = false;
-
-    for line in reader.lines() {
-        let line = line?;
-
-        // Check if we're entering the INORDER section
-        if line.trim().starts_with("INORDER") {
-            in_inorder_section = true;
-            if line.trim().ends_with(";") {
-                // Skip this function if INORDER is one line and ends with ;
-                in_inorder_section = false;
-                new_contents.push_str(&line);
-                new_contents.push('\n');
-                continue;
-            } else {
-                new_contents.push_str(&line.trim());
-                new_contents.push(' ');
-            }
-        // Check if we're entering the OUTORDER section
-        } else if line.trim().starts_with("OUTORDER") {
-            in_outorder_section = true;
-            if line.trim().ends_with(";") {
-                // Skip this function if OUTORDER is one line and ends with ;
-                in_outorder_section = false;
-                new_contents.push_str(&line);
-                new_contents.push('\n');
-                continue;
-            } else {
-                new_contents.push_str(&line.trim());
-                new_contents.push(' ');
-            }
-        } else if in_inorder_section || in_outorder_section {
-            // Continue appending lines that are part of INORDER or OUTORDER sections
-            new_contents.push_str(&line.trim());
-            if line.trim().ends_with(";") {
-                // End of section, reset flags
-                in_inorder_section = false;
-                in_outorder_section = false;
-                new_contents.push('\n');
-            } else {
-                new_contents.push(' ');
-            }
-        } else {
-            // Append lines that are not part of INORDER or OUTORDER sections
-            new_contents.push_str(&line);
-            new_contents.push('\n');
-        }
-    }
-
-    // Open the same file for writing
-    let mut file = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .open(file_name)?;
-
-    // Write the new contents to the file
-    file.write_all(new_contents.as_bytes())?;
-
-    Ok(())
-}
-
-pub fn preprocess_file_order(file_name: &str) -> Result<(), io::Error> {
-    // Open the file for reading
-    let file = File::open(file_name)?;
-    let reader = BufReader::new(file);
-
-    // Search for the first line starting with "new_"
-    let mut variables = Vec::new();
-    for line in reader.lines() {
-        let line = line?;
-        if  !line.starts_with("OUTORDER")&&!line.starts_with("INORDER")&&!line.starts_with("new_") &&!line.starts_with("# Equations") {
-            let variable = line.split('=').next().unwrap().trim().to_string();
-            variables.push(variable.clone());
-            //print varibale
-           // println!("variable: {}", variable);
-        } 
-    }
-
-    // Generate new OUTORDER line
-    let outorder = variables.join(" ");
-    let new_outorder = format!("OUTORDER = {};", outorder.trim_end());
-
-    // Create a temporary file
-    let temp_file_name = format!("{}_temp", file_name);
-    let temp_file = File::create(&temp_file_name)?;
-    let mut writer = BufWriter::new(temp_file);
-
-    // Write modified content to the temporary file
-    let input_file = File::open(file_name)?;
-    let reader = BufReader::new(input_file);
-
-    for line in reader.lines() {
-        let line = line?;
-        if line.starts_with("OUTORDER") {
-            writeln!(writer, "{}", new_outorder)?;
-        } else {
-            writeln!(writer, "{}", line)?;
-        }
-    }
-
-    // Flush and close the writer
-    writer.flush()?;
-    drop(writer);
-
-    // Replace the original file with the temporary file
-    fs::rename(&temp_file_name, file_name)?;
-
-    Ok(())
-}
-
 // -----------------------------Unused Functions----------------------------------
 
 pub fn process_json_prop_prallel(json_file: &str) -> String {
@@ -1,7 +1,12 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-use rand::Rng;
-
-
+// Generator for the random choices of one rewriting task. Batch mode seeds
+// circuit `task` with `seed ^ task`, so each circuit is reproducible on its
+// own and the rayon workers share no state.
+pub fn task_rng(seed: u64, task: usize) -> StdRng {
+    StdRng::seed_from_u64(seed ^ task as u64)
+}
 
 // pub fn generate_random_float() -> f64 {
 //     let mut rng = rand::thread_rng();
@@ -9,8 +14,7 @@ use rand::Rng;
 //     random_float
 // }
 
-pub fn generate_random_float1() -> f64 {
-    let mut rng = rand::thread_rng();
+pub fn generate_random_float1(rng: &mut impl Rng) -> f64 {
     let random_float: f64 = rng.gen_range(0.0..1.0);
     random_float
-}
\ No newline at end of file
+}
@@ -0,0 +1,147 @@
+use egg::*;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::utils::language::*;
+
+// Rule files hold one rewrite per line in the syntax of `rewrite!`:
+//
+//   # comment
+//   commutativity1: (* ?b ?c) <=> (* ?c ?b)
+//   null-element1: (* ?b 0) => 0
+//
+// A `<=>` rule also adds the reverse direction, named `<name>-rev`.
+
+#[derive(Debug, Error)]
+pub enum RuleFileError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSpec {
+    pub name: String,
+    pub lhs: String,
+    pub rhs: String,
+    pub bidirectional: bool,
+}
+
+impl fmt::Display for RuleSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = if self.bidirectional { "<=>" } else { "=>" };
+        write!(f, "{}: {} {} {}", self.name, self.lhs, arrow, self.rhs)
+    }
+}
+
+impl RuleSpec {
+    pub fn to_rewrites<N: Analysis<Prop>>(&self) -> Result<Vec<Rewrite<Prop, N>>, String> {
+        let lhs: Pattern<Prop> = self.lhs.parse().map_err(|err| format!("{:?}", err))?;
+        let rhs: Pattern<Prop> = self.rhs.parse().map_err(|err| format!("{:?}", err))?;
+        let mut rewrites = vec![Rewrite::new(self.name.as_str(), lhs.clone(), rhs.clone())?];
+        if self.bidirectional {
+            rewrites.push(Rewrite::new(format!("{}-rev", self.name), rhs, lhs)?);
+        }
+        Ok(rewrites)
+    }
+}
+
+pub fn parse_rule_file(src: &str) -> Result<Vec<RuleSpec>, RuleFileError> {
+    let mut specs = Vec::new();
+    for (index, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let syntax = |message: &str| RuleFileError::Syntax {
+            line: index + 1,
+            message: message.to_string(),
+        };
+
+        let (name, rule) = line.split_once(':').ok_or_else(|| syntax("expected `name: lhs => rhs`"))?;
+        let (lhs, rhs, bidirectional) = if let Some((lhs, rhs)) = rule.split_once("<=>") {
+            (lhs, rhs, true)
+        } else if let Some((lhs, rhs)) = rule.split_once("=>") {
+            (lhs, rhs, false)
+        } else {
+            return Err(syntax("expected `=>` or `<=>`"));
+        };
+        let spec = RuleSpec {
+            name: name.trim().to_string(),
+            lhs: lhs.trim().to_string(),
+            rhs: rhs.trim().to_string(),
+            bidirectional,
+        };
+        if spec.name.is_empty() || spec.lhs.is_empty() || spec.rhs.is_empty() {
+            return Err(syntax("empty rule name or pattern"));
+        }
+        // Check the patterns now so errors carry the line number
+        spec.to_rewrites::<()>().map_err(|err| syntax(&err))?;
+        specs.push(spec);
+    }
+    Ok(specs)
+}
+
+pub fn read_rule_file<N: Analysis<Prop>>(path: &Path) -> Result<Vec<Rewrite<Prop, N>>, RuleFileError> {
+    let specs = parse_rule_file(&fs::read_to_string(path)?)?;
+    let mut rewrites = Vec::new();
+    for spec in specs {
+        rewrites.extend(spec.to_rewrites().expect("checked while parsing"));
+    }
+    Ok(rewrites)
+}
+
+pub fn write_rule_file(path: &Path, header: &str, specs: &[RuleSpec]) -> io::Result<()> {
+    let mut contents = String::new();
+    for line in header.lines() {
+        contents.push_str(&format!("# {}\n", line));
+    }
+    for spec in specs {
+        contents.push_str(&format!("{}\n", spec));
+    }
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let specs = vec![
+            RuleSpec {
+                name: "commutativity1".to_string(),
+                lhs: "(* ?b ?c)".to_string(),
+                rhs: "(* ?c ?b)".to_string(),
+                bidirectional: true,
+            },
+            RuleSpec {
+                name: "null-element1".to_string(),
+                lhs: "(* ?b 0)".to_string(),
+                rhs: "0".to_string(),
+                bidirectional: false,
+            },
+        ];
+        let path = std::env::temp_dir().join(format!("e-rewriter-rules-{}.txt", std::process::id()));
+        write_rule_file(&path, "synthesized rules\nsecond header line", &specs).unwrap();
+        let src = fs::read_to_string(&path).unwrap();
+        let rewrites = read_rule_file::<()>(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(src.starts_with("# synthesized rules\n# second header line\n"));
+        assert_eq!(parse_rule_file(&src).unwrap(), specs);
+        let names: Vec<String> = rewrites.iter().map(|rewrite| rewrite.name.to_string()).collect();
+        assert_eq!(names, ["commutativity1", "commutativity1-rev", "null-element1"]);
+    }
+
+    #[test]
+    fn errors_carry_the_line() {
+        let err = parse_rule_file("# header\n\nbad: (* ?b ?c) -> (* ?c ?b)\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: expected `=>` or `<=>`");
+        assert!(parse_rule_file("unbound: (* ?b 0) => ?c\n").unwrap_err().to_string().starts_with("line 1: "));
+    }
+}
@@ -8,6 +8,14 @@ use egg::*;
 use serde_json::Value;
 use serde::__private::fmt::Display;
 use std::io;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use num::ToPrimitive;
+use std::collections::HashMap;
 /** Faciliates running rewrites over an [`EGraph`].
 
 One use for [`EGraph`]s is as the basis of a rewriting system.
@@ -161,6 +169,8 @@ pub struct Runner<L: Language, N: Analysis<L>, IterData = ()> {
     time_limit: Duration,
     target_delay: f64,
     check_interval:i32,
+    // Iterations restored by `resume`, not counted against the iteration limit
+    first_iteration: usize,
     start_time: Option<Instant>,
     scheduler: Box<dyn RewriteScheduler<L, N>>,
 }
@@ -194,6 +204,7 @@ where
             time_limit,
             target_delay,
             check_interval,
+            first_iteration,
             start_time,
             scheduler: _,
         } = self;
@@ -207,6 +218,7 @@ where
             .field("iter_limit", iter_limit)
             .field("node_limit", node_limit)
             .field("time_limit", time_limit)
+            .field("first_iteration", first_iteration)
             .field("start_time", start_time)
             .field("scheduler", &format_args!("<dyn RewriteScheduler ..>"))
             .finish()
@@ -215,8 +227,7 @@ where
 
 /// Error returned by [`Runner`] when it stops.
 ///
-#[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde-1", derive(serde::Serialize))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum StopReason {
     /// The egraph saturated, i.e., there was an iteration where we
     /// didn't learn anything new from applying the rules.
@@ -236,8 +247,7 @@ pub enum StopReason {
 /// This is basically a summary of the [`Iteration`] data,
 /// but summed across iterations.
 /// See [`Iteration`] docs for details about fields.
-#[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde-1", derive(serde::Serialize))]
+#[derive(Debug, Clone, serde::Serialize)]
 #[non_exhaustive]
 #[allow(missing_docs)]
 pub struct Report {
@@ -271,15 +281,66 @@ impl std::fmt::Display for Report {
     }
 }
 
+/// The state of a [`Runner`] between two iterations, written by
+/// [`save_checkpoint`](Runner::save_checkpoint()) in bincode and
+/// continued with [`resume`](Runner::resume()).
+pub struct Checkpoint<L: Language, N: Analysis<L>, IterData> {
+    /// Type name of the analysis the egraph was saved with.
+    pub analysis: String,
+    pub egraph: EGraph<L, N>,
+    pub roots: Vec<Id>,
+    pub iterations: Vec<Iteration<IterData>>,
+    pub scheduler: SchedulerState,
+}
+
+/// Error reading or writing a [`Checkpoint`].
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("malformed checkpoint: {0}")]
+    Format(#[from] bincode::Error),
+    #[error("checkpoint was saved with analysis {found}, expected {expected}")]
+    Analysis { expected: String, found: String },
+}
+
+impl<L, N, IterData> Checkpoint<L, N, IterData>
+where
+    L: Language,
+    N: Analysis<L>,
+    EGraph<L, N>: DeserializeOwned,
+    IterData: DeserializeOwned,
+{
+    /// Reads a checkpoint written with the same analysis type `N`.
+    pub fn load(path: &Path) -> Result<Self, CheckpointError> {
+        // Symbols deserialize as borrowed strings, which a reader can't lend
+        let bytes = fs::read(path)?;
+        let (analysis, egraph, roots, iterations, scheduler): (String, _, _, _, _) =
+            bincode::deserialize(&bytes)?;
+        let expected = std::any::type_name::<N>();
+        if analysis != expected {
+            return Err(CheckpointError::Analysis {
+                expected: expected.to_string(),
+                found: analysis,
+            });
+        }
+        Ok(Checkpoint {
+            analysis,
+            egraph,
+            roots,
+            iterations,
+            scheduler,
+        })
+    }
+}
+
 /// Data generated by running a [`Runner`] one iteration.
 ///
-/// If the `serde-1` feature is enabled, this implements
-/// [`serde::Serialize`][ser], which is useful if you want to output
-/// this as a JSON or some other format.
+/// This implements [`serde::Serialize`][ser], which is useful if you
+/// want to output this as a JSON or some other format.
 ///
 /// [ser]: https://docs.rs/serde/latest/serde/trait.Serialize.html
-#[derive(Debug, Clone)]
-#[cfg_attr(feature = "serde-1", derive(serde::Serialize))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct Iteration<IterData> {
     /// The number of enodes in the egraph at the start of this
@@ -291,6 +352,9 @@ pub struct Iteration<IterData> {
     /// A map from rule name to number of times it was _newly_ applied
     /// in this iteration.
     pub applied: IndexMap<Symbol, usize>,
+    /// Per-rule search and apply statistics for this iteration, for
+    /// every rule that was searched.
+    pub rules: IndexMap<Symbol, RuleIteration>,
     /// Seconds spent running hooks.
     pub hook_time: f64,
     /// Seconds spent searching in this iteration.
@@ -310,6 +374,21 @@ pub struct Iteration<IterData> {
     pub stop_reason: Option<StopReason>,
 }
 
+/// What a single rule did during one [`Iteration`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleIteration {
+    /// Matches returned by the scheduler's search (0 while banned).
+    pub matches: usize,
+    /// Matches that were _newly_ applied.
+    pub applied: usize,
+    /// Seconds spent searching for this rule.
+    pub search_time: f64,
+    /// Seconds spent applying this rule.
+    pub apply_time: f64,
+    /// The number of enodes added to the egraph while applying this rule.
+    pub nodes_added: usize,
+}
+
 type RunnerResult<T> = std::result::Result<T, StopReason>;
 
 impl<L, N, IterData> Runner<L, N, IterData>
@@ -326,6 +405,7 @@ where
             time_limit: Duration::from_secs(5),
             target_delay: 0.0,
             check_interval:0,
+            first_iteration: 0,
             egraph: EGraph::new(analysis),
             roots: vec![],
             iterations: vec![],
@@ -397,6 +477,98 @@ where
         self
     }
 
+    /// Prune the egraph with [`prune_by_cost`] before every iteration,
+    /// dropping e-nodes that cost more than `factor` times the best
+    /// e-node of their class.
+    ///
+    /// Each pruning recomputes the best cost of every class from scratch,
+    /// sweeping all e-nodes until no cost improves, so it costs at least
+    /// one full pass over the egraph per iteration and one more pass per
+    /// level of the deepest best term.
+    pub fn with_cost_pruning<CF>(self, mut cost_function: CF, factor: f64) -> Self
+    where
+        CF: CostFunction<L> + 'static,
+        CF::Cost: ToPrimitive,
+        L: 'static,
+        N: 'static,
+        IterData: 'static,
+    {
+        assert!(factor >= 1.0, "pruning factor must be at least 1");
+        self.with_hook(move |runner| {
+            let pruned = prune_by_cost(&mut runner.egraph, &mut cost_function, factor);
+            info!("Pruned {} enodes", pruned);
+            Ok(())
+        })
+    }
+
+    /// Save a [`Checkpoint`] to `path` every `interval` iterations.
+    /// The file is replaced each time, call
+    /// [`save_checkpoint`](Runner::save_checkpoint()) after the run
+    /// to also keep the final state.
+    pub fn with_checkpoint(self, path: PathBuf, interval: usize) -> Self
+    where
+        L: Serialize + 'static,
+        N: 'static,
+        EGraph<L, N>: Serialize,
+        IterData: Serialize + 'static,
+    {
+        assert!(interval > 0, "checkpoint interval must be positive");
+        self.with_hook(move |runner| {
+            let done = runner.iterations.len() - runner.first_iteration;
+            if done > 0 && done % interval == 0 {
+                // A failed checkpoint shouldn't throw away the run
+                if let Err(err) = runner.save_checkpoint(&path) {
+                    log::warn!("Failed to write checkpoint {}: {}", path.display(), err);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Continue from a [`Checkpoint`]: its egraph, roots, iterations
+    /// and scheduler state replace those of this runner. The limits
+    /// stay those of this runner, and the iteration limit only counts
+    /// iterations after the checkpoint.
+    ///
+    /// Call this after [`with_scheduler`](Runner::with_scheduler()),
+    /// the state is restored into the current scheduler.
+    pub fn resume(mut self, checkpoint: Checkpoint<L, N, IterData>) -> Self {
+        self.egraph = checkpoint.egraph;
+        // The op index isn't serialized, rebuilding restores it so
+        // patterns match again
+        self.egraph.rebuild();
+        self.roots = checkpoint.roots;
+        self.first_iteration = checkpoint.iterations.len();
+        self.iterations = checkpoint.iterations;
+        self.scheduler.restore_state(checkpoint.scheduler);
+        self
+    }
+
+    /// Writes the egraph, roots, iterations and scheduler state to
+    /// `path`, see [`Checkpoint`].
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), CheckpointError>
+    where
+        EGraph<L, N>: Serialize,
+        IterData: Serialize,
+    {
+        // Same layout as `Checkpoint`, without cloning the egraph
+        let checkpoint = (
+            std::any::type_name::<N>(),
+            &self.egraph,
+            &self.roots,
+            &self.iterations,
+            self.scheduler.save_state(),
+        );
+        // Write next to the old checkpoint first, so an interrupted
+        // write leaves the old one intact
+        let partial = path.with_extension("partial");
+        let mut file = io::BufWriter::new(fs::File::create(&partial)?);
+        bincode::serialize_into(&mut file, &checkpoint)?;
+        io::Write::flush(&mut file)?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
     /// Change out the [`RewriteScheduler`] used by this [`Runner`].
     /// The default one is [`BackoffScheduler`].
     ///
@@ -442,7 +614,7 @@ where
             self.iterations.push(iter);
             let stop_reason = self.iterations.last().unwrap().stop_reason.clone();
             iteration_count += 1;
-            if iteration_count % self.check_interval == 0{
+            if self.check_interval > 0 && iteration_count % self.check_interval == 0{
 
 
 
@@ -557,6 +729,28 @@ where
         println!("{}", self.report())
     }
 
+    /// Writes the per-rule statistics of every [`Iteration`] as CSV,
+    /// one row per rule and iteration.
+    pub fn write_rule_stats_csv(&self, path: &Path) -> csv::Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["iteration", "rule", "matches", "applied", "search_time", "apply_time", "nodes_added"])?;
+        for (i, iteration) in self.iterations.iter().enumerate() {
+            for (name, rule) in &iteration.rules {
+                writer.write_record(&[
+                    i.to_string(),
+                    name.to_string(),
+                    rule.matches.to_string(),
+                    rule.applied.to_string(),
+                    rule.search_time.to_string(),
+                    rule.apply_time.to_string(),
+                    rule.nodes_added.to_string(),
+                ])?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
     /// Creates a [`Report`] summarizing this `Runner`s run.
     pub fn report(&self) -> Report {
         Report {
@@ -604,9 +798,14 @@ where
 
         let mut matches = Vec::new();
         let mut applied = IndexMap::default();
+        let mut rule_iterations: IndexMap<Symbol, RuleIteration> = IndexMap::default();
         result = result.and_then(|_| {
             rules.iter().try_for_each(|rw| {
+                let search_start = Instant::now();
                 let ms = self.scheduler.search_rewrite(i, &self.egraph, rw);
+                let rule = rule_iterations.entry(rw.name).or_default();
+                rule.matches += ms.iter().map(|m| m.substs.len()).sum::<usize>();
+                rule.search_time += search_start.elapsed().as_secs_f64();
                 matches.push(ms);
                 self.check_limits()
             })
@@ -622,7 +821,13 @@ where
                 let total_matches: usize = ms.iter().map(|m| m.substs.len()).sum();
                 debug!("Applying {} {} times", rw.name, total_matches);
 
+                let nodes_before = self.egraph.total_size();
+                let apply_start = Instant::now();
                 let actually_matched = self.scheduler.apply_rewrite(i, &mut self.egraph, rw, ms);
+                let rule = rule_iterations.entry(rw.name).or_default();
+                rule.applied += actually_matched;
+                rule.apply_time += apply_start.elapsed().as_secs_f64();
+                rule.nodes_added += self.egraph.total_size().saturating_sub(nodes_before);
                 if actually_matched > 0 {
                     if let Some(count) = applied.get_mut(&rw.name) {
                         *count += actually_matched;
@@ -666,6 +871,7 @@ where
 
         Iteration {
             applied,
+            rules: rule_iterations,
             egraph_nodes,
             egraph_classes,
             hook_time,
@@ -694,7 +900,7 @@ where
             return Err(StopReason::NodeLimit(size));
         }
 
-        if self.iterations.len() >= self.iter_limit {
+        if self.iterations.len() - self.first_iteration >= self.iter_limit {
             return Err(StopReason::IterationLimit(self.iterations.len()));
         }
 
@@ -702,6 +908,87 @@ where
     }
 }
 
+/// Bottom-up best cost of every class that has a finite cost.
+fn best_costs<L, N, CF>(egraph: &EGraph<L, N>, cost_function: &mut CF) -> HashMap<Id, CF::Cost>
+where
+    L: Language,
+    N: Analysis<L>,
+    CF: CostFunction<L>,
+{
+    let mut costs: HashMap<Id, CF::Cost> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for class in egraph.classes() {
+            let best = class
+                .nodes
+                .iter()
+                .filter(|node| node.all(|child| costs.contains_key(&egraph.find(child))))
+                .map(|node| cost_function.cost(node, |child| costs[&egraph.find(child)].clone()))
+                .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            if let Some(best) = best {
+                if costs.get(&class.id).is_none_or(|old| best < *old) {
+                    costs.insert(class.id, best);
+                    changed = true;
+                }
+            }
+        }
+    }
+    costs
+}
+
+/// Removes the e-nodes whose cost, with every child at its best cost,
+/// exceeds `factor` times the best cost of their class, and returns how
+/// many were removed.
+///
+/// Pruned e-nodes stay in the hashcons, so rules can't add them back,
+/// but they are no longer matched or extracted, which keeps the rules
+/// from building on structure that would never be extracted. Every class
+/// keeps its best e-node, and e-nodes without a finite cost yet are kept.
+pub fn prune_by_cost<L, N, CF>(egraph: &mut EGraph<L, N>, cost_function: &mut CF, factor: f64) -> usize
+where
+    L: Language,
+    N: Analysis<L>,
+    CF: CostFunction<L>,
+    CF::Cost: ToPrimitive,
+{
+    let costs = best_costs(egraph, cost_function);
+    let cost = |cost: &CF::Cost| cost.to_f64().unwrap_or(f64::INFINITY);
+
+    // Decide on a shared borrow, then remove
+    let mut keep: HashMap<Id, Vec<bool>> = HashMap::new();
+    for class in egraph.classes() {
+        let Some(best) = costs.get(&class.id) else {
+            continue;
+        };
+        let bound = factor * cost(best);
+        let flags = class
+            .nodes
+            .iter()
+            .map(|node| {
+                if !node.all(|child| costs.contains_key(&egraph.find(child))) {
+                    return true;
+                }
+                let node_cost = cost_function.cost(node, |child| costs[&egraph.find(child)].clone());
+                cost(&node_cost) <= bound
+            })
+            .collect();
+        keep.insert(class.id, flags);
+    }
+
+    let mut pruned = 0;
+    for class in egraph.classes_mut() {
+        let Some(flags) = keep.get(&class.id) else {
+            continue;
+        };
+        let before = class.nodes.len();
+        let mut flags = flags.iter();
+        class.nodes.retain(|_| *flags.next().unwrap());
+        pruned += before - class.nodes.len();
+    }
+    pruned
+}
+
 fn check_rules<L, N>(rules: &[&Rewrite<L, N>]) {
     let mut name_counts:IndexMap<egg::Symbol, usize>= IndexMap::default();
     for rw in rules {
@@ -771,6 +1058,33 @@ where
     ) -> usize {
         rewrite.apply(egraph, &matches).len()
     }
+
+    /// The state to keep in a [`Checkpoint`].
+    ///
+    /// Default implementation has no state.
+    fn save_state(&self) -> SchedulerState {
+        SchedulerState::Stateless
+    }
+
+    /// Restores the state from [`save_state`](RewriteScheduler::save_state())
+    /// when a [`Runner`] resumes.
+    ///
+    /// Default implementation ignores it.
+    fn restore_state(&mut self, state: SchedulerState) {}
+}
+
+/// What a [`RewriteScheduler`] needs to continue where it stopped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub enum SchedulerState {
+    #[default]
+    Stateless,
+    Backoff(BackoffState),
+}
+
+/// Bans and ban counts of a [`BackoffScheduler`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackoffState {
+    stats: IndexMap<Symbol, RuleStats>,
 }
 
 /// A very simple [`RewriteScheduler`] that runs every rewrite every
@@ -813,7 +1127,7 @@ pub struct BackoffScheduler {
     stats: IndexMap<Symbol, RuleStats>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct RuleStats {
     times_applied: usize,
     banned_until: usize,
@@ -870,6 +1184,64 @@ impl BackoffScheduler {
     }
 }
 
+/// Limits for a [`BackoffScheduler`], read from a JSON file like
+///
+/// ```json
+/// {
+///     "match_limit": 1000,
+///     "ban_length": 5,
+///     "rules": {
+///         "associativity1": { "match_limit": 200, "ban_length": 10 },
+///         "commutativity1": { "do_not_ban": true }
+///     }
+/// }
+/// ```
+///
+/// All fields are optional.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackoffConfig {
+    pub match_limit: Option<usize>,
+    pub ban_length: Option<usize>,
+    #[serde(default)]
+    pub rules: BTreeMap<String, RuleBackoffConfig>,
+}
+
+/// Per-rule overrides in a [`BackoffConfig`].
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleBackoffConfig {
+    pub match_limit: Option<usize>,
+    pub ban_length: Option<usize>,
+    #[serde(default)]
+    pub do_not_ban: bool,
+}
+
+impl BackoffConfig {
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Applies the per-rule overrides on top of `scheduler`. The default
+    /// limits of `scheduler` must already be set, rules configured here
+    /// start from them.
+    pub fn apply(&self, mut scheduler: BackoffScheduler) -> BackoffScheduler {
+        for (name, rule) in &self.rules {
+            if let Some(limit) = rule.match_limit {
+                scheduler = scheduler.rule_match_limit(name.as_str(), limit);
+            }
+            if let Some(length) = rule.ban_length {
+                scheduler = scheduler.rule_ban_length(name.as_str(), length);
+            }
+            if rule.do_not_ban {
+                scheduler = scheduler.do_not_ban(name.as_str());
+            }
+        }
+        scheduler
+    }
+}
+
 impl Default for BackoffScheduler {
     fn default() -> Self {
         Self {
@@ -968,6 +1340,24 @@ where
             matches
         }
     }
+
+    fn save_state(&self) -> SchedulerState {
+        SchedulerState::Backoff(BackoffState {
+            stats: self.stats.clone(),
+        })
+    }
+
+    // Limits come from this scheduler's configuration, only the bans carry over
+    fn restore_state(&mut self, state: SchedulerState) {
+        if let SchedulerState::Backoff(state) = state {
+            for (name, saved) in state.stats {
+                let stats = self.rule_stats(name);
+                stats.times_applied = saved.times_applied;
+                stats.banned_until = saved.banned_until;
+                stats.times_banned = saved.times_banned;
+            }
+        }
+    }
 }
 
 /// Custom data to inject into the [`Iteration`]s recorded by a [`Runner`]
@@ -996,4 +1386,102 @@ where
     N: Analysis<L>,
 {
     fn make(_: &Runner<L, N, Self>) -> Self {}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::language::{make_rules, Prop};
+
+    fn runner(iter_limit: usize) -> Runner<Prop, ()> {
+        let expr: RecExpr<Prop> = "(+ (* a (+ b c)) (* (! a) (+ (* b d) c)))".parse().unwrap();
+        let mut runner = Runner::new(())
+            .with_expr(&expr)
+            .with_iter_limit(iter_limit)
+            .with_scheduler(SimpleScheduler);
+        runner.egraph.rebuild();
+        runner
+    }
+
+    #[test]
+    fn resumed_run_grows_like_an_uninterrupted_one() {
+        let rules = make_rules::<()>();
+        let path = std::env::temp_dir().join(format!("e-rewriter-checkpoint-{}.bin", std::process::id()));
+        runner(1).run(&rules).save_checkpoint(&path).unwrap();
+        let checkpoint = Checkpoint::<Prop, (), ()>::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(checkpoint.iterations.len(), 1);
+
+        let resumed = runner(1).resume(checkpoint).run(&rules);
+        let uninterrupted = runner(2).run(&rules);
+        assert!(matches!(uninterrupted.stop_reason, Some(StopReason::IterationLimit(2))));
+        assert_eq!(resumed.iterations.len(), 2);
+        assert_eq!(resumed.egraph.total_number_of_nodes(), uninterrupted.egraph.total_number_of_nodes());
+        assert_eq!(resumed.egraph.number_of_classes(), uninterrupted.egraph.number_of_classes());
+    }
+
+    #[test]
+    fn rule_stats_csv_has_a_row_per_rule_and_iteration() {
+        let runner = runner(2).run(&make_rules::<()>());
+        let path = std::env::temp_dir().join(format!("e-rewriter-rule-stats-{}.csv", std::process::id()));
+        runner.write_rule_stats_csv(&path).unwrap();
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let rows: Vec<csv::StringRecord> = reader.records().collect::<Result<_, _>>().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            header.iter().collect::<Vec<_>>(),
+            ["iteration", "rule", "matches", "applied", "search_time", "apply_time", "nodes_added"]
+        );
+        assert_eq!(runner.iterations.len(), 2);
+        let expected: usize = runner.iterations.iter().map(|iteration| iteration.rules.len()).sum();
+        assert!(expected > 0);
+        assert_eq!(rows.len(), expected);
+        assert!(rows.iter().all(|row| row[0] == *"0" || row[0] == *"1"));
+    }
+
+    #[test]
+    fn backoff_config_overrides_reach_the_scheduler() {
+        let config: BackoffConfig = serde_json::from_str(
+            r#"{
+                "match_limit": 500,
+                "rules": {
+                    "commutativity1": { "match_limit": 0, "ban_length": 3 },
+                    "associativity1": { "do_not_ban": true }
+                }
+            }"#,
+        )
+        .unwrap();
+        let scheduler = config.apply(BackoffScheduler::default().with_initial_match_limit(config.match_limit.unwrap()));
+        assert_eq!(scheduler.default_match_limit, 500);
+        let commutativity = &scheduler.stats[&Symbol::from("commutativity1")];
+        assert_eq!((commutativity.match_limit, commutativity.ban_length), (0, 3));
+        assert_eq!(scheduler.stats[&Symbol::from("associativity1")].match_limit, usize::MAX);
+
+        // A match limit of 0 bans commutativity1 on its first search
+        let rules = make_rules::<()>();
+        let matches = |runner: Runner<Prop, ()>| {
+            runner.run(&rules).iterations[0].rules[&Symbol::from("commutativity1")].matches
+        };
+        assert_eq!(matches(runner(1).with_scheduler(scheduler)), 0);
+        assert!(matches(runner(1).with_scheduler(BackoffScheduler::default())) > 0);
+    }
+
+    #[test]
+    fn pruning_drops_expensive_nodes_and_keeps_the_best() {
+        let mut egraph = EGraph::<Prop, ()>::default();
+        let and = egraph.add_expr(&"(* a b)".parse().unwrap());
+        let double_not = egraph.add_expr(&"(! (! (* a b)))".parse().unwrap());
+        egraph.union(and, double_not);
+        egraph.rebuild();
+        let and = egraph.find(and);
+        assert_eq!(egraph[and].nodes.len(), 2);
+
+        // (! (! (* a b))) costs 5 against the best 3
+        assert_eq!(prune_by_cost(&mut egraph, &mut egg::AstSize, 2.0), 0);
+        assert_eq!(prune_by_cost(&mut egraph, &mut egg::AstSize, 1.5), 1);
+        assert_eq!(egraph[and].nodes.len(), 1);
+        assert!(matches!(egraph[and].nodes[0], Prop::And(_)));
+        assert_eq!(egraph.number_of_classes(), 4);
+    }
+}
@@ -0,0 +1,253 @@
+use egg::*;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::utils::language::*;
+use crate::utils::runner_modified::{RewriteScheduler, SchedulerState};
+
+// Number of 64-bit words in a signature, i.e. 256 random input patterns
+pub const SIGNATURE_WORDS: usize = 4;
+
+// Output of a class under bit-parallel random simulation of the primary inputs
+pub type Signature = [u64; SIGNATURE_WORDS];
+
+// E-class analysis carrying a random-simulation signature per class.
+// Two classes with different signatures are not equivalent, so every union is
+// checked and a mismatch aborts with the rule that caused it. Equal signatures
+// don't prove equivalence, this is a cheap guard, not a replacement for `cec`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Simulation {
+    pub seed: u64,
+    // Rule currently being applied, set by `TrackRules`
+    pub current_rule: Option<Symbol>,
+}
+
+impl Simulation {
+    pub fn new(seed: u64) -> Self {
+        Simulation {
+            seed,
+            current_rule: None,
+        }
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Random patterns of a primary input (or pattern variable), fixed by its name and the seed
+pub fn random_signature(seed: u64, name: &str) -> Signature {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    let mut state = hasher.finish() ^ seed;
+    let mut signature = [0; SIGNATURE_WORDS];
+    for word in signature.iter_mut() {
+        *word = splitmix64(&mut state);
+    }
+    signature
+}
+
+// One word of a Boolean node, each bit an independent pattern, given the word
+// of every child. Constants are evaluated here, other symbols and the
+// non-Boolean operators give `None`. The one place the semantics of the
+// Boolean operators are defined, every evaluator of `Prop` goes through it.
+pub fn eval_word(node: &Prop, child: impl Fn(Id) -> u64) -> Option<u64> {
+    Some(match node {
+        Prop::Bool(value) => if *value { !0 } else { 0 },
+        Prop::Symbol(name) => match name.as_str() {
+            "0" => 0,
+            "1" => !0,
+            _ => return None,
+        },
+        Prop::Not(a) => !child(*a),
+        Prop::And([a, b]) => child(*a) & child(*b),
+        Prop::Or([a, b]) => child(*a) | child(*b),
+        Prop::Xor([a, b]) => child(*a) ^ child(*b),
+        Prop::Implies([a, b]) => !child(*a) | child(*b),
+        Prop::Mux([s, t, e]) => {
+            let s = child(*s);
+            (s & child(*t)) | (!s & child(*e))
+        }
+        Prop::Maj([a, b, c]) => {
+            let (a, b, c) = (child(*a), child(*b), child(*c));
+            (a & b) | (a & c) | (b & c)
+        }
+        Prop::Let(_) | Prop::Concat(_) | Prop::Rooting(_) => return None,
+    })
+}
+
+// Simulates a single node, `signature` returns the signature of a child
+pub fn simulate_node(node: &Prop, seed: u64, signature: impl Fn(Id) -> Signature) -> Signature {
+    match node {
+        Prop::Symbol(name) if name.as_str() != "0" && name.as_str() != "1" => random_signature(seed, name.as_str()),
+        // Not Boolean functions, mix the children so only identical tuples collide
+        Prop::Let([a, b]) | Prop::Concat([a, b]) | Prop::Rooting([a, b]) => {
+            let (a, b) = (signature(*a), signature(*b));
+            let mut out = a;
+            for (o, b) in out.iter_mut().zip(b) {
+                *o = o.rotate_left(23) ^ b.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            }
+            out
+        }
+        _ => {
+            let mut out = [0; SIGNATURE_WORDS];
+            for (w, word) in out.iter_mut().enumerate() {
+                *word = eval_word(node, |child| signature(child)[w]).unwrap();
+            }
+            out
+        }
+    }
+}
+
+impl Analysis<Prop> for Simulation {
+    type Data = Signature;
+
+    fn make(egraph: &EGraph<Prop, Self>, enode: &Prop) -> Self::Data {
+        simulate_node(enode, egraph.analysis.seed, |id| egraph[id].data)
+    }
+
+    fn pre_union(egraph: &EGraph<Prop, Self>, id1: Id, id2: Id, justification: &Option<Justification>) {
+        if egraph[id1].data == egraph[id2].data {
+            return;
+        }
+        let rule = match justification {
+            Some(Justification::Rule(rule)) => Some(*rule),
+            _ => egraph.analysis.current_rule,
+        };
+        let cause = match (rule, justification) {
+            (Some(rule), _) => format!("rule '{}'", rule),
+            (None, Some(Justification::Congruence)) => "congruence".to_string(),
+            _ => "an unnamed union".to_string(),
+        };
+        panic!(
+            "Simulation signatures differ: {} merged non-equivalent classes {} and {}",
+            cause,
+            egraph.find(id1),
+            egraph.find(id2)
+        );
+    }
+
+    fn merge(&mut self, a: &mut Self::Data, b: Self::Data) -> DidMerge {
+        // pre_union already rejected unions of different signatures
+        debug_assert_eq!(*a, b);
+        DidMerge(false, false)
+    }
+}
+
+// Analyses that want to know which rule is being applied
+pub trait RuleTracking {
+    fn set_current_rule(&mut self, _rule: Option<Symbol>) {}
+}
+
+impl RuleTracking for () {}
+
+impl RuleTracking for Simulation {
+    fn set_current_rule(&mut self, rule: Option<Symbol>) {
+        self.current_rule = rule;
+    }
+}
+
+// Wraps a scheduler and tells the analysis which rule each union comes from
+pub struct TrackRules<S>(pub S);
+
+impl<N, S> RewriteScheduler<Prop, N> for TrackRules<S>
+where
+    N: Analysis<Prop> + RuleTracking,
+    S: RewriteScheduler<Prop, N>,
+{
+    fn can_stop(&mut self, iteration: usize) -> bool {
+        self.0.can_stop(iteration)
+    }
+
+    fn search_rewrite<'a>(
+        &mut self,
+        iteration: usize,
+        egraph: &EGraph<Prop, N>,
+        rewrite: &'a Rewrite<Prop, N>,
+    ) -> Vec<SearchMatches<'a, Prop>> {
+        self.0.search_rewrite(iteration, egraph, rewrite)
+    }
+
+    fn apply_rewrite(
+        &mut self,
+        iteration: usize,
+        egraph: &mut EGraph<Prop, N>,
+        rewrite: &Rewrite<Prop, N>,
+        matches: Vec<SearchMatches<Prop>>,
+    ) -> usize {
+        egraph.analysis.set_current_rule(Some(rewrite.name));
+        let applied = self.0.apply_rewrite(iteration, egraph, rewrite, matches);
+        egraph.analysis.set_current_rule(None);
+        applied
+    }
+
+    fn save_state(&self) -> SchedulerState {
+        self.0.save_state()
+    }
+
+    fn restore_state(&mut self, state: SchedulerState) {
+        self.0.restore_state(state)
+    }
+}
+
+fn simulate_pattern(pattern: &PatternAst<Prop>, seed: u64) -> Signature {
+    let mut signatures: Vec<Signature> = Vec::with_capacity(pattern.as_ref().len());
+    for node in pattern.as_ref() {
+        let signature = match node {
+            ENodeOrVar::Var(var) => random_signature(seed, &var.to_string()),
+            ENodeOrVar::ENode(node) => simulate_node(node, seed, |id| signatures[usize::from(id)]),
+        };
+        signatures.push(signature);
+    }
+    *signatures.last().expect("empty pattern")
+}
+
+// Names of the rules whose two sides simulate differently, i.e. rules that are
+// certainly unsound. Rules without a pattern on both sides are skipped.
+pub fn unsound_rules<N: Analysis<Prop>>(rules: &[Rewrite<Prop, N>], seed: u64) -> Vec<Symbol> {
+    rules
+        .iter()
+        .filter(|rule| {
+            match (rule.searcher.get_pattern_ast(), rule.applier.get_pattern_ast()) {
+                (Some(lhs), Some(rhs)) => simulate_pattern(lhs, seed) != simulate_pattern(rhs, seed),
+                _ => false,
+            }
+        })
+        .map(|rule| rule.name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::runner_modified::{Runner, SimpleScheduler};
+
+    #[test]
+    fn wrong_rule_is_flagged() {
+        let mut rules = make_rules::<()>();
+        rules.push(rewrite!("and-is-or"; "(* ?a ?b)" => "(+ ?a ?b)"));
+        assert_eq!(unsound_rules(&rules, 7), vec![Symbol::from("and-is-or")]);
+    }
+
+    #[test]
+    fn xmg_rules_are_sound() {
+        assert!(unsound_rules(&make_rules_xmg::<()>(), 7).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "rule 'and-is-or' merged non-equivalent classes")]
+    fn unsound_union_aborts() {
+        let rules = vec![rewrite!("and-is-or"; "(* ?a ?b)" => "(+ ?a ?b)")];
+        let expr: RecExpr<Prop> = "(* a b)".parse().unwrap();
+        let mut runner = Runner::<Prop, Simulation>::new(Simulation::new(7))
+            .with_expr(&expr)
+            .with_scheduler(TrackRules(SimpleScheduler));
+        runner.egraph.rebuild();
+        runner.run(&rules);
+    }
+}
@@ -0,0 +1,265 @@
+use egg::*;
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use crate::utils::language::*;
+use crate::utils::rule_file::RuleSpec;
+use crate::utils::simulation::eval_word;
+
+// Rule synthesis in the style of Ruler. Terms over `vars` variables are enumerated
+// layer by layer (by AST size) directly in an egraph. The truth table analysis
+// groups them by function over all 2^vars assignments, so any two terms with the
+// same table are equal for every value of the variables. After each layer the
+// egraph is saturated with the known rules, and while two classes with the same
+// table remain apart, the smallest rule merging them is added. Rules implied by
+// the known set (within the saturation limits) are never emitted.
+
+pub struct SynthConfig {
+    pub vars: usize,
+    pub max_size: usize,
+    pub ops: Vec<String>,
+    pub iter_limit: usize,
+    pub node_limit: usize,
+    pub time_limit: Duration,
+}
+
+// Truth table of a class over `vars` variables named ?a, ?b, ...
+#[derive(Debug, Clone, Default)]
+pub struct TruthTable {
+    pub vars: usize,
+}
+
+const PROJECTIONS: [u64; 6] = [
+    0xaaaa_aaaa_aaaa_aaaa,
+    0xcccc_cccc_cccc_cccc,
+    0xf0f0_f0f0_f0f0_f0f0,
+    0xff00_ff00_ff00_ff00,
+    0xffff_0000_ffff_0000,
+    0xffff_ffff_0000_0000,
+];
+
+impl TruthTable {
+    fn mask(&self) -> u64 {
+        if self.vars >= 6 {
+            !0
+        } else {
+            (1u64 << (1 << self.vars)) - 1
+        }
+    }
+}
+
+impl Analysis<Prop> for TruthTable {
+    type Data = u64;
+
+    fn make(egraph: &EGraph<Prop, Self>, enode: &Prop) -> Self::Data {
+        let table = match enode {
+            Prop::Symbol(name) if name.as_str() != "0" && name.as_str() != "1" => {
+                let index = name
+                    .as_str()
+                    .strip_prefix('?')
+                    .and_then(|v| v.chars().next())
+                    .and_then(|c| (c as usize).checked_sub('a' as usize));
+                match index {
+                    Some(index) if index < egraph.analysis.vars => PROJECTIONS[index],
+                    _ => panic!("unexpected leaf {} in rule synthesis", name),
+                }
+            }
+            _ => eval_word(enode, |id| egraph[id].data)
+                .unwrap_or_else(|| panic!("not a Boolean operator: {}", enode)),
+        };
+        table & egraph.analysis.mask()
+    }
+
+    fn merge(&mut self, a: &mut Self::Data, b: Self::Data) -> DidMerge {
+        assert_eq!(*a, b, "a rule merged terms with different truth tables");
+        DidMerge(false, false)
+    }
+}
+
+fn arity(op: &str) -> Option<usize> {
+    match op {
+        "!" => Some(1),
+        "*" | "+" | "^" | "->" => Some(2),
+        "mux" | "maj" => Some(3),
+        _ => None,
+    }
+}
+
+fn saturate(
+    egraph: EGraph<Prop, TruthTable>,
+    rules: &[Rewrite<Prop, TruthTable>],
+    config: &SynthConfig,
+) -> EGraph<Prop, TruthTable> {
+    Runner::<Prop, TruthTable, ()>::new(egraph.analysis.clone())
+        .with_egraph(egraph)
+        .with_iter_limit(config.iter_limit)
+        .with_node_limit(config.node_limit)
+        .with_time_limit(config.time_limit)
+        .with_scheduler(SimpleScheduler)
+        .run(rules)
+        .egraph
+}
+
+// Every tuple of `arity` classes whose smallest terms add up to `total` nodes
+fn tuples(by_size: &BTreeMap<usize, Vec<Id>>, arity: usize, total: usize) -> Vec<Vec<Id>> {
+    if arity == 0 || total == 0 {
+        return if arity == 0 && total == 0 { vec![vec![]] } else { vec![] };
+    }
+    let mut result = vec![];
+    for (&size, classes) in by_size.range(1..=total) {
+        for rest in tuples(by_size, arity - 1, total - size) {
+            for &class in classes {
+                let mut tuple = vec![class];
+                tuple.extend(&rest);
+                result.push(tuple);
+            }
+        }
+    }
+    result
+}
+
+fn pattern_vars(pattern: &str) -> HashSet<String> {
+    pattern
+        .split(|c: char| c == '(' || c == ')' || c.is_whitespace())
+        .filter(|token| token.starts_with('?'))
+        .map(str::to_string)
+        .collect()
+}
+
+// The smallest rule merging two classes with equal truth tables that are still apart
+fn next_rule(egraph: &EGraph<Prop, TruthTable>, name: String) -> Option<RuleSpec> {
+    let extractor = Extractor::new(egraph, AstSize);
+    let mut groups: BTreeMap<u64, Vec<(usize, String)>> = BTreeMap::new();
+    for class in egraph.classes() {
+        let (size, term) = extractor.find_best(class.id);
+        groups.entry(class.data).or_default().push((size, term.to_string()));
+    }
+
+    let mut best: Option<(usize, RuleSpec)> = None;
+    for terms in groups.values_mut().filter(|terms| terms.len() > 1) {
+        terms.sort();
+        let (rhs_size, rhs) = &terms[0];
+        for (lhs_size, lhs) in &terms[1..] {
+            let (lhs_vars, rhs_vars) = (pattern_vars(lhs), pattern_vars(rhs));
+            let (lhs, rhs) = if rhs_vars.is_subset(&lhs_vars) {
+                (lhs, rhs)
+            } else if lhs_vars.is_subset(&rhs_vars) {
+                (rhs, lhs)
+            } else {
+                continue;
+            };
+            let size = lhs_size + rhs_size;
+            if best.as_ref().is_none_or(|(best_size, _)| size < *best_size) {
+                let rule = RuleSpec {
+                    name: name.clone(),
+                    lhs: lhs.clone(),
+                    rhs: rhs.clone(),
+                    bidirectional: lhs_vars == rhs_vars,
+                };
+                best = Some((size, rule));
+            }
+        }
+    }
+    best.map(|(_, rule)| rule)
+}
+
+pub fn synthesize_rules(known: Vec<Rewrite<Prop, TruthTable>>, config: &SynthConfig) -> Result<Vec<RuleSpec>, String> {
+    assert!((1..=6).contains(&config.vars), "rule synthesis supports 1 to 6 variables");
+    for op in &config.ops {
+        if arity(op).is_none() {
+            return Err(format!("unsupported operator '{}' for rule synthesis", op));
+        }
+    }
+
+    let mut rules = known;
+    let mut synthesized = Vec::new();
+    let mut egraph = EGraph::new(TruthTable { vars: config.vars });
+    egraph.add(Prop::Symbol("0".into()));
+    egraph.add(Prop::Symbol("1".into()));
+    for var in 0..config.vars {
+        egraph.add(Prop::Symbol(format!("?{}", (b'a' + var as u8) as char).into()));
+    }
+    egraph.rebuild();
+
+    for size in 1..=config.max_size {
+        if size > 1 {
+            // Smallest term size of every class decides which layer it feeds
+            let extractor = Extractor::new(&egraph, AstSize);
+            let mut by_size: BTreeMap<usize, Vec<Id>> = BTreeMap::new();
+            for class in egraph.classes() {
+                by_size.entry(extractor.find_best_cost(class.id)).or_default().push(class.id);
+            }
+            let mut layer = Vec::new();
+            for op in &config.ops {
+                for children in tuples(&by_size, arity(op).unwrap(), size - 1) {
+                    layer.push(Prop::from_op(op, children).map_err(|err| format!("{:?}", err))?);
+                }
+            }
+            for node in layer {
+                egraph.add(node);
+            }
+            egraph.rebuild();
+        }
+
+        loop {
+            egraph = saturate(egraph, &rules, config);
+            let Some(rule) = next_rule(&egraph, format!("synth-{}", synthesized.len())) else {
+                break;
+            };
+            // Saturation stopped at a limit before the last rule fired, move on
+            if synthesized.iter().any(|known: &RuleSpec| known.lhs == rule.lhs && known.rhs == rule.rhs) {
+                break;
+            }
+            rules.extend(rule.to_rewrites()?);
+            synthesized.push(rule);
+        }
+    }
+    Ok(synthesized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::simulation::unsound_rules;
+
+    fn config() -> SynthConfig {
+        SynthConfig {
+            vars: 2,
+            max_size: 4,
+            ops: vec!["!".to_string(), "*".to_string(), "+".to_string()],
+            iter_limit: 10,
+            node_limit: 10_000,
+            time_limit: Duration::from_secs(10),
+        }
+    }
+
+    fn rewrites<N: Analysis<Prop>>(specs: &[RuleSpec]) -> Vec<Rewrite<Prop, N>> {
+        specs.iter().flat_map(|spec| spec.to_rewrites().unwrap()).collect()
+    }
+
+    #[test]
+    fn synthesized_rules_are_sound() {
+        let synthesized = synthesize_rules(vec![], &config()).unwrap();
+        assert!(!synthesized.is_empty());
+        assert!(unsound_rules(&rewrites::<()>(&synthesized), 0).is_empty());
+    }
+
+    #[test]
+    fn known_rules_are_not_emitted_again() {
+        let first = synthesize_rules(vec![], &config()).unwrap();
+        let second = synthesize_rules(rewrites(&first), &config()).unwrap();
+        assert!(second.is_empty(), "re-emitted {:?}", second.iter().map(|rule| (&rule.lhs, &rule.rhs)).collect::<Vec<_>>());
+
+        let known = make_rules::<TruthTable>();
+        let known_pairs: HashSet<(String, String)> = known
+            .iter()
+            .filter_map(|rule| Some((rule.searcher.get_pattern_ast()?.to_string(), rule.applier.get_pattern_ast()?.to_string())))
+            .collect();
+        let synthesized = synthesize_rules(known, &config()).unwrap();
+        assert!(synthesized.len() < first.len());
+        for rule in &synthesized {
+            assert!(!known_pairs.contains(&(rule.lhs.clone(), rule.rhs.clone())));
+            assert!(!known_pairs.contains(&(rule.rhs.clone(), rule.lhs.clone())));
+        }
+    }
+}
@@ -218,6 +218,20 @@ fn dag_to_equations(
             }
             result
         }
+        "^" | "mux" | "maj" => {
+            let operands: Vec<String> = node.children
+                .iter()
+                .map(|child_id| dag_to_equations(nodes, child_id, visited, visit_count, is_large))
+                .collect();
+
+            // The eqn format only has !, * and +, so write out the AND/OR expansion
+            match (node.op.as_str(), operands.as_slice()) {
+                ("^", [a, b]) => format!("(({} * !({})) + (!({}) * {}))", a, b, a, b),
+                ("mux", [s, t, e]) => format!("(({} * {}) + (!({}) * {}))", s, t, s, e),
+                ("maj", [a, b, c]) => format!("(({} * {}) + ({} * {}) + ({} * {}))", a, b, a, c, b, c),
+                _ => unreachable!(),
+            }
+        }
         _ => {
             let operands: Vec<String> = node.children
                 .iter()
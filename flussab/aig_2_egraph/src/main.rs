@@ -8,32 +8,21 @@ use std::time::Instant;
 use egg::*;
 mod parser;
 mod language;
-mod preprocess;
-use crate::preprocess::*;
 use crate::parser::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use crate::language::*;
 use flussab::DeferredWriter;
 use flussab_aiger::{
     aig::{Renumber, RenumberConfig},
     ascii, binary, Error,
 };
-use flussab_aiger::aig::Aig;
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 7 {
-        println!("Usage: <program> <path_to_input_json_file> <path_to_output_circuit_file> <output_file_path> <aig_2_egraph_nodemap_path> <iteration> <rewritten_path>");
-        std::process::exit(1);
-    }
-
-    let file_path = &args[1];
-    let output_path = &args[2];
-    let output_file_path = &args[3];
-    let aig_2_egraph_nodemap_path = &args[4];
-    let iteration = &args[5];
-    let rewritten_path = &args[6]; // 新增的路径参数
-
-    let mut file = File::open(file_path).expect("Unable to open file");
+use flussab_aiger::aig::{Aig, OrderedAig};
+use std::collections::HashMap;
+// Parses a binary AIGER file, renumbers it with structural hashing and constant
+// folding, writes the ASCII form to `aag_path` and returns the renumbered AIG
+// with its input and output names
+fn load_aig(path: &Path, aag_path: &Path) -> Result<(OrderedAig<u32>, Vec<String>, Vec<String>), Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
     let aig_reader = binary::Parser::<u32>::from_read(file, binary::Config::default())?;
     let ordered_aig = aig_reader.parse()?;
     // Parse the AIG
@@ -45,71 +34,60 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     .const_fold(true);
     let (aig_order, _renumber) = Renumber::renumber_aig(config, &aig)?;
 
-    let output_file = File::create(output_file_path)?;
-    let output_file1 = File::create(aig_2_egraph_nodemap_path)?;
-
+    let output_file = File::create(aag_path)?;
     let mut aag_writer = DeferredWriter::from_write(&output_file);
-    let writer = ascii::Writer::<u32>::new(&mut aag_writer);
-    writer.write_ordered_aig(&aig_order);
-    let (input_vec, output_vec) = writer.collect_symbol(&aig_order);
-    let (root_id, input_ids, one_out_sig, node_id_to_egraph_id, outputmap) =
-        process_aig_to_egraph(&aig_order, input_vec, output_vec, output_path);
+    let (input_vec, output_vec) = {
+        let writer = ascii::Writer::<u32>::new(&mut aag_writer);
+        writer.write_ordered_aig(&aig_order);
+        writer.collect_symbol(&aig_order)
+    };
+    aag_writer.flush_defer_err();
+    aag_writer.check_io_error()?;
+    Ok((aig_order, input_vec, output_vec))
+}
 
+fn write_nodemap(path: &Path, lit_to_class: &HashMap<u32, Id>) -> Result<(), Box<dyn std::error::Error>> {
     // 将 HashMap 转换为 Vec，并按键的数字大小排序
-    let mut sorted_node_ids: Vec<(&String, &Id)> = node_id_to_egraph_id.iter().collect();
-    sorted_node_ids.sort_by(|a, b| {
-        a.0.parse::<u32>().unwrap().cmp(&b.0.parse::<u32>().unwrap())
-    });
-
-    // 打印排序后的 outputmap
-    let mut sorted_outputmap: Vec<(&Id, &String)> = outputmap.iter().collect();
-    sorted_outputmap.sort_by(|a, b| a.0.cmp(b.0));
-    for (id, op_name) in &sorted_outputmap {
-        println!("{:?}: {}", id, op_name);
-    }
+    let mut sorted_node_ids: Vec<(&u32, &Id)> = lit_to_class.iter().collect();
+    sorted_node_ids.sort_by_key(|(lit, _)| **lit);
 
-    let mut writer1 = BufWriter::new(output_file1);
+    let mut writer1 = BufWriter::new(File::create(path)?);
     for (key, value) in &sorted_node_ids {
         writeln!(writer1, "{} {}", key, value)?;
     }
+    Ok(())
+}
 
-    // Process JSON
-    let modified_json_file = process_json_prop(&output_path);
-    let converted_json_data =
-        fs::read_to_string(&modified_json_file).expect("Unable to read the JSON file");
-
-    // Read egraph from JSON file
-    let mut input_egraph: egg::EGraph<Prop, ()> =
-        serde_json::from_str(&converted_json_data).unwrap();
-    input_egraph.rebuild(); // eqn2egraph finished
-    println!("input egraph");
-    println!("input node: {}", input_egraph.total_size());
-    println!("input class: {}", input_egraph.number_of_classes());
+// Saturates `input_egraph` from `roots` and writes the egraph files into
+// `rewritten_path`. `annotate` can add fields to the weighted cost json.
+fn rewrite_and_save(
+    input_egraph: egg::EGraph<Prop, ()>,
+    roots: Vec<Id>,
+    runner_iteration_limit: usize,
+    rewritten_path: &Path,
+    annotate: impl FnOnce(&egg::EGraph<Prop, ()>, &mut serde_json::Value),
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root_ids: Vec<usize> = roots.iter().map(|&id| id.into()).collect();
 
     // 使用动态路径替换所有 rewritten_circuit/
     let serialized_input_egraph_json_path =
-        Path::new(rewritten_path).join("rewritten_egraph_with_weight_cost_serd_base.json");
+        rewritten_path.join("rewritten_egraph_with_weight_cost_serd_base.json");
     save_serialized_egraph_to_json(
         &egg_to_serialized_egraph(&input_egraph),
         &serialized_input_egraph_json_path,
-        &[root_id.into()],
+        &root_ids,
     )?;
 
-    let runner_iteration_limit = iteration.parse().unwrap_or_else(|_| {
-        eprintln!("Invalid iteration value: {}", iteration);
-        std::process::exit(1);
-    });
-
     let egraph_node_limit = 200000000;
     let start = Instant::now();
     let mut runner = Runner::default()
         .with_explanations_enabled()
-        .with_egraph(input_egraph.clone())
+        .with_egraph(input_egraph)
         .with_time_limit(std::time::Duration::from_secs(10))
         .with_iter_limit(runner_iteration_limit)
         .with_node_limit(egraph_node_limit);
 
-    runner.roots = vec![root_id];
+    runner.roots = roots;
     let runner_result = runner.run(&make_rules());
 
     let duration = start.elapsed();
@@ -122,10 +100,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         runner_result.egraph.total_size()
     );
 
-    let root = runner_result.roots[0];
-
     // Save output egraph from runner (input for extraction gym)
-    let output_egraph_json_path = Path::new(rewritten_path).join("rewritten_egraph_internal.json");
+    let output_egraph_json_path = rewritten_path.join("rewritten_egraph_internal.json");
     save_egraph_to_json(&runner_result.egraph, &output_egraph_json_path)?;
 
     println!("egraph after runner");
@@ -136,11 +112,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let serialized_output_egraph =
         egg_to_serialized_egraph(&runner_result.egraph);
     let serialized_output_egraph_json_path =
-        Path::new(rewritten_path).join("rewritten_egraph_internal_serd.json");
+        rewritten_path.join("rewritten_egraph_internal_serd.json");
     save_serialized_egraph_to_json(
         &serialized_output_egraph,
         &serialized_output_egraph_json_path,
-        &[root_id.into()],
+        &root_ids,
     )?;
 
     println!("------------------assign cost of enode-----------------");
@@ -162,16 +138,154 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cost_string = process_json_prop_cost(&json_string);
 
     let output_egraph_cost_json_path =
-        Path::new(rewritten_path).join("rewritten_egraph_with_weight_cost_serd.json");
+        rewritten_path.join("rewritten_egraph_with_weight_cost_serd.json");
     let mut json_data: serde_json::Value =
         serde_json::from_str(&cost_string)?;
     json_data["root_eclasses"] = serde_json::Value::Array(
             root_values.iter().map(|id| serde_json::Value::String(id.clone())).collect()
         );
+    annotate(&runner_result.egraph, &mut json_data);
     let file = File::create(&output_egraph_cost_json_path)?;
     let writer = BufWriter::new(file);
     serde_json::to_writer_pretty(writer, &json_data)?;
 
     println!("done");
     Ok(())
-}
\ No newline at end of file
+}
+
+fn parse_iteration(iteration: &str) -> usize {
+    iteration.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid iteration value: {}", iteration);
+        std::process::exit(1);
+    })
+}
+
+// Loads every partition into one egraph, so logic shared between partitions
+// is hash-consed and rewritten once, and saturates it once. The weighted cost
+// json lists the outputs of all partitions in order as its root classes, and
+// each partition's root class and `[class, name]` outputs under "partitions".
+fn run_shared(rewritten_path: &Path, iteration: &str, inputs: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(rewritten_path)?;
+    let runner_iteration_limit = parse_iteration(iteration);
+
+    let mut input_egraph: egg::EGraph<Prop, ()> = egg::EGraph::default();
+    let mut partitions: Vec<(String, AigEgraph)> = Vec::new();
+    for input in inputs {
+        let input = Path::new(input);
+        let name = input.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let (aig_order, input_vec, output_vec) = load_aig(input, &rewritten_path.join(format!("{}.aag", name)))?;
+
+        let nodes_before = input_egraph.total_size();
+        let classes = aig_to_egraph(&mut input_egraph, &aig_order, &input_vec, &output_vec)
+            .map_err(|err| format!("{}: {}", input.display(), err))?;
+        if !classes.latches.latches.is_empty() {
+            return Err(format!("{}: sequential partitions are not supported in shared mode", input.display()).into());
+        }
+        println!(
+            "partition {}: {} and gates, {} new egraph nodes",
+            name,
+            aig_order.and_gates.len(),
+            input_egraph.total_size() - nodes_before
+        );
+        write_nodemap(&rewritten_path.join(format!("{}.nodemap", name)), &classes.lit_to_class)?;
+        partitions.push((name, classes));
+    }
+    input_egraph.rebuild();
+    println!("shared input egraph");
+    println!("input node: {}", input_egraph.total_size());
+    println!("input class: {}", input_egraph.number_of_classes());
+
+    let roots: Vec<Id> = partitions.iter().map(|(_, classes)| input_egraph.find(classes.root)).collect();
+    rewrite_and_save(input_egraph, roots, runner_iteration_limit, rewritten_path, |egraph, json_data| {
+        // egraph2aig numbers outputs in this order, so list every output of
+        // every partition in partition order
+        json_data["root_eclasses"] = serde_json::Value::Array(
+            partitions
+                .iter()
+                .flat_map(|(_, classes)| &classes.output_classes)
+                .map(|&id| serde_json::Value::String(egraph.find(id).to_string()))
+                .collect(),
+        );
+        json_data["partitions"] = serde_json::Value::Array(
+            partitions
+                .iter()
+                .map(|(name, classes)| {
+                    let outputs: Vec<serde_json::Value> = classes
+                        .outputs
+                        .iter()
+                        .map(|(id, op_name)| serde_json::json!([egraph.find(*id).to_string(), op_name]))
+                        .collect();
+                    serde_json::json!({
+                        "name": name,
+                        "root": egraph.find(classes.root).to_string(),
+                        "outputs": outputs,
+                    })
+                })
+                .collect(),
+        );
+    })
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--shared") {
+        if args.len() < 5 {
+            println!("Usage: <program> --shared <rewritten_path> <iteration> <input_aig>...");
+            std::process::exit(1);
+        }
+        return run_shared(Path::new(&args[2]), &args[3], &args[4..]);
+    }
+    if args.len() < 7 {
+        println!("Usage: <program> <path_to_input_aig_file> <path_to_output_egraph_json> <output_file_path> <aig_2_egraph_nodemap_path> <iteration> <rewritten_path>");
+        println!("       <program> --shared <rewritten_path> <iteration> <input_aig>...");
+        std::process::exit(1);
+    }
+
+    let file_path = &args[1];
+    let output_path = &args[2];
+    let output_file_path = &args[3];
+    let aig_2_egraph_nodemap_path = &args[4];
+    let iteration = &args[5];
+    let rewritten_path = &args[6]; // 新增的路径参数
+
+    let (aig_order, input_vec, output_vec) = load_aig(Path::new(file_path), Path::new(output_file_path))?;
+
+    // Build the Prop egraph straight from the AIG
+    let mut input_egraph: egg::EGraph<Prop, ()> = egg::EGraph::default();
+    let classes = aig_to_egraph(&mut input_egraph, &aig_order, &input_vec, &output_vec)
+        .map_err(|err| format!("{}: {}", file_path, err))?;
+    input_egraph.rebuild(); // eqn2egraph finished
+
+    // 按输出顺序打印 outputmap
+    for (id, op_name) in &classes.outputs {
+        println!("{:?}: {}", id, op_name);
+    }
+
+    write_nodemap(Path::new(aig_2_egraph_nodemap_path), &classes.lit_to_class)?;
+    save_egraph_to_json(&input_egraph, &PathBuf::from(output_path))?;
+
+    println!("input egraph");
+    println!("input node: {}", input_egraph.total_size());
+    println!("input class: {}", input_egraph.number_of_classes());
+
+    let runner_iteration_limit = parse_iteration(iteration);
+    let root = input_egraph.find(classes.root);
+    if classes.latches.latches.is_empty() {
+        return rewrite_and_save(input_egraph, vec![root], runner_iteration_limit, Path::new(rewritten_path), |_, _| {});
+    }
+
+    // Sequential: egraph2aig needs every output and next-state function as a
+    // root, in order, plus the latch map to turn them back into latches
+    let latch_map_path = Path::new(rewritten_path).join("latches.json");
+    serde_json::to_writer_pretty(BufWriter::new(File::create(&latch_map_path)?), &classes.latches)?;
+    println!("{} latches, latch map written to {}", classes.latches.latches.len(), latch_map_path.display());
+    let output_classes = classes.output_classes;
+    rewrite_and_save(input_egraph, vec![root], runner_iteration_limit, Path::new(rewritten_path), |egraph, json_data| {
+        json_data["root_eclasses"] = serde_json::Value::Array(
+            output_classes
+                .iter()
+                .map(|&id| serde_json::Value::String(egraph.find(id).to_string()))
+                .collect(),
+        );
+    })
+}
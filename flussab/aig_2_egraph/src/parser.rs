@@ -1,6 +1,6 @@
 use ::serde::{Deserialize, Serialize};
 use egg::*;
-use serde::__private::fmt::Display;
+use std::fmt::Display;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
@@ -16,7 +16,8 @@ use std::collections::{HashSet, VecDeque};
 use egraph_serialize::*;
 use rustc_hash::FxHashMap;
 use crate::language::*;
-use flussab_aiger::aig::OrderedAig;
+use flussab_aiger::aig::{OrderedAig, SymbolTarget};
+use flussab_aiger::traversal::{LatchInfo, LatchMap};
 use flussab_aiger::Lit;
 use flussab_aiger::{
     aig::{Renumber, RenumberConfig},
@@ -359,142 +360,274 @@ pub fn dfs_traversal(graph: &Graph, start_node: &str) -> Vec<String> {
     }
 
 
-    pub fn process_aig_to_egraph(
-        aig: &OrderedAig<u32>,
-        input_vec: Vec<String>, // 假设 input_vec 提供了与 input_count 对应的名称
-        output_vec: Vec<String>, // 假设 output_vec 提供了与 outputs 对应的名称
-        output_file: &str,
-    ) -> (egg::Id, Vec<Id>, i32, HashMap<String, Id>, HashMap<Id, String>) {
-        let mut egraph: egg::EGraph<SymbolLang, ()> = egg::EGraph::default();
-        let mut node_id_to_egraph_id: HashMap<String, Id> = HashMap::new();
-        let mut input_ids: Vec<Id> = Vec::new();
-        let mut one_out_sig = 0;
-        let mut count_out = 0;
-        let mut concat_ids: Vec<Id> = Vec::new(); // 用于存储所有新创建的 `Id`
-        let mut output_map: HashMap<Id, String> = HashMap::new(); // 用于记录 `concat_ids` 和对应的 `op_name`
-    
-        // **第一步**: 添加常量 "0" 和 "1" 到 EGraph
-        let id0 = egraph.add(SymbolLang::leaf("n0"));
-        // let id1 = egraph.add(SymbolLang::leaf("n1"));
-        let id1= egraph.add(SymbolLang::new("Not", vec![id0]));
-        node_id_to_egraph_id.insert("0".to_string(), id0);
-        node_id_to_egraph_id.insert("1".to_string(), id1);
-    
-        // **第二步**: 插入输入信号
-        for n in 0..aig.input_count {
-            let node_id = ((n + 1) * 2).to_string();
-    
-            if let Some(op_name) = input_vec.get(n) {
-                let id = egraph.add(SymbolLang::leaf(op_name));
-                input_ids.push(id);
-                node_id_to_egraph_id.insert(node_id.clone(), id);
+/// The classes of an AIG added to a `Prop` egraph by [`aig_to_egraph`].
+pub struct AigEgraph {
+    /// Concat of all outputs, the class to saturate from.
+    pub root: Id,
+    /// Input classes in AIG order.
+    pub inputs: Vec<Id>,
+    /// Class of every literal that was used, negated literals included.
+    pub lit_to_class: HashMap<u32, Id>,
+    /// Class and name of every output, parallel to `output_classes`. Outputs
+    /// driven by the same class keep their own names.
+    pub outputs: Vec<(Id, String)>,
+    /// Output classes in AIG order, followed by the next-state class of every latch.
+    pub output_classes: Vec<Id>,
+    /// Latches cut into pseudo-primary inputs and outputs, for egraph2aig to restore.
+    pub latches: LatchMap,
+}
+
+fn literal_class<N: Analysis<Prop>>(
+    egraph: &mut egg::EGraph<Prop, N>,
+    lit_to_class: &mut HashMap<u32, Id>,
+    lit: u32,
+) -> anyhow::Result<Id> {
+    if let Some(&id) = lit_to_class.get(&lit) {
+        return Ok(id);
+    }
+    let positive = lit & !1;
+    let Some(&id) = lit_to_class.get(&positive) else {
+        anyhow::bail!("literal {} is used before it is defined", lit);
+    };
+    let not_id = egraph.add(Prop::Not(id));
+    lit_to_class.insert(lit, not_id);
+    Ok(not_id)
+}
+
+/// Adds an AIG to a `Prop` egraph, the caller rebuilds it.
+///
+/// Constant false is the symbol `n0` and true is `(! n0)`. Inputs are symbols
+/// named after `input_names` (`pi<n>` when unnamed), so AIGs sharing input
+/// names hash-cons their common logic. The first output is marked with `@`,
+/// and all outputs are joined into the root by left-nested `Concat` nodes.
+///
+/// Latches are cut: each latch output becomes a symbol named after the latch
+/// (`lo<n>` when unnamed) and each next-state function an extra output named
+/// `li<n>` after the real ones. The returned [`LatchMap`] records how to put
+/// them back together.
+pub fn aig_to_egraph<N: Analysis<Prop>>(
+    egraph: &mut egg::EGraph<Prop, N>,
+    aig: &OrderedAig<u32>,
+    input_names: &[String],
+    output_names: &[String],
+) -> anyhow::Result<AigEgraph> {
+    let mut lit_to_class: HashMap<u32, Id> = HashMap::new();
+    let mut outputs: Vec<(Id, String)> = Vec::with_capacity(aig.outputs.len() + aig.latches.len());
+
+    let id0 = egraph.add(Prop::Symbol("n0".into()));
+    let id1 = egraph.add(Prop::Not(id0));
+    lit_to_class.insert(0, id0);
+    lit_to_class.insert(1, id1);
+
+    let mut inputs = Vec::with_capacity(aig.input_count);
+    for n in 0..aig.input_count {
+        let name = input_names.get(n).cloned().unwrap_or_else(|| format!("pi{}", n));
+        let id = egraph.add(Prop::Symbol(name.into()));
+        inputs.push(id);
+        lit_to_class.insert(((n + 1) * 2) as u32, id);
+    }
+
+    let mut latch_names: Vec<Option<String>> = vec![None; aig.latches.len()];
+    for symbol in &aig.symbols {
+        if let SymbolTarget::Latch(index) = symbol.target {
+            if let Some(name) = latch_names.get_mut(index) {
+                *name = Some(symbol.name.to_string());
             }
         }
-    
-        // **第三步**: 遍历 `and_gates` 并处理输入和输出
-        let mut code = aig.input_count as u32 * 2 + 2;
-    
-        for (index, and_gate) in aig.and_gates.iter().enumerate() {
-            let input_code_1 = and_gate.inputs[0].to_string();
-            let input_code_2 = and_gate.inputs[1].to_string();
-            let output_code = code.to_string();
-    
-            let mut child_ids = vec![];
-    
-            if let Some(id) = node_id_to_egraph_id.get(&input_code_1) {
-                child_ids.push(*id);
-            } else {
-                let negated_code = (and_gate.inputs[0] - 1).to_string();
-                if let Some(negated_id) = node_id_to_egraph_id.get(&negated_code) {
-                    let id = egraph.add(SymbolLang::new("Not", vec![*negated_id]));
-                    node_id_to_egraph_id.insert(input_code_1.clone(), id);
-                    child_ids.push(id);
-                }
-            }
-    
-            if let Some(id) = node_id_to_egraph_id.get(&input_code_2) {
-                child_ids.push(*id);
-            } else {
-                let negated_code = (and_gate.inputs[1] - 1).to_string();
-                if let Some(negated_id) = node_id_to_egraph_id.get(&negated_code) {
-                    let id = egraph.add(SymbolLang::new("Not", vec![*negated_id]));
-                    node_id_to_egraph_id.insert(input_code_2.clone(), id);
-                    child_ids.push(id);
-                }
-            }
-    
-            if child_ids.len() == 2 {
-                let id = egraph.add(SymbolLang::new("And", vec![child_ids[0], child_ids[1]]));
-                node_id_to_egraph_id.insert(output_code.clone(), id);
-            }
-    
-            code += 2;
+    }
+    let mut latches = LatchMap {
+        outputs: aig.outputs.len(),
+        latches: Vec::with_capacity(aig.latches.len()),
+    };
+    for (n, (latch, name)) in aig.latches.iter().zip(latch_names).enumerate() {
+        let name = name.unwrap_or_else(|| format!("lo{}", n));
+        let id = egraph.add(Prop::Symbol(name.as_str().into()));
+        lit_to_class.insert(((aig.input_count + n + 1) * 2) as u32, id);
+        latches.latches.push(LatchInfo {
+            name,
+            initialization: latch.initialization,
+        });
+    }
+
+    let mut code = (aig.input_count + aig.latches.len()) as u32 * 2 + 2;
+    for and_gate in &aig.and_gates {
+        let a = literal_class(egraph, &mut lit_to_class, and_gate.inputs[0])?;
+        let b = literal_class(egraph, &mut lit_to_class, and_gate.inputs[1])?;
+        let id = egraph.add(Prop::And([a, b]));
+        lit_to_class.insert(code, id);
+        code += 2;
+    }
+
+    let mut output_ids = Vec::with_capacity(aig.outputs.len());
+    for (index, &output) in aig.outputs.iter().enumerate() {
+        let id = literal_class(egraph, &mut lit_to_class, output)?;
+        let name = output_names.get(index).cloned().unwrap_or_else(|| format!("po{}", index));
+        outputs.push((id, name));
+        output_ids.push(id);
+    }
+    for (index, latch) in aig.latches.iter().enumerate() {
+        let id = literal_class(egraph, &mut lit_to_class, latch.next_state)?;
+        outputs.push((id, format!("li{}", index)));
+        output_ids.push(id);
+    }
+
+    let Some((&first, rest)) = output_ids.split_first() else {
+        anyhow::bail!("the AIG has no outputs");
+    };
+    egraph.add(Prop::Root(first));
+    let mut root = first;
+    for &id in rest {
+        root = egraph.add(Prop::Concat([root, id]));
+    }
+
+    Ok(AigEgraph {
+        root,
+        inputs,
+        lit_to_class,
+        outputs,
+        output_classes: output_ids,
+        latches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flussab_aiger::aig::{OrderedAndGate, OrderedLatch, Symbol};
+
+    // Value of `id` with the symbols in `env` and `n0` false
+    fn eval(egraph: &egg::EGraph<Prop, ()>, id: Id, env: &HashMap<&str, bool>) -> bool {
+        let (_, expr) = Extractor::new(egraph, AstSize).find_best(id);
+        let mut values: Vec<bool> = vec![];
+        for node in expr.as_ref() {
+            let value = match node {
+                Prop::Symbol(name) if name.as_str() == "n0" => false,
+                Prop::Symbol(name) => env[name.as_str()],
+                Prop::Not(a) => !values[usize::from(*a)],
+                Prop::And([a, b]) => values[usize::from(*a)] && values[usize::from(*b)],
+                other => panic!("unexpected node {}", other),
+            };
+            values.push(value);
         }
-    
-        // **第四步**: 遍历 `outputs` 并根据 `output_vec` 中的名称处理
-        for (index, &output) in aig.outputs.iter().enumerate() {
-            let output_code = output.to_string();
-        
-            if let Some(op_name) = output_vec.get(index) {
-                println!("op_name: {}", op_name);
-        
-                if let Some(&id) = node_id_to_egraph_id.get(&output_code) {
-                    concat_ids.push(id); // 直接使用现有的 `id`
-                    output_map.insert(id, op_name.clone()); // 记录对应的 `op_name`
-                } else {
-                    let negated_code = (output - 1).to_string();
-                    if let Some(&negated_id) = node_id_to_egraph_id.get(&negated_code) {
-                        let not_id = egraph.add(SymbolLang::new("Not", vec![negated_id]));
-                        concat_ids.push(not_id); // 使用 `not_id` 而不是创建新的节点
-                        output_map.insert(not_id, op_name.clone()); // 记录对应的 `op_name`
-                        node_id_to_egraph_id.insert(output_code.clone(), not_id);
-                    } else {
-                        println!("Warning: Node ID {} or its negation not found in node_id_to_egraph_id", output_code);
-                    }
-                }
-            } 
+        *values.last().unwrap()
+    }
+
+    // Values of the outputs followed by the next-state functions
+    fn simulate(aig: &OrderedAig<u32>, assignment: usize) -> Vec<bool> {
+        let first_gate = aig.input_count + aig.latches.len() + 1;
+        let mut values: Vec<bool> = (0..first_gate).map(|var| var > 0 && (assignment >> (var - 1)) & 1 == 1).collect();
+        let lit = |values: &[bool], lit: u32| values[lit as usize >> 1] ^ (lit & 1 == 1);
+        for gate in &aig.and_gates {
+            values.push(lit(&values, gate.inputs[0]) && lit(&values, gate.inputs[1]));
         }
-    
-        // **第五步**: 使用 `Concat` 拼接所有 `concat_ids`
-
-        let mut root_id = Id::from(0); // 初始化 root_id
-        if !concat_ids.is_empty() {
-            root_id = concat_ids[0]; // 第一个拼接节点
-            println!("root{}", root_id); // 打印 root_id
-            let root_new = egraph.add(SymbolLang::new("Root", vec![root_id]));
-            for i in 1..concat_ids.len() {
-                root_id = egraph.add(SymbolLang::new("Concat", vec![root_id, concat_ids[i]]));
-            }
+        let sinks = aig.outputs.iter().chain(aig.latches.iter().map(|latch| &latch.next_state));
+        sinks.map(|&sink| lit(&values, sink)).collect()
+    }
+
+    fn assert_round_trip(egraph: &egg::EGraph<Prop, ()>, aig: &OrderedAig<u32>, classes: &AigEgraph, names: &[&str]) {
+        assert_eq!(classes.output_classes.len(), aig.outputs.len() + aig.latches.len());
+        for assignment in 0..1 << names.len() {
+            let env: HashMap<&str, bool> = names
+                .iter()
+                .enumerate()
+                .map(|(index, &name)| (name, (assignment >> index) & 1 == 1))
+                .collect();
+            let values: Vec<bool> = classes.output_classes.iter().map(|&id| eval(egraph, id, &env)).collect();
+            assert_eq!(values, simulate(aig, assignment), "assignment {:b}", assignment);
         }
-        // let root_id = if let Some(root_eclass) = graph.root_eclasses.get(0) {
-        //     println!("Root eclass: {}", root_eclass);
-            
-        //     if let Some(egraph_id) = node_id_to_egraph_id.get(root_eclass) {
-        //         let root_new = egraph.add(SymbolLang::new("Root", vec![*egraph_id]));
-                
-        //         // println!(
-        //         //     "Root eclass found: Node ID: {}, EGraph ID: {:?}",
-        //         //     root_eclass, egraph_id
-        //         // );
-        //         count_out += 1;
-        //         one_out_sig = 1;
-        //         root_new
-        //     } else {
-        //         println!("Error: Root eclass node_id not found in map");
-        //         Id::from(0) // Default or invalid ID
-        //     }
-        // } else {
-        //     println!("Error: Root eclass not found");
-        //     Id::from(0) // Default or invalid ID
-        // };
-        // Rebuild the egraph
+    }
+
+    #[test]
+    fn combinational_outputs_keep_their_names() {
+        // g4 = a & b, g5 = !g4 & c, outputs g5, g4, g5 again and !g4
+        let aig = OrderedAig {
+            max_var_index: 5,
+            input_count: 3,
+            and_gates: vec![OrderedAndGate { inputs: [4, 2] }, OrderedAndGate { inputs: [9, 6] }],
+            outputs: vec![10, 8, 10, 9],
+            ..OrderedAig::default()
+        };
+        let names = ["a".to_string(), "b".to_string(), "c".to_string()];
+        let outputs = ["f".to_string(), "g".to_string(), "h".to_string()];
+        let mut egraph = egg::EGraph::<Prop, ()>::default();
+        let classes = aig_to_egraph(&mut egraph, &aig, &names, &outputs).unwrap();
         egraph.rebuild();
-    
-        // **序列化 EGraph 并写入到文件**
-        let json_str = serde_json::to_string_pretty(&egraph).expect("Failed to serialize EGraph");
-        fs::write(output_file, json_str).expect("Failed to write output JSON file");
-    
-        // 返回值
-        one_out_sig = 1; // 假设至少有一个输出信号
-        (root_id, input_ids, one_out_sig, node_id_to_egraph_id, output_map)
-    }
\ No newline at end of file
+
+        let named: Vec<&str> = classes.outputs.iter().map(|(_, name)| name.as_str()).collect();
+        assert_eq!(named, ["f", "g", "h", "po3"]);
+        let ids: Vec<Id> = classes.outputs.iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, classes.output_classes);
+        assert_eq!(ids[0], ids[2]);
+        assert!(classes.latches.latches.is_empty());
+        assert!(matches!(egraph[classes.root].nodes[0], Prop::Concat(_)));
+        assert!(egraph.lookup(Prop::Root(ids[0])).is_some());
+        assert_round_trip(&egraph, &aig, &classes, &["a", "b", "c"]);
+    }
+
+    #[test]
+    fn sequential_next_states_keep_their_names() {
+        // g4 = a & l0 drives the output and the next state of both latches
+        let aig = OrderedAig {
+            max_var_index: 4,
+            input_count: 1,
+            latches: vec![
+                OrderedLatch {
+                    next_state: 8,
+                    initialization: Some(false),
+                },
+                OrderedLatch {
+                    next_state: 8,
+                    initialization: None,
+                },
+            ],
+            and_gates: vec![OrderedAndGate { inputs: [4, 2] }],
+            outputs: vec![8],
+            symbols: vec![Symbol {
+                target: SymbolTarget::Latch(0),
+                name: "state".into(),
+            }],
+            ..OrderedAig::default()
+        };
+        let mut egraph = egg::EGraph::<Prop, ()>::default();
+        let classes = aig_to_egraph(&mut egraph, &aig, &[], &[]).unwrap();
+        egraph.rebuild();
+
+        let named: Vec<&str> = classes.outputs.iter().map(|(_, name)| name.as_str()).collect();
+        assert_eq!(named, ["po0", "li0", "li1"]);
+        assert_eq!(classes.latches.outputs, 1);
+        let latch_names: Vec<&str> = classes.latches.latches.iter().map(|latch| latch.name.as_str()).collect();
+        assert_eq!(latch_names, ["state", "lo1"]);
+        assert_eq!(classes.latches.latches[0].initialization, Some(false));
+        assert_eq!(classes.latches.latches[1].initialization, None);
+        assert_round_trip(&egraph, &aig, &classes, &["pi0", "state", "lo1"]);
+    }
+
+    #[test]
+    fn shared_inputs_hash_cons_common_logic() {
+        let names = ["a".to_string(), "b".to_string()];
+        // a & b, and !(a & b) over the same inputs
+        let and = OrderedAig {
+            max_var_index: 3,
+            input_count: 2,
+            and_gates: vec![OrderedAndGate { inputs: [4, 2] }],
+            outputs: vec![6],
+            ..OrderedAig::default()
+        };
+        let nand = OrderedAig {
+            outputs: vec![7],
+            ..and.clone()
+        };
+        let mut egraph = egg::EGraph::<Prop, ()>::default();
+        let first = aig_to_egraph(&mut egraph, &and, &names, &[]).unwrap();
+        let nodes = egraph.total_size();
+        let second = aig_to_egraph(&mut egraph, &nand, &names, &[]).unwrap();
+        egraph.rebuild();
+
+        assert_eq!(first.inputs, second.inputs);
+        assert_eq!(first.lit_to_class[&6], second.lit_to_class[&6]);
+        // Only the negation and its `@` marker are new
+        assert_eq!(egraph.total_size(), nodes + 2);
+        assert_round_trip(&egraph, &and, &first, &["a", "b"]);
+        assert_round_trip(&egraph, &nand, &second, &["a", "b"]);
+    }
+}
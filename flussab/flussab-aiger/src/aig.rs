@@ -4,7 +4,10 @@ use thiserror::Error;
 use zwohash::HashMap;
 use crate::traversal::Graph;
 use crate::Lit;
-use std::collections::HashSet;
+
+pub mod cone;
+pub mod to_cnf;
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct Latch<L> {
     pub state: L,
@@ -58,113 +61,15 @@ pub struct Aig<L> {
     pub symbols: Vec<Symbol<'static>>,
     pub comment: Option<String>,
 }
-impl<L: Lit + std::cmp::Ord>  Aig<L> {
+impl<L: Lit> Aig<L> {
     /// 提取 cone，从指定的 `and_gate` 开始，追溯到所有的相关节点
+    ///
+    /// 多个输出或在边界处截断时使用 [`ConeExtractor`](cone::ConeExtractor)
     pub fn extract_cone(&self, gate_output: L) -> Self {
-        // 存储新的 and_gates 和 inputs
-        let mut new_and_gates = Vec::new();
-        let mut new_inputs = HashSet::new();
-
-        // 用于追踪访问过的节点
-        let mut visited = HashSet::new();
-        // 用于递归追踪的队列，起点是给定的 and_gate 的输出
-        let mut queue = vec![gate_output];
-
-        // 开始追踪逻辑锥
-        while let Some(current_output) = queue.pop() {
-            // 防止重复追踪
-            if !visited.insert(current_output) {
-                continue;
-            }
-
-            // 在 `and_gates` 中查找输出是 `current_output` 的 gate
-            if let Some((_, and_gate)) = self.and_gates.iter().enumerate().find(|(_, gate)| gate.output == current_output) {
-                // 记录当前 and_gate
-                new_and_gates.push(and_gate.clone());
-
-                // 遍历 and_gate 的 inputs
-                for &input in &and_gate.inputs {
-                    // 将 `L` 转换为 `u32` 以处理奇偶性
-                    let input_code = input.code();
-
-                    let normalized_input_code = if input_code % 2 == 1 {
-                        input_code - 1
-                    } else {
-                        input_code
-                    };
-
-                    // 将 `u32` 转回 `L`
-                    let normalized_input = L::from_code(normalized_input_code);
-
-                    if self.inputs.contains(&normalized_input) {
-                        // 如果 normalized input 是原始 AIG 的 primary input，记录为新 inputs
-                        new_inputs.insert(normalized_input);
-                    } else {
-                        // 如果 normalized input 是另一个 and_gate 的输出，继续追踪
-                        queue.push(normalized_input);
-                    }
-                }
-            }
-        }
-
-        // 构建新的 inputs 和 symbols 的映射
-        let mut new_inputs_vec: Vec<L> = new_inputs.into_iter().collect();
-        new_inputs_vec.sort(); // 确保顺序一致
-
-        // 构建新的 symbols
-        let old_input_to_symbol: HashMap<_, _> = self
-            .symbols
-            .iter()
-            .filter(|symbol| matches!(symbol.target, SymbolTarget::Input(_)))
-            .map(|symbol| {
-                if let SymbolTarget::Input(index) = symbol.target {
-                    (self.inputs[index], symbol.clone())
-                } else {
-                    unreachable!()
-                }
-            })
-            .collect();
-
-        let mut new_symbols: Vec<_> = new_inputs_vec
-            .iter()
-            .enumerate()
-            .map(|(index, &input)| {
-                let old_symbol = old_input_to_symbol.get(&input).expect("Input not found in symbols");
-                Symbol {
-                    target: SymbolTarget::Input(index),
-                    name: old_symbol.name.clone(),
-                }
-            })
-            .collect();
-
-            let symbol = Symbol {
-                target: SymbolTarget::Output(0),
-                name: Cow::Owned(format!("po{}", 0)),
-            };
-            new_symbols.push(symbol);
-        
-
-        // 构建新的 AIG
-        Aig {
-            max_var_index: self.max_var_index,
-            inputs: new_inputs_vec,
-            latches: vec![], // 假设逻辑锥中没有 latches
-            outputs: vec![gate_output], // 输出是指定节点的 output
-            bad_state_properties: vec![],
-            invariant_constraints: vec![],
-            justice_properties: vec![],
-            fairness_constraints: vec![],
-            and_gates: new_and_gates,
-            symbols: new_symbols,
-            comment: None,
-        }
+        cone::ConeExtractor::new(self).extract(&[gate_output], &[]).aig
     }
 }
 
-
-
-   
-
 impl<L: Lit> Aig<L> {
     pub fn from_graph(graph: &Graph) -> Self {
         let mut aig = Aig::<L>::default();
@@ -181,19 +86,17 @@ impl<L: Lit> Aig<L> {
         }
     
         // Step 2: 解析 root 节点
-        let max_order = graph
-            .nodes
-            .values()
-            .filter_map(|node| node.order)
-            .max()
-            .unwrap_or(0);
-    
+        // Each root is its own node, or the complement of the node below its `!`
+        let root_order = |id: &String| -> Option<usize> { graph.nodes.get(id)?.order };
         for (i, root_id) in graph.root_eclasses.iter().enumerate() {
             if let Some(root_node) = graph.nodes.get(root_id) {
                 let root_lit = if root_node.op == "!" {
-                    L::from_code(max_order * 2 + 1)
+                    let order = root_order(&root_node.children[0])
+                        .unwrap_or_else(|| panic!("Root `{}` negates a node without order", root_id));
+                    L::from_code(order * 2 + 1)
                 } else {
-                    L::from_code(max_order * 2)
+                    let order = root_node.order.unwrap_or_else(|| panic!("Root `{}` has no order", root_id));
+                    L::from_code(order * 2)
                 };
                 aig.outputs.push(root_lit);
     
@@ -0,0 +1,220 @@
+//! Indexed extraction of the logic cone of several literals.
+//!
+//! [`ConeExtractor`] indexes the AND gates of an [`Aig`] by output variable once,
+//! so each extraction is linear in the size of the cone. Extraction stops at
+//! inputs, latch outputs and an optional boundary, which all become inputs of the
+//! extracted AIG.
+use std::collections::HashMap;
+
+use super::{Aig, AndGate, Symbol, SymbolTarget};
+use crate::Lit;
+
+pub struct ConeExtractor<'a, L> {
+    aig: &'a Aig<L>,
+    // Index into `and_gates` of the gate driving each variable
+    gate_of: Vec<Option<usize>>,
+}
+
+/// An extracted cone, numbered densely: inputs first, then AND gates in
+/// topological order.
+#[derive(Clone, Debug)]
+pub struct Cone<L> {
+    pub aig: Aig<L>,
+    /// Positive literal in the original AIG of every variable of `aig`, the
+    /// constant first.
+    pub origin: Vec<L>,
+    map: HashMap<usize, usize>,
+}
+
+impl<L: Lit> Cone<L> {
+    /// Literal of the cone corresponding to a literal of the original AIG, if the
+    /// cone contains its variable.
+    pub fn lit(&self, original: L) -> Option<L> {
+        let var = self.map.get(&(original.code() >> 1))?;
+        Some(L::from_code(var * 2 + (original.code() & 1)))
+    }
+}
+
+impl<'a, L: Lit> ConeExtractor<'a, L> {
+    pub fn new(aig: &'a Aig<L>) -> Self {
+        let vars = aig
+            .and_gates
+            .iter()
+            .map(|gate| gate.output.code() >> 1)
+            .max()
+            .map_or(0, |var| var + 1)
+            .max(aig.max_var_index + 1);
+        let mut gate_of = vec![None; vars];
+        for (index, gate) in aig.and_gates.iter().enumerate() {
+            gate_of[gate.output.code() >> 1] = Some(index);
+        }
+        ConeExtractor { aig, gate_of }
+    }
+
+    /// Extracts the cone of `roots`, stopping at the variables of `boundary`.
+    ///
+    /// Original inputs keep their symbols, other leaves are named `n<code>` after
+    /// their literal and outputs are named `po<index>`.
+    pub fn extract(&self, roots: &[L], boundary: &[L]) -> Cone<L> {
+        let mut is_leaf = vec![false; self.gate_of.len()];
+        for lit in boundary {
+            is_leaf[lit.code() >> 1] = true;
+        }
+
+        // Post-order without recursion, AIGs can be deep
+        let mut visited = vec![false; self.gate_of.len()];
+        visited[0] = true;
+        let mut leaves = vec![];
+        let mut gates = vec![];
+        for root in roots {
+            let mut stack = vec![(root.code() >> 1, false)];
+            while let Some((var, expanded)) = stack.pop() {
+                if expanded {
+                    gates.push(var);
+                    continue;
+                }
+                if visited[var] {
+                    continue;
+                }
+                visited[var] = true;
+                match self.gate_of[var] {
+                    Some(index) if !is_leaf[var] => {
+                        stack.push((var, true));
+                        for lit in self.aig.and_gates[index].inputs {
+                            stack.push((lit.code() >> 1, false));
+                        }
+                    }
+                    _ => leaves.push(var),
+                }
+            }
+        }
+        leaves.sort_unstable();
+
+        let mut map = HashMap::with_capacity(1 + leaves.len() + gates.len());
+        map.insert(0, 0);
+        let mut origin = vec![L::from_code(0)];
+        for &var in leaves.iter().chain(&gates) {
+            map.insert(var, origin.len());
+            origin.push(L::from_code(var * 2));
+        }
+        let lit =
+            |original: L| L::from_code(map[&(original.code() >> 1)] * 2 + (original.code() & 1));
+
+        let input_names: HashMap<usize, &Symbol> = self
+            .aig
+            .symbols
+            .iter()
+            .filter_map(|symbol| match symbol.target {
+                SymbolTarget::Input(index) => Some((self.aig.inputs[index].code() >> 1, symbol)),
+                _ => None,
+            })
+            .collect();
+        let mut symbols: Vec<Symbol<'static>> = leaves
+            .iter()
+            .enumerate()
+            .map(|(index, var)| Symbol {
+                target: SymbolTarget::Input(index),
+                name: match input_names.get(var) {
+                    Some(symbol) if !is_leaf[*var] => symbol.name.clone(),
+                    _ => format!("n{}", var * 2).into(),
+                },
+            })
+            .collect();
+        symbols.extend((0..roots.len()).map(|index| Symbol {
+            target: SymbolTarget::Output(index),
+            name: format!("po{}", index).into(),
+        }));
+
+        let and_gates = gates
+            .iter()
+            .map(|&var| {
+                let gate = &self.aig.and_gates[self.gate_of[var].unwrap()];
+                AndGate {
+                    inputs: gate.inputs.map(lit),
+                    output: lit(gate.output),
+                }
+            })
+            .collect();
+        let aig = Aig {
+            max_var_index: origin.len() - 1,
+            inputs: (1..=leaves.len())
+                .map(|var| L::from_code(var * 2))
+                .collect(),
+            outputs: roots.iter().map(|&root| lit(root)).collect(),
+            and_gates,
+            symbols,
+            ..Aig::default()
+        };
+        Cone { aig, origin, map }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // g8 = a & b, g10 = g8 & !c, g12 = !g8 & c
+    fn aig() -> Aig<u32> {
+        Aig {
+            max_var_index: 6,
+            inputs: vec![2, 4, 6],
+            and_gates: vec![
+                AndGate {
+                    inputs: [2, 4],
+                    output: 8,
+                },
+                AndGate {
+                    inputs: [8, 7],
+                    output: 10,
+                },
+                AndGate {
+                    inputs: [9, 6],
+                    output: 12,
+                },
+            ],
+            outputs: vec![10, 12],
+            symbols: ["a", "b", "c"]
+                .iter()
+                .enumerate()
+                .map(|(i, name)| Symbol {
+                    target: SymbolTarget::Input(i),
+                    name: name.to_string().into(),
+                })
+                .collect(),
+            ..Aig::default()
+        }
+    }
+
+    #[test]
+    fn shared_logic_is_extracted_once() {
+        let aig = aig();
+        let cone = ConeExtractor::new(&aig).extract(&[10, 13], &[]);
+        assert_eq!(cone.aig.inputs, vec![2, 4, 6]);
+        assert_eq!(cone.aig.and_gates.len(), 3);
+        assert_eq!(
+            cone.aig.outputs,
+            vec![cone.lit(10).unwrap(), cone.lit(13).unwrap()]
+        );
+        assert_eq!(cone.lit(9), Some(9));
+        assert_eq!(cone.origin[4], 8);
+        assert_eq!(cone.aig.symbols[2].name, "c");
+    }
+
+    #[test]
+    fn boundary_becomes_an_input() {
+        let aig = aig();
+        let cone = ConeExtractor::new(&aig).extract(&[10], &[8]);
+        // Leaves g8 and c, a and b are cut off
+        assert_eq!(cone.origin, vec![0, 6, 8, 10]);
+        assert_eq!(
+            cone.aig.and_gates,
+            vec![AndGate {
+                inputs: [4, 3],
+                output: 6
+            }]
+        );
+        assert_eq!(cone.aig.symbols[0].name, "c");
+        assert_eq!(cone.aig.symbols[1].name, "n8");
+        assert_eq!(cone.lit(2), None);
+    }
+}
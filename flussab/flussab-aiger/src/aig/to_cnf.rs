@@ -0,0 +1,182 @@
+//! Tseitin encoding of an [`OrderedAig`] as DIMACS CNF.
+//!
+//! Variable `v` of the AIG becomes DIMACS variable `v + 1`, so the constant is
+//! variable 1, forced false by a unit clause. Every AND gate gets the three usual
+//! clauses. Latch outputs stay unconstrained, like inputs, and next-state
+//! functions are encoded but not tied to anything.
+use flussab::DeferredWriter;
+use flussab_cnf::cnf;
+
+use super::OrderedAig;
+use crate::Lit;
+
+/// Which constraint the outputs add to the encoding.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AssertOutputs {
+    /// Outputs are only encoded.
+    #[default]
+    None,
+    /// Every output must be true.
+    All,
+    /// Some output must be true, as for a miter.
+    Any,
+}
+
+#[derive(Default)]
+#[non_exhaustive]
+pub struct CnfConfig {
+    pub assert_outputs: AssertOutputs,
+}
+
+impl CnfConfig {
+    pub fn assert_outputs(mut self, value: AssertOutputs) -> Self {
+        self.assert_outputs = value;
+        self
+    }
+}
+
+/// Maps AIG literals to DIMACS literals and solver assignments back to the AIG.
+#[derive(Clone, Copy, Debug)]
+pub struct VarMap {
+    input_count: usize,
+    latch_count: usize,
+}
+
+impl VarMap {
+    pub fn lit<L: Lit>(&self, lit: L) -> i32 {
+        let var = (lit.code() >> 1) as i32 + 1;
+        if lit.code() & 1 == 1 {
+            -var
+        } else {
+            var
+        }
+    }
+
+    /// DIMACS variable of input `index`.
+    pub fn input_var(&self, index: usize) -> i32 {
+        index as i32 + 2
+    }
+
+    /// DIMACS variable of the output of latch `index`.
+    pub fn latch_var(&self, index: usize) -> i32 {
+        (self.input_count + index) as i32 + 2
+    }
+
+    // Values of `count` variables starting at `first`, `None` when unassigned
+    fn values(first: i32, count: usize, assignment: &[i32]) -> Vec<Option<bool>> {
+        let mut values = vec![None; count];
+        for &lit in assignment {
+            let index = lit.abs() - first;
+            if (0..count as i32).contains(&index) {
+                values[index as usize] = Some(lit > 0);
+            }
+        }
+        values
+    }
+
+    /// Input values of a satisfying assignment, as in
+    /// [`SolverLog::assignment`](flussab_cnf::sat_solver_log::SolverLog).
+    pub fn inputs(&self, assignment: &[i32]) -> Vec<Option<bool>> {
+        Self::values(self.input_var(0), self.input_count, assignment)
+    }
+
+    /// Latch output values of a satisfying assignment.
+    pub fn latches(&self, assignment: &[i32]) -> Vec<Option<bool>> {
+        Self::values(self.latch_var(0), self.latch_count, assignment)
+    }
+}
+
+pub struct Cnf {
+    pub var_count: usize,
+    pub clauses: Vec<Vec<i32>>,
+    pub var_map: VarMap,
+}
+
+impl Cnf {
+    pub fn write(&self, writer: &mut DeferredWriter) {
+        cnf::write_header(
+            writer,
+            cnf::Header {
+                var_count: self.var_count,
+                clause_count: self.clauses.len(),
+            },
+        );
+        for clause in &self.clauses {
+            cnf::write_clause(writer, clause);
+        }
+    }
+}
+
+pub fn to_cnf<L: Lit>(aig: &OrderedAig<L>, config: CnfConfig) -> Cnf {
+    let var_map = VarMap {
+        input_count: aig.input_count,
+        latch_count: aig.latches.len(),
+    };
+    let first_gate = aig.input_count + aig.latches.len() + 1;
+
+    let mut clauses = Vec::with_capacity(3 * aig.and_gates.len() + aig.outputs.len() + 1);
+    clauses.push(vec![-1]);
+    for (k, gate) in aig.and_gates.iter().enumerate() {
+        let out = var_map.lit(L::from_code((first_gate + k) * 2));
+        let [x, y] = gate.inputs.map(|lit| var_map.lit(lit));
+        clauses.push(vec![-out, x]);
+        clauses.push(vec![-out, y]);
+        clauses.push(vec![out, -x, -y]);
+    }
+
+    let outputs = aig.outputs.iter().map(|&lit| var_map.lit(lit));
+    match config.assert_outputs {
+        AssertOutputs::None => {}
+        AssertOutputs::All => clauses.extend(outputs.map(|lit| vec![lit])),
+        AssertOutputs::Any => clauses.push(outputs.collect()),
+    }
+
+    Cnf {
+        var_count: first_gate + aig.and_gates.len(),
+        clauses,
+        var_map,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aig::OrderedAndGate;
+    use flussab::{text::LineReader, DeferredReader};
+    use flussab_cnf::sat_solver_log;
+
+    #[test]
+    fn asserted_and_maps_model_back_to_inputs() {
+        let aig = OrderedAig {
+            max_var_index: 3,
+            input_count: 2,
+            and_gates: vec![OrderedAndGate { inputs: [4, 3] }],
+            outputs: vec![6],
+            ..OrderedAig::<u32>::default()
+        };
+        let cnf = to_cnf(
+            &aig,
+            CnfConfig::default().assert_outputs(AssertOutputs::All),
+        );
+        assert_eq!(cnf.var_count, 4);
+        assert_eq!(
+            cnf.clauses,
+            vec![vec![-1], vec![-4, 3], vec![-4, -2], vec![4, -3, 2], vec![4]]
+        );
+
+        let mut buf = vec![];
+        let mut writer = DeferredWriter::from_write(&mut buf);
+        cnf.write(&mut writer);
+        writer.flush_defer_err();
+        drop(writer);
+        assert!(buf.starts_with(b"p cnf 4 5\n-1 0\n"));
+
+        let log = b"s SATISFIABLE\nv -1 -2 3 4 0\n";
+        let mut reader = LineReader::new(DeferredReader::from_read(&log[..]));
+        let log = sat_solver_log::parse_log::<i32>(&mut reader, Default::default()).unwrap();
+        assert_eq!(
+            cnf.var_map.inputs(&log.assignment),
+            vec![Some(false), Some(true)]
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! AND-tree balancing, the counterpart of ABC's `balance`.
+//!
+//! Every AND gate that is used more than once, used complemented or used outside
+//! the AND gates roots a supergate: the multi-input AND of the leaves reached
+//! through uncomplemented single-fanout gates. Each supergate is rebuilt as a
+//! minimum-depth tree by repeatedly pairing the two leaves that arrive earliest.
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{
+    aig::{Aig, AigStructureError, AndGate, Latch, OrderedAig, Renumber, RenumberConfig},
+    Lit,
+};
+
+struct Builder<L> {
+    and_gates: Vec<AndGate<L>>,
+    strash: HashMap<[usize; 2], usize>,
+    // Level of every variable, inputs and latches at level 0
+    level: Vec<usize>,
+}
+
+impl<L: Lit> Builder<L> {
+    fn level(&self, code: usize) -> usize {
+        self.level[code >> 1]
+    }
+
+    fn and(&mut self, a: usize, b: usize) -> usize {
+        let [a, b] = if a <= b { [a, b] } else { [b, a] };
+        if a == 0 || a ^ 1 == b {
+            return 0;
+        }
+        if a == 1 || a == b {
+            return b;
+        }
+        if let Some(&code) = self.strash.get(&[a, b]) {
+            return code;
+        }
+        let code = self.level.len() * 2;
+        self.level.push(1 + self.level(a).max(self.level(b)));
+        self.and_gates.push(AndGate {
+            inputs: [L::from_code(a), L::from_code(b)],
+            output: L::from_code(code),
+        });
+        self.strash.insert([a, b], code);
+        code
+    }
+
+    // Minimum-depth AND of `leaves`, pairing the earliest arrivals first
+    fn and_tree(&mut self, mut leaves: Vec<usize>) -> usize {
+        leaves.sort_unstable();
+        leaves.dedup();
+        if leaves.windows(2).any(|pair| pair[0] ^ 1 == pair[1]) {
+            return 0;
+        }
+        let mut heap: BinaryHeap<Reverse<(usize, usize)>> = leaves
+            .into_iter()
+            .map(|code| Reverse((self.level(code), code)))
+            .collect();
+        loop {
+            let Some(Reverse((_, a))) = heap.pop() else {
+                return 1;
+            };
+            let Some(Reverse((_, b))) = heap.pop() else {
+                return a;
+            };
+            let code = self.and(a, b);
+            heap.push(Reverse((self.level(code), code)));
+        }
+    }
+}
+
+/// Balances every supergate of `aig`. Inputs, latches, outputs and symbols are
+/// kept, and the result is trimmed and structurally hashed.
+pub fn balance<L: Lit>(aig: &Aig<L>) -> Result<OrderedAig<L>, AigStructureError<L>> {
+    let config = || {
+        RenumberConfig::default()
+            .trim(true)
+            .structural_hash(true)
+            .const_fold(true)
+    };
+    let (ordered, _) = Renumber::renumber_aig(config(), aig)?;
+    let first_gate = ordered.input_count + ordered.latches.len() + 1;
+    let vars = first_gate + ordered.and_gates.len();
+
+    let sinks: Vec<L> = ordered
+        .outputs
+        .iter()
+        .chain(ordered.latches.iter().map(|latch| &latch.next_state))
+        .chain(&ordered.bad_state_properties)
+        .chain(&ordered.invariant_constraints)
+        .chain(&ordered.fairness_constraints)
+        .chain(ordered.justice_properties.iter().flatten())
+        .copied()
+        .collect();
+
+    // A gate is absorbed into its user's supergate when that is its only use
+    let mut refs = vec![0; vars];
+    let mut plain_gate_refs = vec![0; vars];
+    for gate in &ordered.and_gates {
+        for lit in gate.inputs {
+            refs[lit.code() >> 1] += 1;
+            if lit.code() & 1 == 0 {
+                plain_gate_refs[lit.code() >> 1] += 1;
+            }
+        }
+    }
+    for lit in &sinks {
+        refs[lit.code() >> 1] += 1;
+    }
+    let absorbed = |var: usize| var >= first_gate && refs[var] == 1 && plain_gate_refs[var] == 1;
+
+    let mut builder = Builder {
+        and_gates: vec![],
+        strash: HashMap::new(),
+        level: vec![0; first_gate],
+    };
+    // Balanced literal of every input, latch and supergate root
+    let mut map: Vec<usize> = (0..first_gate).map(|var| var * 2).collect();
+    map.resize(vars, 0);
+    let translate = |map: &[usize], code: usize| map[code >> 1] ^ (code & 1);
+
+    for var in first_gate..vars {
+        if absorbed(var) {
+            continue;
+        }
+        let mut leaves = vec![];
+        let mut stack = vec![var * 2];
+        while let Some(code) = stack.pop() {
+            let gate = &ordered.and_gates[(code >> 1) - first_gate];
+            for lit in gate.inputs {
+                if absorbed(lit.code() >> 1) {
+                    stack.push(lit.code());
+                } else {
+                    leaves.push(translate(&map, lit.code()));
+                }
+            }
+        }
+        map[var] = builder.and_tree(leaves);
+    }
+
+    let lit = |lit: &L| L::from_code(translate(&map, lit.code()));
+    let lits = |lits: &[L]| lits.iter().map(lit).collect::<Vec<L>>();
+    let balanced = Aig {
+        max_var_index: builder.level.len() - 1,
+        inputs: (1..=ordered.input_count)
+            .map(|var| L::from_code(var * 2))
+            .collect(),
+        latches: ordered
+            .latches
+            .iter()
+            .enumerate()
+            .map(|(index, latch)| Latch {
+                state: L::from_code((ordered.input_count + 1 + index) * 2),
+                next_state: lit(&latch.next_state),
+                initialization: latch.initialization,
+            })
+            .collect(),
+        outputs: lits(&ordered.outputs),
+        bad_state_properties: lits(&ordered.bad_state_properties),
+        invariant_constraints: lits(&ordered.invariant_constraints),
+        justice_properties: ordered
+            .justice_properties
+            .iter()
+            .map(|property| lits(property))
+            .collect(),
+        fairness_constraints: lits(&ordered.fairness_constraints),
+        and_gates: builder.and_gates,
+        symbols: ordered.symbols,
+        comment: ordered.comment,
+    };
+    let (result, _) = Renumber::renumber_aig(config(), &balanced)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::stats;
+
+    fn aig(and_gates: &[(u32, u32, u32)], outputs: Vec<u32>) -> Aig<u32> {
+        Aig {
+            max_var_index: 12,
+            inputs: vec![2, 4, 6, 8, 10],
+            outputs,
+            and_gates: and_gates
+                .iter()
+                .map(|&(output, a, b)| AndGate {
+                    inputs: [a, b],
+                    output,
+                })
+                .collect(),
+            ..Aig::default()
+        }
+    }
+
+    #[test]
+    fn chain_becomes_a_tree() {
+        // ((((a & b) & c) & d) & e)
+        let chain = aig(
+            &[(12, 2, 4), (14, 12, 6), (16, 14, 8), (18, 16, 10)],
+            vec![18],
+        );
+        let before = stats(
+            &Renumber::renumber_aig(RenumberConfig::default(), &chain)
+                .unwrap()
+                .0,
+        );
+        let after = stats(&balance(&chain).unwrap());
+        assert_eq!(before.levels, 4);
+        assert_eq!(after.levels, 3);
+        assert_eq!(after.and_gates, 4);
+    }
+
+    #[test]
+    fn shared_and_complemented_gates_stay_leaves() {
+        // g12 = a & b is shared, g14 = !g12 & c, g16 = g14 & d
+        let shared = aig(&[(12, 2, 4), (14, 13, 6), (16, 14, 8)], vec![16, 12]);
+        let balanced = balance(&shared).unwrap();
+        assert_eq!(balanced.and_gates.len(), 3);
+        assert_eq!(stats(&balanced).levels, 2);
+
+        // (a & b) & !a is constant false
+        let contradiction = aig(&[(12, 2, 4), (14, 12, 3)], vec![14]);
+        let balanced = balance(&contradiction).unwrap();
+        assert!(balanced.and_gates.is_empty());
+        assert_eq!(balanced.outputs, vec![0]);
+    }
+}
@@ -0,0 +1,790 @@
+//! Bit-blasting of word-level BTOR2 into an [`Aig`].
+//!
+//! Every bit-vector value becomes a vector of AIG literals, least significant bit
+//! first. Inputs and states become one AIG input or latch per bit, named after
+//! the BTOR2 symbol with a `[i]` suffix for wider values. Arithmetic uses ripple
+//! carry adders, shift-add multipliers, restoring dividers and barrel shifters,
+//! with BTOR2's division by zero semantics. Arrays are not supported.
+use std::collections::HashMap;
+
+use flussab_btor2::{
+    btor2::{
+        AssignmentKind, BinaryOp, Const, Line, Node, NodeId, NodeVariant, Op, Output,
+        SingleValueOutputKind, Sort, TernaryOp, UnaryOp, ValueVariant,
+    },
+    ParseError, Parser,
+};
+use thiserror::Error;
+
+use crate::{
+    aig::{Aig, AndGate, Latch, Symbol, SymbolTarget},
+    Lit,
+};
+
+#[derive(Error, Debug)]
+pub enum BitblastError {
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error("node {id} is used before it is defined")]
+    UndefinedNode { id: u64 },
+    #[error("node {id}: {what} is not supported")]
+    Unsupported { id: u64, what: &'static str },
+    #[error("node {id}: operand widths do not match")]
+    WidthMismatch { id: u64 },
+    #[error("node {id} is not a state")]
+    NotAState { id: u64 },
+    #[error("node {id}: only constant initial values are supported")]
+    NonConstantInit { id: u64 },
+}
+
+const FALSE: usize = 0;
+const TRUE: usize = 1;
+
+/// Builds an [`Aig`] from the lines of a BTOR2 file, in file order.
+pub struct Bitblaster<L> {
+    aig: Aig<L>,
+    strash: HashMap<[usize; 2], usize>,
+    next_var: usize,
+    // Bit width of every bit-vector sort, `None` for arrays
+    sorts: HashMap<u64, Option<usize>>,
+    values: HashMap<u64, Vec<usize>>,
+    // Latch indices of the bits of every state
+    states: HashMap<u64, Vec<usize>>,
+    has_next: Vec<bool>,
+}
+
+impl<L: Lit> Default for Bitblaster<L> {
+    fn default() -> Self {
+        Bitblaster {
+            aig: Aig::default(),
+            strash: HashMap::new(),
+            next_var: 1,
+            sorts: HashMap::new(),
+            values: HashMap::new(),
+            states: HashMap::new(),
+            has_next: vec![],
+        }
+    }
+}
+
+fn bit_name(name: &str, bit: usize, width: usize) -> String {
+    if width == 1 {
+        name.to_string()
+    } else {
+        format!("{}[{}]", name, bit)
+    }
+}
+
+fn id(node: NodeId) -> u64 {
+    node.0.get()
+}
+
+// Bits of a constant given by digits in `radix`, most significant digit first
+fn const_bits(digits: &str, radix: u32, width: usize) -> Vec<bool> {
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, digits),
+    };
+    let mut digits: Vec<u32> = digits.chars().filter_map(|c| c.to_digit(radix)).collect();
+    let mut bits = Vec::with_capacity(width);
+    while bits.len() < width {
+        // Long division of the digit string by 2
+        let mut remainder = 0;
+        for digit in &mut digits {
+            let value = remainder * radix + *digit;
+            *digit = value / 2;
+            remainder = value % 2;
+        }
+        bits.push(remainder == 1);
+    }
+    if negative {
+        let mut carry = true;
+        for bit in &mut bits {
+            let inverted = !*bit;
+            *bit = inverted ^ carry;
+            carry &= inverted;
+        }
+    }
+    bits
+}
+
+impl<L: Lit> Bitblaster<L> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn fresh_var(&mut self) -> usize {
+        let code = self.next_var * 2;
+        self.next_var += 1;
+        code
+    }
+
+    fn and(&mut self, a: usize, b: usize) -> usize {
+        let [a, b] = if a <= b { [a, b] } else { [b, a] };
+        if a == FALSE || a ^ 1 == b {
+            return FALSE;
+        }
+        if a == TRUE || a == b {
+            return b;
+        }
+        if let Some(&code) = self.strash.get(&[a, b]) {
+            return code;
+        }
+        let code = self.fresh_var();
+        self.aig.and_gates.push(AndGate {
+            inputs: [L::from_code(a), L::from_code(b)],
+            output: L::from_code(code),
+        });
+        self.strash.insert([a, b], code);
+        code
+    }
+
+    fn or(&mut self, a: usize, b: usize) -> usize {
+        self.and(a ^ 1, b ^ 1) ^ 1
+    }
+
+    fn xor(&mut self, a: usize, b: usize) -> usize {
+        let x = self.and(a, b ^ 1);
+        let y = self.and(a ^ 1, b);
+        self.or(x, y)
+    }
+
+    fn mux(&mut self, select: usize, then: usize, other: usize) -> usize {
+        let x = self.and(select, then);
+        let y = self.and(select ^ 1, other);
+        self.or(x, y)
+    }
+
+    fn and_all(&mut self, bits: &[usize]) -> usize {
+        bits.iter().fold(TRUE, |acc, &bit| self.and(acc, bit))
+    }
+
+    fn or_all(&mut self, bits: &[usize]) -> usize {
+        bits.iter().fold(FALSE, |acc, &bit| self.or(acc, bit))
+    }
+
+    fn zip(
+        &mut self,
+        a: &[usize],
+        b: &[usize],
+        op: fn(&mut Self, usize, usize) -> usize,
+    ) -> Vec<usize> {
+        a.iter().zip(b).map(|(&x, &y)| op(self, x, y)).collect()
+    }
+
+    fn mux_all(&mut self, select: usize, then: &[usize], other: &[usize]) -> Vec<usize> {
+        then.iter()
+            .zip(other)
+            .map(|(&x, &y)| self.mux(select, x, y))
+            .collect()
+    }
+
+    // Sum and carry out
+    fn add(&mut self, a: &[usize], b: &[usize], mut carry: usize) -> (Vec<usize>, usize) {
+        let mut sum = Vec::with_capacity(a.len());
+        for (&x, &y) in a.iter().zip(b) {
+            let half = self.xor(x, y);
+            sum.push(self.xor(half, carry));
+            let generate = self.and(x, y);
+            let propagate = self.and(half, carry);
+            carry = self.or(generate, propagate);
+        }
+        (sum, carry)
+    }
+
+    // Difference and carry out, which is set unless `b` is larger than `a`
+    fn sub(&mut self, a: &[usize], b: &[usize]) -> (Vec<usize>, usize) {
+        let inverted: Vec<usize> = b.iter().map(|bit| bit ^ 1).collect();
+        self.add(a, &inverted, TRUE)
+    }
+
+    fn neg(&mut self, a: &[usize]) -> Vec<usize> {
+        let zero = vec![FALSE; a.len()];
+        self.sub(&zero, a).0
+    }
+
+    fn ult(&mut self, a: &[usize], b: &[usize]) -> usize {
+        self.sub(a, b).1 ^ 1
+    }
+
+    fn slt(&mut self, a: &[usize], b: &[usize]) -> usize {
+        let flip = |bits: &[usize]| {
+            let mut bits = bits.to_vec();
+            *bits.last_mut().unwrap() ^= 1;
+            bits
+        };
+        self.ult(&flip(a), &flip(b))
+    }
+
+    fn eq(&mut self, a: &[usize], b: &[usize]) -> usize {
+        let same: Vec<usize> = self
+            .zip(a, b, Self::xor)
+            .iter()
+            .map(|bit| bit ^ 1)
+            .collect();
+        self.and_all(&same)
+    }
+
+    // Product truncated to the width of `a`
+    fn mul(&mut self, a: &[usize], b: &[usize]) -> Vec<usize> {
+        let width = a.len();
+        let mut product = vec![FALSE; width];
+        for (shift, &y) in b.iter().enumerate().take(width) {
+            let mut partial = vec![FALSE; shift];
+            for &x in &a[..width - shift] {
+                partial.push(self.and(x, y));
+            }
+            product = self.add(&product, &partial, FALSE).0;
+        }
+        product
+    }
+
+    // Quotient and remainder, all ones and `a` when dividing by zero
+    fn udivrem(&mut self, a: &[usize], b: &[usize]) -> (Vec<usize>, Vec<usize>) {
+        let width = a.len();
+        let mut divisor = b.to_vec();
+        divisor.push(FALSE);
+        let mut quotient = vec![FALSE; width];
+        let mut remainder = vec![FALSE; width];
+        for i in (0..width).rev() {
+            let mut shifted = vec![a[i]];
+            shifted.extend_from_slice(&remainder);
+            let (difference, fits) = self.sub(&shifted, &divisor);
+            quotient[i] = fits;
+            remainder = self.mux_all(fits, &difference[..width], &shifted[..width]);
+        }
+        (quotient, remainder)
+    }
+
+    fn abs(&mut self, a: &[usize]) -> Vec<usize> {
+        let negated = self.neg(a);
+        self.mux_all(*a.last().unwrap(), &negated, a)
+    }
+
+    fn negate_if(&mut self, condition: usize, a: &[usize]) -> Vec<usize> {
+        let negated = self.neg(a);
+        self.mux_all(condition, &negated, a)
+    }
+
+    fn shift(&mut self, a: &[usize], amount: &[usize], left: bool, fill: usize) -> Vec<usize> {
+        let width = a.len();
+        let mut result = a.to_vec();
+        let mut overflow = vec![];
+        for (k, &bit) in amount.iter().enumerate() {
+            let step = match 1usize.checked_shl(k as u32) {
+                Some(step) if step < width => step,
+                _ => {
+                    overflow.push(bit);
+                    continue;
+                }
+            };
+            let shifted: Vec<usize> = (0..width)
+                .map(|i| match left {
+                    true if i >= step => result[i - step],
+                    false if i + step < width => result[i + step],
+                    _ => fill,
+                })
+                .collect();
+            result = self.mux_all(bit, &shifted, &result);
+        }
+        let overflow = self.or_all(&overflow);
+        let filled = vec![fill; width];
+        self.mux_all(overflow, &filled, &result)
+    }
+
+    fn rotate(&mut self, a: &[usize], amount: &[usize], left: bool) -> Vec<usize> {
+        let width = a.len();
+        let modulus: Vec<usize> = (0..amount.len())
+            .map(|k| {
+                if k < usize::BITS as usize && (width >> k) & 1 == 1 {
+                    TRUE
+                } else {
+                    FALSE
+                }
+            })
+            .collect();
+        let amount = self.udivrem(amount, &modulus).1;
+        let mut result = a.to_vec();
+        for (k, &bit) in amount.iter().enumerate() {
+            let step = match 1usize.checked_shl(k as u32) {
+                Some(step) if step < width => step,
+                _ => break,
+            };
+            let rotated: Vec<usize> = (0..width)
+                .map(|i| match left {
+                    true => result[(i + width - step) % width],
+                    false => result[(i + step) % width],
+                })
+                .collect();
+            result = self.mux_all(bit, &rotated, &result);
+        }
+        result
+    }
+
+    fn sort_width(&self, node: u64, sort: NodeId) -> Result<usize, BitblastError> {
+        match self.sorts.get(&id(sort)) {
+            Some(Some(width)) => Ok(*width),
+            Some(None) => Err(BitblastError::Unsupported {
+                id: node,
+                what: "array sort",
+            }),
+            None => Err(BitblastError::UndefinedNode { id: id(sort) }),
+        }
+    }
+
+    fn value(&self, node: NodeId) -> Result<&[usize], BitblastError> {
+        self.values
+            .get(&id(node))
+            .map(Vec::as_slice)
+            .ok_or(BitblastError::UndefinedNode { id: id(node) })
+    }
+
+    fn single_bit(&self, node: u64, value: NodeId) -> Result<L, BitblastError> {
+        match self.value(value)? {
+            &[bit] => Ok(L::from_code(bit)),
+            _ => Err(BitblastError::WidthMismatch { id: node }),
+        }
+    }
+
+    fn add_symbol(&mut self, target: SymbolTarget, name: String) {
+        self.aig.symbols.push(Symbol {
+            target,
+            name: name.into(),
+        });
+    }
+
+    /// Adds the node defined by `line`. Comments are ignored.
+    pub fn add_line(&mut self, line: &Line) -> Result<(), BitblastError> {
+        match line {
+            Line::Comment(_) => Ok(()),
+            Line::Node(node) => self.add_node(node),
+        }
+    }
+
+    fn add_node(&mut self, node: &Node) -> Result<(), BitblastError> {
+        let node_id = id(node.id);
+        let name = node.symbol.map(|symbol| symbol.to_string());
+        match node.variant {
+            NodeVariant::Sort(Sort::BitVec(width)) => {
+                self.sorts.insert(node_id, Some(width.get() as usize));
+            }
+            NodeVariant::Sort(Sort::Array(_)) => {
+                self.sorts.insert(node_id, None);
+            }
+            NodeVariant::Value(value) => {
+                let width = self.sort_width(node_id, value.sort)?;
+                let bits = match value.variant {
+                    ValueVariant::Const(constant) => {
+                        let bits = match constant {
+                            Const::Binary(digits) => const_bits(&digits.to_string(), 2, width),
+                            Const::Decimal(digits) => const_bits(&digits.to_string(), 10, width),
+                            Const::Hex(digits) => const_bits(&digits.to_string(), 16, width),
+                            Const::One => const_bits("1", 2, width),
+                            Const::Ones => vec![true; width],
+                            Const::Zero => vec![false; width],
+                        };
+                        bits.into_iter().map(usize::from).collect()
+                    }
+                    ValueVariant::Input => {
+                        let mut bits = Vec::with_capacity(width);
+                        for bit in 0..width {
+                            let code = self.fresh_var();
+                            if let Some(name) = &name {
+                                self.add_symbol(
+                                    SymbolTarget::Input(self.aig.inputs.len()),
+                                    bit_name(name, bit, width),
+                                );
+                            }
+                            self.aig.inputs.push(L::from_code(code));
+                            bits.push(code);
+                        }
+                        bits
+                    }
+                    ValueVariant::State => {
+                        let mut bits = Vec::with_capacity(width);
+                        let mut latches = Vec::with_capacity(width);
+                        for bit in 0..width {
+                            let code = self.fresh_var();
+                            if let Some(name) = &name {
+                                self.add_symbol(
+                                    SymbolTarget::Latch(self.aig.latches.len()),
+                                    bit_name(name, bit, width),
+                                );
+                            }
+                            latches.push(self.aig.latches.len());
+                            self.aig.latches.push(Latch {
+                                state: L::from_code(code),
+                                next_state: L::from_code(code),
+                                initialization: None,
+                            });
+                            self.has_next.push(false);
+                            bits.push(code);
+                        }
+                        self.states.insert(node_id, latches);
+                        bits
+                    }
+                    ValueVariant::Op(op) => self.op(node_id, op)?,
+                };
+                if bits.len() != width {
+                    return Err(BitblastError::WidthMismatch { id: node_id });
+                }
+                self.values.insert(node_id, bits);
+            }
+            NodeVariant::Assignment(assignment) => {
+                let latches = self
+                    .states
+                    .get(&id(assignment.state))
+                    .ok_or(BitblastError::NotAState {
+                        id: id(assignment.state),
+                    })?
+                    .clone();
+                let bits = self.value(assignment.value)?.to_vec();
+                if bits.len() != latches.len() {
+                    return Err(BitblastError::WidthMismatch { id: node_id });
+                }
+                for (latch, bit) in latches.into_iter().zip(bits) {
+                    match assignment.kind {
+                        AssignmentKind::Init => {
+                            if bit > TRUE {
+                                return Err(BitblastError::NonConstantInit { id: node_id });
+                            }
+                            self.aig.latches[latch].initialization = Some(bit == TRUE);
+                        }
+                        AssignmentKind::Next => {
+                            self.aig.latches[latch].next_state = L::from_code(bit);
+                            self.has_next[latch] = true;
+                        }
+                    }
+                }
+            }
+            NodeVariant::Output(Output::SingleValue(output)) => match output.kind {
+                SingleValueOutputKind::Output => {
+                    let bits = self.value(output.value)?.to_vec();
+                    let width = bits.len();
+                    for (bit, code) in bits.into_iter().enumerate() {
+                        if let Some(name) = &name {
+                            self.add_symbol(
+                                SymbolTarget::Output(self.aig.outputs.len()),
+                                bit_name(name, bit, width),
+                            );
+                        }
+                        self.aig.outputs.push(L::from_code(code));
+                    }
+                }
+                kind => {
+                    let lit = self.single_bit(node_id, output.value)?;
+                    let (target, lits): (fn(usize) -> SymbolTarget, &mut Vec<L>) = match kind {
+                        SingleValueOutputKind::Bad => (
+                            SymbolTarget::BadStateProperty,
+                            &mut self.aig.bad_state_properties,
+                        ),
+                        SingleValueOutputKind::Constraint => (
+                            SymbolTarget::InvariantConstraint,
+                            &mut self.aig.invariant_constraints,
+                        ),
+                        _ => (
+                            SymbolTarget::FairnessConstraint,
+                            &mut self.aig.fairness_constraints,
+                        ),
+                    };
+                    let target = target(lits.len());
+                    lits.push(lit);
+                    if let Some(name) = name {
+                        self.add_symbol(target, name);
+                    }
+                }
+            },
+            NodeVariant::Output(Output::Justice(conditions)) => {
+                let lits = conditions
+                    .iter()
+                    .map(|&condition| self.single_bit(node_id, condition))
+                    .collect::<Result<Vec<L>, _>>()?;
+                if let Some(name) = name {
+                    self.add_symbol(
+                        SymbolTarget::JusticeProperty(self.aig.justice_properties.len()),
+                        name,
+                    );
+                }
+                self.aig.justice_properties.push(lits);
+            }
+        }
+        Ok(())
+    }
+
+    fn op(&mut self, node: u64, op: Op) -> Result<Vec<usize>, BitblastError> {
+        let bits = match op {
+            Op::Unary(op, arg) => {
+                let a = self.value(arg)?.to_vec();
+                let sign = *a.last().unwrap();
+                match op {
+                    UnaryOp::Uext(extra) => [a, vec![FALSE; extra as usize]].concat(),
+                    UnaryOp::Sext(extra) => [a, vec![sign; extra as usize]].concat(),
+                    UnaryOp::Slice(upper, lower) => a
+                        .get(lower as usize..=upper as usize)
+                        .ok_or(BitblastError::WidthMismatch { id: node })?
+                        .to_vec(),
+                    UnaryOp::Not => a.iter().map(|bit| bit ^ 1).collect(),
+                    UnaryOp::Inc => self.add(&a, &vec![FALSE; a.len()], TRUE).0,
+                    UnaryOp::Dec => self.add(&a, &vec![TRUE; a.len()], FALSE).0,
+                    UnaryOp::Neg => self.neg(&a),
+                    UnaryOp::Redand => vec![self.and_all(&a)],
+                    UnaryOp::Redor => vec![self.or_all(&a)],
+                    UnaryOp::Redxor => vec![a.iter().fold(FALSE, |acc, &bit| self.xor(acc, bit))],
+                }
+            }
+            Op::Binary(op, [arg_a, arg_b]) => {
+                let a = self.value(arg_a)?.to_vec();
+                let b = self.value(arg_b)?.to_vec();
+                match op {
+                    BinaryOp::Concat => return Ok([b, a].concat()),
+                    BinaryOp::Read => {
+                        return Err(BitblastError::Unsupported {
+                            id: node,
+                            what: "read",
+                        })
+                    }
+                    _ if a.len() != b.len() => {
+                        return Err(BitblastError::WidthMismatch { id: node })
+                    }
+                    _ => {}
+                }
+                let w = a.len();
+                let (sign_a, sign_b) = (a[w - 1], b[w - 1]);
+                match op {
+                    BinaryOp::Iff | BinaryOp::Eq => vec![self.eq(&a, &b)],
+                    BinaryOp::Neq => vec![self.eq(&a, &b) ^ 1],
+                    BinaryOp::Implies => vec![self.or(a[0] ^ 1, b[0])],
+                    BinaryOp::Ugt => vec![self.ult(&b, &a)],
+                    BinaryOp::Ugte => vec![self.ult(&a, &b) ^ 1],
+                    BinaryOp::Ult => vec![self.ult(&a, &b)],
+                    BinaryOp::Ulte => vec![self.ult(&b, &a) ^ 1],
+                    BinaryOp::Sgt => vec![self.slt(&b, &a)],
+                    BinaryOp::Sgte => vec![self.slt(&a, &b) ^ 1],
+                    BinaryOp::Slt => vec![self.slt(&a, &b)],
+                    BinaryOp::Slte => vec![self.slt(&b, &a) ^ 1],
+                    BinaryOp::And => self.zip(&a, &b, Self::and),
+                    BinaryOp::Nand => self
+                        .zip(&a, &b, Self::and)
+                        .iter()
+                        .map(|bit| bit ^ 1)
+                        .collect(),
+                    BinaryOp::Or => self.zip(&a, &b, Self::or),
+                    BinaryOp::Nor => self
+                        .zip(&a, &b, Self::or)
+                        .iter()
+                        .map(|bit| bit ^ 1)
+                        .collect(),
+                    BinaryOp::Xor => self.zip(&a, &b, Self::xor),
+                    BinaryOp::Xnor => self
+                        .zip(&a, &b, Self::xor)
+                        .iter()
+                        .map(|bit| bit ^ 1)
+                        .collect(),
+                    BinaryOp::Rol => self.rotate(&a, &b, true),
+                    BinaryOp::Ror => self.rotate(&a, &b, false),
+                    BinaryOp::Sll => self.shift(&a, &b, true, FALSE),
+                    BinaryOp::Srl => self.shift(&a, &b, false, FALSE),
+                    BinaryOp::Sra => self.shift(&a, &b, false, sign_a),
+                    BinaryOp::Add => self.add(&a, &b, FALSE).0,
+                    BinaryOp::Sub => self.sub(&a, &b).0,
+                    BinaryOp::Mul => self.mul(&a, &b),
+                    BinaryOp::Udiv => self.udivrem(&a, &b).0,
+                    BinaryOp::Urem => self.udivrem(&a, &b).1,
+                    BinaryOp::Sdiv => {
+                        let (abs_a, abs_b) = (self.abs(&a), self.abs(&b));
+                        let quotient = self.udivrem(&abs_a, &abs_b).0;
+                        let negative = self.xor(sign_a, sign_b);
+                        self.negate_if(negative, &quotient)
+                    }
+                    BinaryOp::Srem => {
+                        let (abs_a, abs_b) = (self.abs(&a), self.abs(&b));
+                        let remainder = self.udivrem(&abs_a, &abs_b).1;
+                        self.negate_if(sign_a, &remainder)
+                    }
+                    BinaryOp::Smod => {
+                        // The remainder takes the sign of the divisor, as in SMT-LIB's bvsmod
+                        let (abs_a, abs_b) = (self.abs(&a), self.abs(&b));
+                        let remainder = self.udivrem(&abs_a, &abs_b).1;
+                        let signed = self.negate_if(sign_a, &remainder);
+                        let adjusted = self.add(&signed, &b, FALSE).0;
+                        let zero = self.or_all(&remainder) ^ 1;
+                        let same_sign = self.xor(sign_a, sign_b) ^ 1;
+                        let keep = self.or(zero, same_sign);
+                        self.mux_all(keep, &signed, &adjusted)
+                    }
+                    BinaryOp::Uaddo => vec![self.add(&a, &b, FALSE).1],
+                    BinaryOp::Usubo => vec![self.sub(&a, &b).1 ^ 1],
+                    BinaryOp::Saddo | BinaryOp::Ssubo => {
+                        let (result, sign_b) = match op {
+                            BinaryOp::Saddo => (self.add(&a, &b, FALSE).0, sign_b),
+                            _ => (self.sub(&a, &b).0, sign_b ^ 1),
+                        };
+                        let same_sign = self.xor(sign_a, sign_b) ^ 1;
+                        let flipped = self.xor(sign_a, result[w - 1]);
+                        vec![self.and(same_sign, flipped)]
+                    }
+                    BinaryOp::Umulo | BinaryOp::Smulo => {
+                        // Full-width product of the extended operands
+                        let extend =
+                            |bits: &[usize], fill: usize| [bits.to_vec(), vec![fill; w]].concat();
+                        let product = if op == BinaryOp::Umulo {
+                            let product = self.mul(&extend(&a, FALSE), &extend(&b, FALSE));
+                            product[w..].to_vec()
+                        } else {
+                            let product = self.mul(&extend(&a, sign_a), &extend(&b, sign_b));
+                            let sign = product[w - 1];
+                            product[w..]
+                                .iter()
+                                .map(|&bit| self.xor(bit, sign))
+                                .collect()
+                        };
+                        vec![self.or_all(&product)]
+                    }
+                    BinaryOp::Sdivo => {
+                        let min: Vec<usize> = (0..w).map(|i| usize::from(i == w - 1)).collect();
+                        let is_min = self.eq(&a, &min);
+                        let minus_one = self.and_all(&b);
+                        vec![self.and(is_min, minus_one)]
+                    }
+                    BinaryOp::Concat | BinaryOp::Read => unreachable!(),
+                }
+            }
+            Op::Ternary(TernaryOp::Ite, [condition, then, other]) => {
+                let condition = self.single_bit(node, condition)?.code();
+                let then = self.value(then)?.to_vec();
+                let other = self.value(other)?.to_vec();
+                if then.len() != other.len() {
+                    return Err(BitblastError::WidthMismatch { id: node });
+                }
+                self.mux_all(condition, &then, &other)
+            }
+            Op::Ternary(TernaryOp::Write, _) => {
+                return Err(BitblastError::Unsupported {
+                    id: node,
+                    what: "write",
+                })
+            }
+        };
+        Ok(bits)
+    }
+
+    /// The bit-blasted AIG. States without a next-state function get a fresh
+    /// input for every bit, as BTOR2 leaves them unconstrained.
+    pub fn finish(mut self) -> Aig<L> {
+        for latch in 0..self.aig.latches.len() {
+            if !self.has_next[latch] {
+                let code = self.fresh_var();
+                self.aig.inputs.push(L::from_code(code));
+                self.aig.latches[latch].next_state = L::from_code(code);
+            }
+        }
+        self.aig.max_var_index = self.next_var - 1;
+        self.aig
+    }
+}
+
+/// Bit-blasts every line of a BTOR2 file.
+pub fn bitblast<L: Lit>(parser: &mut Parser) -> Result<Aig<L>, BitblastError> {
+    let mut bitblaster = Bitblaster::new();
+    while let Some(line) = parser.next_line()? {
+        bitblaster.add_line(&line)?;
+    }
+    Ok(bitblaster.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use flussab_btor2::Config;
+
+    use super::*;
+    use crate::{
+        aig::{Renumber, RenumberConfig},
+        simulate::{Patterns, Simulation},
+    };
+
+    fn blast(source: &str) -> Aig<u32> {
+        let mut parser = Parser::from_read(source.as_bytes(), Config::default()).unwrap();
+        bitblast(&mut parser).unwrap()
+    }
+
+    // Every input pattern with the outputs read as a number
+    fn outputs(aig: &Aig<u32>) -> Vec<(u64, u64)> {
+        let (ordered, _) = Renumber::renumber_aig(RenumberConfig::default(), aig).unwrap();
+        let simulation = Simulation::new(&ordered, Patterns::Exhaustive).unwrap();
+        let signatures = simulation.output_signatures();
+        (0..simulation.patterns)
+            .map(|pattern| {
+                let bit = |signature: &Vec<u64>| (signature[pattern / 64] >> (pattern % 64)) & 1;
+                let value = signatures
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, signature| acc << 1 | bit(signature));
+                (pattern as u64, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arithmetic_matches_word_level_semantics() {
+        type Reference = fn(u64, u64) -> u64;
+        let ops: [(&str, Reference); 6] = [
+            ("add", |a, b| (a + b) % 8),
+            ("mul", |a, b| (a * b) % 8),
+            ("udiv", |a, b| a.checked_div(b).unwrap_or(7)),
+            ("urem", |a, b| a.checked_rem(b).unwrap_or(a)),
+            ("sll", |a, b| if b >= 3 { 0 } else { (a << b) % 8 }),
+            ("ror", |a, b| {
+                let b = b % 3;
+                ((a >> b) | (a << (3 - b))) % 8
+            }),
+        ];
+        for (op, reference) in ops {
+            let source = format!(
+                "1 sort bitvec 3\n2 input 1 a\n3 input 1 b\n4 {} 1 2 3\n5 output 4 y\n",
+                op
+            );
+            let aig = blast(&source);
+            assert_eq!(aig.inputs.len(), 6);
+            assert_eq!(aig.symbols[0].name, "a[0]");
+            for (pattern, value) in outputs(&aig) {
+                let (a, b) = (pattern & 7, pattern >> 3);
+                assert_eq!(value, reference(a, b), "{} {} {}", op, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn counter_with_init_and_next() {
+        let aig = blast(
+            "1 sort bitvec 2\n2 sort bitvec 1\n3 zero 1\n4 state 1 count\n5 init 1 4 3\n\
+             6 one 1\n7 add 1 4 6\n8 next 1 4 7\n9 constd 1 -1\n10 eq 2 4 9\n11 bad 10 full\n\
+             12 slice 2 4 1 1\n13 concat 1 12 12\n14 ite 1 10 13 4\n15 output 14\n",
+        );
+        assert_eq!(aig.latches.len(), 2);
+        assert!(aig
+            .latches
+            .iter()
+            .all(|latch| latch.initialization == Some(false)));
+        assert_eq!(aig.bad_state_properties.len(), 1);
+        assert_eq!(aig.outputs.len(), 2);
+        assert!(aig
+            .symbols
+            .iter()
+            .any(|symbol| symbol.target == SymbolTarget::BadStateProperty(0)
+                && symbol.name == "full"));
+
+        // The latches are inputs of the combinational part: count + 1, and
+        // count == 3 on all patterns
+        let (ordered, _) = Renumber::renumber_aig(RenumberConfig::default(), &aig).unwrap();
+        let simulation = Simulation::new(&ordered, Patterns::Exhaustive).unwrap();
+        let next = ordered
+            .latches
+            .iter()
+            .map(|latch| simulation.lit_values(latch.next_state)[0] & 0xf);
+        assert_eq!(next.collect::<Vec<_>>(), vec![0b0101, 0b0110]);
+        assert_eq!(
+            simulation.lit_values(ordered.bad_state_properties[0])[0] & 0xf,
+            0b1000
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//! Combinational equivalence checking of two [`Aig`]s.
+//!
+//! The AIGs are joined into a miter whose inputs are shared by name and whose
+//! outputs are the XORs of equally named outputs. Random simulation of the miter
+//! looks for a cheap counterexample first. If there is none, the Tseitin encoding
+//! of the miter with a clause requiring some XOR to be true goes to the built-in
+//! [`Solver`], and UNSAT proves the AIGs equivalent.
+use std::collections::HashMap;
+
+use flussab::DeferredWriter;
+use thiserror::Error;
+
+use crate::{
+    aig::{
+        to_cnf::{to_cnf, AssertOutputs, Cnf, CnfConfig},
+        Aig, AndGate, OrderedAig, Renumber, RenumberConfig, SymbolTarget,
+    },
+    sat::{SatResult, Solver},
+    simulate::{Patterns, Simulation},
+    Lit,
+};
+
+#[derive(Error, Debug)]
+pub enum CecError {
+    #[error("sequential AIGs are not supported, found {0} latches")]
+    Latches(usize),
+    #[error("{kind} `{name}` appears more than once")]
+    DuplicateName { kind: &'static str, name: String },
+    #[error("{kind} `{name}` only exists in one of the AIGs")]
+    UnmatchedName { kind: &'static str, name: String },
+    #[error("invalid AIG structure: {0}")]
+    Structure(String),
+}
+
+pub struct CecConfig {
+    /// 64-pattern words of random simulation before calling the solver.
+    pub sim_words: usize,
+    pub seed: u64,
+    /// Conflicts after which the solver gives up, `None` for no limit.
+    pub conflict_limit: Option<u64>,
+}
+
+impl Default for CecConfig {
+    fn default() -> Self {
+        CecConfig {
+            sim_words: 16,
+            seed: 0,
+            conflict_limit: None,
+        }
+    }
+}
+
+/// An input assignment for which the AIGs differ.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Counterexample {
+    /// Value of every input, by name.
+    pub inputs: Vec<(String, bool)>,
+    /// Outputs that differ under `inputs`.
+    pub outputs: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CecResult {
+    Equivalent,
+    NotEquivalent(Counterexample),
+    /// The solver hit its conflict limit.
+    Undecided,
+}
+
+/// Miter of two AIGs. Inputs and outputs follow the order of the first AIG.
+pub struct Miter<L> {
+    pub aig: OrderedAig<L>,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+}
+
+// Input and output names, `i<n>` and `o<n>` where there is no symbol
+fn interface_names<L>(aig: &Aig<L>) -> (Vec<String>, Vec<String>) {
+    let mut inputs: Vec<String> = (0..aig.inputs.len()).map(|i| format!("i{}", i)).collect();
+    let mut outputs: Vec<String> = (0..aig.outputs.len()).map(|i| format!("o{}", i)).collect();
+    for symbol in &aig.symbols {
+        match symbol.target {
+            SymbolTarget::Input(i) if i < inputs.len() => inputs[i] = symbol.name.to_string(),
+            SymbolTarget::Output(i) if i < outputs.len() => outputs[i] = symbol.name.to_string(),
+            _ => {}
+        }
+    }
+    (inputs, outputs)
+}
+
+// Position of every name in `b` for each name of `a`
+fn match_names(kind: &'static str, a: &[String], b: &[String]) -> Result<Vec<usize>, CecError> {
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for (i, name) in b.iter().enumerate() {
+        if index.insert(name, i).is_some() {
+            return Err(CecError::DuplicateName {
+                kind,
+                name: name.clone(),
+            });
+        }
+    }
+    let mut seen = vec![false; b.len()];
+    let mut matching = Vec::with_capacity(a.len());
+    for name in a {
+        let Some(&i) = index.get(name.as_str()) else {
+            return Err(CecError::UnmatchedName {
+                kind,
+                name: name.clone(),
+            });
+        };
+        if std::mem::replace(&mut seen[i], true) {
+            return Err(CecError::DuplicateName {
+                kind,
+                name: name.clone(),
+            });
+        }
+        matching.push(i);
+    }
+    if let Some(i) = seen.iter().position(|&seen| !seen) {
+        return Err(CecError::UnmatchedName {
+            kind,
+            name: b[i].clone(),
+        });
+    }
+    Ok(matching)
+}
+
+// Code of `lit` in the miter, an error when it reads an undefined variable
+fn translate<L: Lit>(map: &HashMap<usize, usize>, lit: L) -> Result<usize, CecError> {
+    match map.get(&(lit.code() >> 1)) {
+        Some(&code) => Ok(code ^ (lit.code() & 1)),
+        None => Err(CecError::Structure(format!(
+            "literal {} is neither an input nor an AND gate",
+            lit.code()
+        ))),
+    }
+}
+
+// Copies the gates of `aig` into `gates`, with `inputs` as the codes of its inputs
+fn copy_gates<L: Lit>(
+    aig: &Aig<L>,
+    inputs: &[usize],
+    next_code: &mut usize,
+    gates: &mut Vec<[usize; 3]>,
+) -> Result<HashMap<usize, usize>, CecError> {
+    let mut map: HashMap<usize, usize> = HashMap::new();
+    map.insert(0, 0);
+    for (lit, &code) in aig.inputs.iter().zip(inputs) {
+        map.insert(lit.code() >> 1, code);
+    }
+    for gate in &aig.and_gates {
+        map.insert(gate.output.code() >> 1, *next_code);
+        *next_code += 2;
+    }
+    for gate in &aig.and_gates {
+        gates.push([
+            translate(&map, gate.output)?,
+            translate(&map, gate.inputs[0])?,
+            translate(&map, gate.inputs[1])?,
+        ]);
+    }
+    Ok(map)
+}
+
+impl<L: Lit> Miter<L> {
+    pub fn new(a: &Aig<L>, b: &Aig<L>) -> Result<Self, CecError> {
+        for aig in [a, b] {
+            if !aig.latches.is_empty() {
+                return Err(CecError::Latches(aig.latches.len()));
+            }
+        }
+        let (a_inputs, a_outputs) = interface_names(a);
+        let (b_inputs, b_outputs) = interface_names(b);
+        let input_match = match_names("input", &a_inputs, &b_inputs)?;
+        let output_match = match_names("output", &a_outputs, &b_outputs)?;
+
+        let mut next_code = 2;
+        let a_input_codes: Vec<usize> = (0..a.inputs.len()).map(|i| 2 * (i + 1)).collect();
+        next_code += 2 * a.inputs.len();
+        let mut b_input_codes = vec![0; b.inputs.len()];
+        for (i, &j) in input_match.iter().enumerate() {
+            b_input_codes[j] = a_input_codes[i];
+        }
+
+        let mut gates = vec![];
+        let a_map = copy_gates(a, &a_input_codes, &mut next_code, &mut gates)?;
+        let b_map = copy_gates(b, &b_input_codes, &mut next_code, &mut gates)?;
+        let mut and = |x: usize, y: usize, gates: &mut Vec<[usize; 3]>| {
+            let code = next_code;
+            next_code += 2;
+            gates.push([code, x, y]);
+            code
+        };
+
+        let mut outputs = vec![];
+        for (i, &j) in output_match.iter().enumerate() {
+            let x = translate(&a_map, a.outputs[i])?;
+            let y = translate(&b_map, b.outputs[j])?;
+            // x ^ y = !(!(x & !y) & !(!x & y))
+            let left = and(x, y ^ 1, &mut gates);
+            let right = and(x ^ 1, y, &mut gates);
+            outputs.push(and(left ^ 1, right ^ 1, &mut gates) ^ 1);
+        }
+
+        let miter = Aig {
+            max_var_index: next_code / 2 - 1,
+            inputs: a_input_codes
+                .iter()
+                .map(|&code| L::from_code(code))
+                .collect(),
+            latches: vec![],
+            outputs: outputs.into_iter().map(L::from_code).collect(),
+            bad_state_properties: vec![],
+            invariant_constraints: vec![],
+            justice_properties: vec![],
+            fairness_constraints: vec![],
+            and_gates: gates
+                .into_iter()
+                .map(|[output, x, y]| AndGate {
+                    inputs: [L::from_code(x), L::from_code(y)],
+                    output: L::from_code(output),
+                })
+                .collect(),
+            symbols: vec![],
+            comment: None,
+        };
+        let config = RenumberConfig::default()
+            .trim(true)
+            .structural_hash(true)
+            .const_fold(true);
+        let (aig, _) = Renumber::renumber_aig(config, &miter)
+            .map_err(|err| CecError::Structure(format!("{:?}", err)))?;
+        Ok(Miter {
+            aig,
+            inputs: a_inputs,
+            outputs: a_outputs,
+        })
+    }
+
+    /// Tseitin encoding of the miter, satisfiable iff some output pair differs.
+    pub fn to_cnf(&self) -> Cnf {
+        to_cnf(
+            &self.aig,
+            CnfConfig::default().assert_outputs(AssertOutputs::Any),
+        )
+    }
+
+    pub fn write_dimacs(&self, writer: &mut DeferredWriter) {
+        self.to_cnf().write(writer);
+    }
+
+    fn counterexample(
+        &self,
+        input: impl Fn(usize) -> bool,
+        output: impl Fn(usize) -> bool,
+    ) -> Counterexample {
+        Counterexample {
+            inputs: self
+                .inputs
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.clone(), input(i)))
+                .collect(),
+            outputs: self
+                .outputs
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| output(i))
+                .map(|(_, name)| name.clone())
+                .collect(),
+        }
+    }
+}
+
+pub fn cec<L: Lit>(a: &Aig<L>, b: &Aig<L>, config: &CecConfig) -> Result<CecResult, CecError> {
+    let miter = Miter::new(a, b)?;
+
+    if config.sim_words > 0 {
+        let patterns = Patterns::Random {
+            words: config.sim_words,
+            seed: config.seed,
+        };
+        let sim =
+            Simulation::new(&miter.aig, patterns).expect("random patterns have no input limit");
+        let signatures = sim.output_signatures();
+        let hit = signatures
+            .iter()
+            .flat_map(|signature| signature.iter().enumerate())
+            .find(|&(_, &word)| word != 0)
+            .map(|(w, word)| w * 64 + word.trailing_zeros() as usize);
+        if let Some(pattern) = hit {
+            let bit = |values: &[u64]| (values[pattern / 64] >> (pattern % 64)) & 1 == 1;
+            let input = |i: usize| bit(&sim.lit_values(L::from_code(2 * (i + 1))));
+            let output = |i: usize| bit(&signatures[i]);
+            return Ok(CecResult::NotEquivalent(
+                miter.counterexample(input, output),
+            ));
+        }
+    }
+
+    let cnf = miter.to_cnf();
+    let mut solver = Solver::new();
+    for clause in &cnf.clauses {
+        solver.add_clause(clause);
+    }
+    Ok(match solver.solve(config.conflict_limit) {
+        SatResult::Unsat => CecResult::Equivalent,
+        SatResult::Unknown => CecResult::Undecided,
+        SatResult::Sat => {
+            let value = |lit: i32| {
+                let value = lit.abs() <= solver.var_count() as i32 && solver.model_value(lit.abs());
+                value == (lit > 0)
+            };
+            let input = |i: usize| value(cnf.var_map.input_var(i));
+            let output = |i: usize| value(cnf.var_map.lit(miter.aig.outputs[i]));
+            CecResult::NotEquivalent(miter.counterexample(input, output))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aig::Symbol;
+    use std::borrow::Cow;
+
+    fn named(inputs: &[&str], outputs: &[&str]) -> Vec<Symbol<'static>> {
+        let inputs = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (SymbolTarget::Input(i), name));
+        let outputs = outputs
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (SymbolTarget::Output(i), name));
+        inputs
+            .chain(outputs)
+            .map(|(target, name)| Symbol {
+                target,
+                name: Cow::Owned(name.to_string()),
+            })
+            .collect()
+    }
+
+    // !(!a & !b) with inputs a, b, or the same gate over the swapped inputs b, a
+    fn or2(output: u32, swapped: bool) -> Aig<u32> {
+        let mut aig = Aig {
+            max_var_index: 3,
+            inputs: vec![2, 4],
+            latches: vec![],
+            outputs: vec![output],
+            bad_state_properties: vec![],
+            invariant_constraints: vec![],
+            justice_properties: vec![],
+            fairness_constraints: vec![],
+            and_gates: vec![AndGate {
+                inputs: [3, 5],
+                output: 6,
+            }],
+            symbols: named(&["a", "b"], &["y"]),
+            comment: None,
+        };
+        if swapped {
+            aig.symbols = named(&["b", "a"], &["y"]);
+        }
+        aig
+    }
+
+    #[test]
+    fn equivalent_up_to_input_order() {
+        let result = cec(&or2(7, false), &or2(7, true), &CecConfig::default()).unwrap();
+        assert_eq!(result, CecResult::Equivalent);
+    }
+
+    #[test]
+    fn solver_finds_counterexample_without_simulation() {
+        let config = CecConfig {
+            sim_words: 0,
+            ..CecConfig::default()
+        };
+        let CecResult::NotEquivalent(cex) = cec(&or2(7, false), &or2(6, false), &config).unwrap()
+        else {
+            panic!("expected a counterexample");
+        };
+        // !(!a & !b) and !a & !b differ everywhere
+        assert_eq!(cex.outputs, vec!["y".to_string()]);
+        assert_eq!(cex.inputs.len(), 2);
+    }
+
+    #[test]
+    fn miter_dimacs_has_header() {
+        let miter = Miter::new(&or2(7, false), &or2(6, false)).unwrap();
+        let mut buf = vec![];
+        let mut writer = DeferredWriter::from_write(&mut buf);
+        miter.write_dimacs(&mut writer);
+        writer.flush_defer_err();
+        writer.check_io_error().unwrap();
+        drop(writer);
+        let text = String::from_utf8(buf).unwrap();
+        let cnf = miter.to_cnf();
+        assert!(text.starts_with(&format!("p cnf {} {}\n", cnf.var_count, cnf.clauses.len())));
+    }
+
+    #[test]
+    fn undefined_variable_is_an_error() {
+        // The gate reads variable 4, which the AIG never defines
+        let mut broken = or2(7, false);
+        broken.inputs = vec![2];
+        broken.symbols = named(&["a"], &["y"]);
+        let mut reference = or2(7, false);
+        reference.and_gates[0].inputs = [3, 3];
+        reference.inputs = vec![2];
+        reference.symbols = named(&["a"], &["y"]);
+        assert!(matches!(
+            cec(&broken, &reference, &CecConfig::default()),
+            Err(CecError::Structure(_))
+        ));
+    }
+}
@@ -0,0 +1,292 @@
+//! K-feasible priority cut enumeration on an [`OrderedAig`].
+//!
+//! A cut of a node is a set of leaves such that every path from an input or latch
+//! to the node passes through a leaf. Cuts are computed bottom-up by merging the
+//! cuts of both gate inputs, dropping cuts larger than K or dominated by a subset,
+//! and keeping only the best ones per node. Cuts of up to six leaves carry their
+//! truth table.
+use crate::{aig::OrderedAig, Lit};
+
+/// Largest cut that still gets a truth table.
+pub const MAX_TRUTH_LEAVES: usize = 6;
+
+// Truth tables of the six leaf variables
+const VAR_TRUTH: [u64; MAX_TRUTH_LEAVES] = [
+    0xaaaa_aaaa_aaaa_aaaa,
+    0xcccc_cccc_cccc_cccc,
+    0xf0f0_f0f0_f0f0_f0f0,
+    0xff00_ff00_ff00_ff00,
+    0xffff_0000_ffff_0000,
+    0xffff_ffff_0000_0000,
+];
+
+#[non_exhaustive]
+pub struct CutConfig {
+    /// Largest number of leaves of a cut, the K of K-feasible.
+    pub max_size: usize,
+    /// Cuts kept per node, not counting the node's trivial cut.
+    pub max_cuts: usize,
+}
+
+impl Default for CutConfig {
+    fn default() -> Self {
+        CutConfig {
+            max_size: 4,
+            max_cuts: 8,
+        }
+    }
+}
+
+impl CutConfig {
+    pub fn max_size(mut self, value: usize) -> Self {
+        self.max_size = value;
+        self
+    }
+
+    pub fn max_cuts(mut self, value: usize) -> Self {
+        self.max_cuts = value;
+        self
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Cut {
+    /// Sorted variable indices of the leaves.
+    pub leaves: Vec<usize>,
+    /// Function of the node over the leaves, leaf `i` being variable `i`, for cuts
+    /// of up to [`MAX_TRUTH_LEAVES`] leaves.
+    pub truth: Option<u64>,
+}
+
+impl Cut {
+    fn trivial(var: usize) -> Self {
+        Cut {
+            leaves: vec![var],
+            truth: Some(VAR_TRUTH[0]),
+        }
+    }
+
+    /// Whether every leaf of `self` is a leaf of `other`.
+    pub fn dominates(&self, other: &Cut) -> bool {
+        self.leaves.len() <= other.leaves.len()
+            && self
+                .leaves
+                .iter()
+                .all(|leaf| other.leaves.binary_search(leaf).is_ok())
+    }
+}
+
+// Re-expresses a truth table over `from` in terms of the superset `to`
+fn stretch(truth: u64, from: &[usize], to: &[usize]) -> u64 {
+    let positions: Vec<usize> = from
+        .iter()
+        .map(|leaf| to.binary_search(leaf).unwrap())
+        .collect();
+    let mut result = 0;
+    for minterm in 0..64 {
+        let mut index = 0;
+        for (i, &position) in positions.iter().enumerate() {
+            index |= ((minterm >> position) & 1) << i;
+        }
+        result |= ((truth >> index) & 1) << minterm;
+    }
+    result
+}
+
+// Sorted union of two leaf sets, `None` when larger than `max_size`
+fn merge_leaves(a: &[usize], b: &[usize], max_size: usize) -> Option<Vec<usize>> {
+    let mut leaves = Vec::with_capacity(max_size);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let next = match (a.get(i), b.get(j)) {
+            (Some(&x), Some(&y)) if x == y => {
+                i += 1;
+                j += 1;
+                x
+            }
+            (Some(&x), Some(&y)) if x < y => {
+                i += 1;
+                x
+            }
+            (Some(&x), None) => {
+                i += 1;
+                x
+            }
+            (_, Some(&y)) => {
+                j += 1;
+                y
+            }
+            (None, None) => unreachable!(),
+        };
+        if leaves.len() == max_size {
+            return None;
+        }
+        leaves.push(next);
+    }
+    Some(leaves)
+}
+
+/// Cuts of every variable of an AIG, indexed by variable.
+pub struct Cuts {
+    cuts: Vec<Vec<Cut>>,
+}
+
+impl Cuts {
+    /// Cuts of variable `var`, best first. The trivial cut of an AND gate comes
+    /// last.
+    pub fn of(&self, var: usize) -> &[Cut] {
+        &self.cuts[var]
+    }
+
+    pub fn len(&self) -> usize {
+        self.cuts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cuts.is_empty()
+    }
+}
+
+/// Enumerates priority cuts of every node of `aig`. Cuts are ranked by their
+/// number of leaves, and by their leaves among cuts of the same size.
+pub fn enumerate_cuts<L: Lit>(aig: &OrderedAig<L>, config: &CutConfig) -> Cuts {
+    let first_gate = aig.input_count + aig.latches.len() + 1;
+
+    let mut cuts: Vec<Vec<Cut>> = Vec::with_capacity(first_gate + aig.and_gates.len());
+    cuts.push(vec![Cut {
+        leaves: vec![],
+        truth: Some(0),
+    }]);
+    cuts.extend((1..first_gate).map(|var| vec![Cut::trivial(var)]));
+
+    for (k, gate) in aig.and_gates.iter().enumerate() {
+        let var = first_gate + k;
+        let [a, b] = gate.inputs;
+        let mut node_cuts: Vec<Cut> = vec![];
+        for cut_a in &cuts[a.code() >> 1] {
+            for cut_b in &cuts[b.code() >> 1] {
+                let Some(leaves) = merge_leaves(&cut_a.leaves, &cut_b.leaves, config.max_size)
+                else {
+                    continue;
+                };
+                let mut cut = Cut {
+                    leaves,
+                    truth: None,
+                };
+                if node_cuts.iter().any(|other| other.dominates(&cut)) {
+                    continue;
+                }
+                if let (true, Some(truth_a), Some(truth_b)) = (
+                    cut.leaves.len() <= MAX_TRUTH_LEAVES,
+                    cut_a.truth,
+                    cut_b.truth,
+                ) {
+                    let input_truth = |truth: u64, input: &Cut, lit: L| {
+                        let truth = stretch(truth, &input.leaves, &cut.leaves);
+                        if lit.code() & 1 == 1 {
+                            !truth
+                        } else {
+                            truth
+                        }
+                    };
+                    cut.truth =
+                        Some(input_truth(truth_a, cut_a, a) & input_truth(truth_b, cut_b, b));
+                }
+                node_cuts.retain(|other| !cut.dominates(other));
+                node_cuts.push(cut);
+            }
+        }
+        node_cuts.sort_by(|x, y| {
+            x.leaves
+                .len()
+                .cmp(&y.leaves.len())
+                .then_with(|| x.leaves.cmp(&y.leaves))
+        });
+        node_cuts.truncate(config.max_cuts);
+        node_cuts.push(Cut::trivial(var));
+        cuts.push(node_cuts);
+    }
+    Cuts { cuts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aig::OrderedAndGate;
+
+    fn leaves(cuts: &[Cut]) -> Vec<Vec<usize>> {
+        cuts.iter().map(|cut| cut.leaves.clone()).collect()
+    }
+
+    #[test]
+    fn three_input_and() {
+        // g4 = a & b, g5 = g4 & c
+        let aig = OrderedAig {
+            max_var_index: 5,
+            input_count: 3,
+            and_gates: vec![
+                OrderedAndGate { inputs: [4, 2] },
+                OrderedAndGate { inputs: [8, 6] },
+            ],
+            outputs: vec![10],
+            ..OrderedAig::<u32>::default()
+        };
+        let cuts = enumerate_cuts(&aig, &CutConfig::default());
+        assert_eq!(leaves(cuts.of(5)), vec![vec![3, 4], vec![1, 2, 3], vec![5]]);
+        assert_eq!(cuts.of(5)[1].truth, Some(0x8080_8080_8080_8080));
+
+        let cuts = enumerate_cuts(&aig, &CutConfig::default().max_size(2));
+        assert_eq!(leaves(cuts.of(5)), vec![vec![3, 4], vec![5]]);
+    }
+
+    #[test]
+    fn dominated_cuts_are_dropped() {
+        // g3 = a & b, g4 = g3 & !a, g5 = g4 & b
+        let aig = OrderedAig {
+            max_var_index: 5,
+            input_count: 2,
+            and_gates: vec![
+                OrderedAndGate { inputs: [4, 2] },
+                OrderedAndGate { inputs: [6, 3] },
+                OrderedAndGate { inputs: [8, 4] },
+            ],
+            outputs: vec![10],
+            ..OrderedAig::<u32>::default()
+        };
+        let cuts = enumerate_cuts(&aig, &CutConfig::default());
+        // {a, b, g3} is dominated by {a, b}
+        assert_eq!(leaves(cuts.of(5)), vec![vec![1, 2], vec![2, 4], vec![5]]);
+        assert_eq!(cuts.of(5)[0].truth, Some(0));
+        assert_eq!(cuts.of(4)[0].leaves, vec![1, 2]);
+        assert_eq!(cuts.of(4)[0].truth, Some(0));
+    }
+
+    #[test]
+    fn small_cuts_keep_truth_beyond_six_leaves() {
+        // AND chain over eight inputs, g9 = a & b, g10 = g9 & c, ..., g15 = g14 & h
+        let aig = OrderedAig {
+            max_var_index: 15,
+            input_count: 8,
+            and_gates: (0..7)
+                .map(|k| OrderedAndGate {
+                    inputs: [
+                        if k == 0 { 4 } else { 16 + 2 * k },
+                        if k == 0 { 2 } else { 4 + 2 * k },
+                    ],
+                })
+                .collect(),
+            outputs: vec![30],
+            ..OrderedAig::<u32>::default()
+        };
+        let cuts = enumerate_cuts(&aig, &CutConfig::default().max_size(8));
+        let top = cuts.of(15);
+        assert_eq!(top.len(), 8);
+        assert_eq!(top[0].leaves, vec![8, 14]);
+        assert_eq!(top[0].truth, Some(0x8888_8888_8888_8888));
+        assert_eq!(top[4].leaves, vec![4, 5, 6, 7, 8, 10]);
+        assert_eq!(top[4].truth, Some(0x8000_0000_0000_0000));
+        assert_eq!(top[5].truth, None);
+        assert_eq!(top[6].leaves, (1..=8).collect::<Vec<_>>());
+        assert_eq!(top[6].truth, None);
+    }
+}
@@ -6,5 +6,14 @@ mod lit;
 mod token;
 pub use error::{Error, InnerParseError, ParseError};
 pub use lit::Lit;
+pub mod balance;
+pub mod bitblast;
+pub mod cec;
+pub mod cuts;
+pub mod mffc;
+pub mod replace;
+pub mod sat;
+pub mod simulate;
+pub mod stats;
 pub mod traversal;
 
@@ -14,8 +14,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     #[cfg(feature = "egraph2aig")]
     {
-        if args.len() != 4 {
-            eprintln!("Usage: program <output_file1> <output_file2> <input_egraph_path>");
+        if args.len() != 4 && args.len() != 5 {
+            eprintln!("Usage: program <output_file1> <output_file2> <input_egraph_path> [latch_map_json]");
             std::process::exit(1);
         }
         let output_file_path1 = &args[1];
@@ -26,7 +26,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let mut json_str = String::new();
         file.read_to_string(&mut json_str).expect("Unable to read file");
 
-        let graph = parse_json_sd(&json_str);
+        let mut graph = parse_json_sd(&json_str);
+        graph.lower_xmg_ops();
 
         let input_vec = generate_input_vec(&graph);
         let mut graph_reorder = graph.reorder(input_vec.clone());
@@ -39,7 +40,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let mut filtered_nodes: Vec<_> = graph_reorder.nodes.iter().collect();
         filtered_nodes.sort_by_key(|(_, node)| node.order.unwrap_or_default());
 
-        let aig: flussab_aiger::aig::Aig<u32> = graph_reorder.to_aig();
+        let mut aig: flussab_aiger::aig::Aig<u32> = graph_reorder.to_aig();
+        if let Some(latch_map_path) = args.get(4) {
+            let latch_map = LatchMap::from_file(std::path::Path::new(latch_map_path))?;
+            aig.restore_latches(&latch_map)?;
+        }
 
         let config = RenumberConfig::default()
             .trim(true)
@@ -60,9 +65,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     #[cfg(feature = "extract_cone")]
     {
-        if args.len() != 5 {
+        if args.len() != 5 && args.len() != 6 {
             eprintln!(
-                "Usage: program <output_file1> <output_file2> <input_aig_path> <gate_output>"
+                "Usage: program <output_file1> <output_file2> <input_aig_path> <gate_output>[,<gate_output>...] [<boundary_lit>,...]"
             );
             std::process::exit(1);
         }
@@ -70,15 +75,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         let output_file_path1 = &args[1];
         let output_file_path2 = &args[2];
         let input_file_path = &args[3];
-        let gate_output: u32 = args[4]
-            .parse()
-            .expect("Invalid gate_output, must be a valid unsigned integer");
+        let parse_lits = |arg: &str| -> Vec<u32> {
+            arg.split(',')
+                .filter(|lit| !lit.is_empty())
+                .map(|lit| lit.parse().expect("Invalid literal, must be a valid unsigned integer"))
+                .collect()
+        };
+        let gate_outputs = parse_lits(&args[4]);
+        let boundary = args.get(5).map_or(vec![], |arg| parse_lits(arg));
 
         let file = File::open(input_file_path)?;
         let aig_reader = binary::Parser::<u32>::from_read(file, binary::Config::default())?;
         let ordered_aig = aig_reader.parse()?;
         let aig = Aig::from(ordered_aig);
-        let aig: flussab_aiger::aig::Aig<u32> = aig.extract_cone(gate_output);
+        let cone = flussab_aiger::aig::cone::ConeExtractor::new(&aig).extract(&gate_outputs, &boundary);
+        let aig = cone.aig;
 
         let config = RenumberConfig::default()
             .trim(true)
@@ -97,6 +108,167 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         binary_writer.write_ordered_aig(&aig_order);
     }
 
+    #[cfg(feature = "stats")]
+    {
+        if args.len() != 2 && !(args.len() == 3 && args[2] == "--json") {
+            eprintln!("Usage: program <input_aig_path> [--json]");
+            std::process::exit(1);
+        }
+
+        // Binary AIGER unless the file ends in .aag
+        let input_file_path = &args[1];
+        let file = File::open(input_file_path)?;
+        let ordered_aig = if input_file_path.ends_with(".aag") {
+            let aig = ascii::Parser::<u32>::from_read(file, ascii::Config::default())?.parse()?;
+            Renumber::renumber_aig(RenumberConfig::default(), &aig)?.0
+        } else {
+            binary::Parser::<u32>::from_read(file, binary::Config::default())?.parse()?
+        };
+
+        let stats = flussab_aiger::stats::stats(&ordered_aig);
+        if args.len() == 3 {
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        } else {
+            println!("{}", stats);
+        }
+    }
+
+    #[cfg(feature = "mffc")]
+    {
+        if args.len() < 3 {
+            eprintln!("Usage: program <input_aig_path> <output_dir> [-N min_size] [-L max_size] [-S max_partitions] [-C]");
+            std::process::exit(1);
+        }
+
+        let mut config = flussab_aiger::mffc::MffcConfig::default();
+        let mut options = args[3..].iter();
+        while let Some(option) = options.next() {
+            let mut value = || -> Result<usize, Box<dyn std::error::Error>> {
+                let value = options.next().ok_or_else(|| format!("{} expects a value", option))?;
+                Ok(value.parse()?)
+            };
+            match option.as_str() {
+                "-N" => config.min_size = value()?,
+                "-L" => config.max_size = Some(value()?),
+                "-S" => config.max_partitions = Some(value()?),
+                "-C" => config.critical_only = true,
+                _ => return Err(format!("Unknown option {}", option).into()),
+            }
+        }
+
+        let file = File::open(&args[1])?;
+        let aig = Aig::from(binary::Parser::<u32>::from_read(file, binary::Config::default())?.parse()?);
+        let mffcs = flussab_aiger::mffc::mffcs(&aig, &config);
+        let boundaries = flussab_aiger::mffc::write_partitions(&aig, &mffcs, std::path::Path::new(&args[2]))?;
+        println!("{} partitions written to {}", boundaries.len(), args[2]);
+    }
+
+    #[cfg(feature = "replace")]
+    {
+        if args.len() != 5 {
+            eprintln!("Usage: program <original_aig_path> <partition_dir> <optimized_dir> <output_aig_path>");
+            std::process::exit(1);
+        }
+        use flussab_aiger::cec::{CecConfig, CecResult};
+        use flussab_aiger::mffc::PartitionBoundary;
+        use flussab_aiger::replace::{replace_subcircuits, GateIndex, Replacement};
+        use std::path::Path;
+
+        let file = File::open(&args[1])?;
+        let aig = Aig::from(binary::Parser::<u32>::from_read(file, binary::Config::default())?.parse()?);
+        let map = File::open(Path::new(&args[2]).join("map.json"))?;
+        let boundaries: Vec<PartitionBoundary> = serde_json::from_reader(map)?;
+        let index = GateIndex::new(&aig);
+
+        // Every optimized partition is proven equivalent before it is spliced in
+        let mut replacements = vec![];
+        for boundary in boundaries {
+            let path = Path::new(&args[3]).join(&boundary.file);
+            let Ok(file) = File::open(&path) else {
+                println!("{}: no optimized partition, keeping the original", boundary.file);
+                continue;
+            };
+            let optimized = Aig::from(binary::Parser::<u32>::from_read(file, binary::Config::default())?.parse()?);
+            let replacement = Replacement {
+                root: boundary.root as u32,
+                leaves: boundary.leaves.iter().map(|&leaf| leaf as u32).collect(),
+                aig: optimized,
+            };
+            match replacement.check(&index, &CecConfig::default()) {
+                Ok(CecResult::Equivalent) => replacements.push(replacement),
+                Ok(result) => println!("{}: not proven equivalent ({:?}), keeping the original", boundary.file, result),
+                Err(err) => println!("{}: {}, keeping the original", boundary.file, err),
+            }
+        }
+
+        let aig_order = replace_subcircuits(&index, &replacements)?;
+        println!(
+            "{} partitions replaced, {} and gates",
+            replacements.len(),
+            aig_order.and_gates.len()
+        );
+        let output_file = File::create(&args[4])?;
+        let mut binary_writer = binary::Writer::<u32>::new(DeferredWriter::from_write(&output_file));
+        binary_writer.write_ordered_aig(&aig_order);
+        binary_writer.flush_defer_err();
+        binary_writer.check_io_error()?;
+    }
+
+    #[cfg(feature = "balance")]
+    {
+        if args.len() != 3 {
+            eprintln!("Usage: program <input_aig_path> <output_aig_path>");
+            std::process::exit(1);
+        }
+
+        let file = File::open(&args[1])?;
+        let ordered_aig = binary::Parser::<u32>::from_read(file, binary::Config::default())?.parse()?;
+        let before = flussab_aiger::stats::stats(&ordered_aig);
+        let aig_order = flussab_aiger::balance::balance(&Aig::from(ordered_aig))?;
+        let after = flussab_aiger::stats::stats(&aig_order);
+        println!(
+            "and = {} -> {}  lev = {} -> {}",
+            before.and_gates, after.and_gates, before.levels, after.levels
+        );
+
+        let output_file = File::create(&args[2])?;
+        let mut binary_writer = binary::Writer::<u32>::new(DeferredWriter::from_write(&output_file));
+        binary_writer.write_ordered_aig(&aig_order);
+        binary_writer.flush_defer_err();
+        binary_writer.check_io_error()?;
+    }
+
+    #[cfg(feature = "btor2aig")]
+    {
+        if args.len() != 3 {
+            eprintln!("Usage: program <input_btor2_path> <output_aig_path>");
+            std::process::exit(1);
+        }
+
+        let file = File::open(&args[1])?;
+        let mut parser = flussab_btor2::Parser::from_read(file, flussab_btor2::Config::default())?;
+        let aig = flussab_aiger::bitblast::bitblast::<u32>(&mut parser)?;
+
+        let config = RenumberConfig::default()
+            .trim(true)
+            .structural_hash(true)
+            .const_fold(true);
+        let (aig_order, _renumber) = Renumber::renumber_aig(config, &aig)?;
+        println!(
+            "i/o = {}/{}  lat = {}  and = {}",
+            aig_order.input_count,
+            aig_order.outputs.len(),
+            aig_order.latches.len(),
+            aig_order.and_gates.len()
+        );
+
+        let output_file = File::create(&args[2])?;
+        let mut binary_writer = binary::Writer::<u32>::new(DeferredWriter::from_write(&output_file));
+        binary_writer.write_ordered_aig(&aig_order);
+        binary_writer.flush_defer_err();
+        binary_writer.check_io_error()?;
+    }
+
     Ok(())
 }
 
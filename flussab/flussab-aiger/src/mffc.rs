@@ -0,0 +1,439 @@
+//! Maximum fanout-free cone partitioning, the native counterpart of the patched
+//! ABC `write_mffc` and `write_mffc_a` commands.
+//!
+//! The MFFC of an AND gate is the gate together with every gate whose fanouts all
+//! end up inside the cone, so it can be replaced without touching the rest of the
+//! AIG. Its leaves are the inputs, latches and shared gates feeding it.
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::Path,
+};
+
+use anyhow::Context;
+use flussab::DeferredWriter;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    aig::{Aig, AndGate, Renumber, RenumberConfig, Symbol, SymbolTarget},
+    binary, Lit,
+};
+
+pub struct MffcConfig {
+    /// Smallest cone to keep, in AND gates (`-N`).
+    pub min_size: usize,
+    /// Largest cone to keep (`-L`).
+    pub max_size: Option<usize>,
+    /// Keep only this many cones, the last ones in topological order (`-S`).
+    pub max_partitions: Option<usize>,
+    /// Only consider gates on a longest path (`-C`).
+    pub critical_only: bool,
+}
+
+impl Default for MffcConfig {
+    fn default() -> Self {
+        MffcConfig {
+            min_size: 10,
+            max_size: None,
+            max_partitions: Some(10),
+            critical_only: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mffc<L> {
+    /// Output literal of the root gate.
+    pub root: L,
+    /// Output literals of the cone's gates, the root first.
+    pub gates: Vec<L>,
+    /// Sorted literals feeding the cone.
+    pub leaves: Vec<L>,
+}
+
+/// Boundary of a partition file, as written to `map.json`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct PartitionBoundary {
+    pub file: String,
+    /// Literal code of the root gate in the partitioned AIG.
+    pub root: usize,
+    /// Literal codes of the partition's inputs, in input order.
+    pub leaves: Vec<usize>,
+    pub gates: usize,
+}
+
+struct Network {
+    // Input codes of every gate, by variable
+    gates: HashMap<usize, [usize; 2]>,
+    // Gate variables, children first
+    order: Vec<usize>,
+    refs: HashMap<usize, usize>,
+}
+
+impl Network {
+    fn new<L: Lit>(aig: &Aig<L>) -> Self {
+        let gates: HashMap<usize, [usize; 2]> = aig
+            .and_gates
+            .iter()
+            .map(|gate| (gate.output.code() >> 1, gate.inputs.map(|lit| lit.code())))
+            .collect();
+
+        let mut refs: HashMap<usize, usize> = HashMap::new();
+        let sinks = aig
+            .outputs
+            .iter()
+            .chain(aig.latches.iter().map(|latch| &latch.next_state))
+            .chain(&aig.bad_state_properties)
+            .chain(&aig.invariant_constraints)
+            .chain(&aig.fairness_constraints)
+            .chain(aig.justice_properties.iter().flatten());
+        for lit in sinks {
+            *refs.entry(lit.code() >> 1).or_default() += 1;
+        }
+        for inputs in gates.values() {
+            for code in inputs {
+                *refs.entry(code >> 1).or_default() += 1;
+            }
+        }
+
+        // Post-order without recursion, AIGs can be deep
+        let mut order = Vec::with_capacity(gates.len());
+        let mut done = HashSet::new();
+        for gate in &aig.and_gates {
+            let mut stack = vec![(gate.output.code() >> 1, false)];
+            while let Some((var, expanded)) = stack.pop() {
+                if done.contains(&var) {
+                    continue;
+                }
+                if expanded {
+                    done.insert(var);
+                    order.push(var);
+                    continue;
+                }
+                stack.push((var, true));
+                for code in gates[&var] {
+                    if gates.contains_key(&(code >> 1)) && !done.contains(&(code >> 1)) {
+                        stack.push((code >> 1, false));
+                    }
+                }
+            }
+        }
+        Network { gates, order, refs }
+    }
+
+    // Gates (root first) and leaves of the MFFC of `root`
+    fn cone(&mut self, root: usize) -> (Vec<usize>, Vec<usize>) {
+        let mut cone = vec![root];
+        let mut touched = vec![];
+        let mut stack = vec![root];
+        while let Some(var) = stack.pop() {
+            for code in self.gates[&var] {
+                let child = code >> 1;
+                if child == 0 {
+                    continue;
+                }
+                let refs = self.refs.get_mut(&child).unwrap();
+                *refs -= 1;
+                touched.push(child);
+                if *refs == 0 && self.gates.contains_key(&child) {
+                    cone.push(child);
+                    stack.push(child);
+                }
+            }
+        }
+        for child in &touched {
+            *self.refs.get_mut(child).unwrap() += 1;
+        }
+
+        let inside: HashSet<usize> = cone.iter().copied().collect();
+        let mut leaves: Vec<usize> = touched
+            .into_iter()
+            .filter(|var| !inside.contains(var))
+            .collect();
+        leaves.sort_unstable();
+        leaves.dedup();
+        (cone, leaves)
+    }
+
+    // Gates on a longest path from a leaf to a sink
+    fn critical<L: Lit>(&self, aig: &Aig<L>) -> HashSet<usize> {
+        let level_of = |level: &HashMap<usize, usize>, code: usize| {
+            level.get(&(code >> 1)).copied().unwrap_or(0)
+        };
+        let mut level: HashMap<usize, usize> = HashMap::new();
+        for &var in &self.order {
+            let [a, b] = self.gates[&var];
+            level.insert(var, 1 + level_of(&level, a).max(level_of(&level, b)));
+        }
+        let depth = aig
+            .outputs
+            .iter()
+            .map(|lit| level_of(&level, lit.code()))
+            .max()
+            .unwrap_or(0);
+
+        // Longest path from each gate to an output, counting the gates after it
+        let mut required: HashMap<usize, usize> = HashMap::new();
+        for lit in &aig.outputs {
+            required.entry(lit.code() >> 1).or_insert(0);
+        }
+        for &var in self.order.iter().rev() {
+            let Some(&after) = required.get(&var) else {
+                continue;
+            };
+            for code in self.gates[&var] {
+                let entry = required.entry(code >> 1).or_insert(0);
+                *entry = (*entry).max(after + 1);
+            }
+        }
+        self.order
+            .iter()
+            .copied()
+            .filter(|var| {
+                required
+                    .get(var)
+                    .is_some_and(|after| level[var] + after == depth)
+            })
+            .collect()
+    }
+}
+
+/// Disjoint MFFCs of the gates of `aig` selected by `config`, in topological
+/// order of their roots.
+///
+/// Roots are visited from the outputs towards the inputs, and a gate inside an
+/// accepted cone is never a root itself. Since two MFFCs are either nested or
+/// disjoint, the cones returned never share a gate.
+pub fn mffcs<L: Lit>(aig: &Aig<L>, config: &MffcConfig) -> Vec<Mffc<L>> {
+    let mut network = Network::new(aig);
+    let critical = config.critical_only.then(|| network.critical(aig));
+
+    let mut covered: HashSet<usize> = HashSet::new();
+    let mut result = vec![];
+    for var in network.order.clone().into_iter().rev() {
+        if config.max_partitions.is_some_and(|max| result.len() == max) {
+            break;
+        }
+        if covered.contains(&var)
+            || critical
+                .as_ref()
+                .is_some_and(|critical| !critical.contains(&var))
+        {
+            continue;
+        }
+        let (gates, leaves) = network.cone(var);
+        if leaves.len() < 2
+            || gates.len() < config.min_size
+            || config.max_size.is_some_and(|max| gates.len() > max)
+        {
+            continue;
+        }
+        covered.extend(gates.iter().copied());
+        result.push(Mffc {
+            root: L::from_code(var * 2),
+            gates: gates.into_iter().map(|var| L::from_code(var * 2)).collect(),
+            leaves: leaves
+                .into_iter()
+                .map(|var| L::from_code(var * 2))
+                .collect(),
+        });
+    }
+    result.reverse();
+    result
+}
+
+impl<L: Lit> Mffc<L> {
+    /// The cone as a standalone AIG, with inputs and output named `n<code>` after
+    /// their literals in `aig`.
+    pub fn to_aig(&self, aig: &Aig<L>) -> Aig<L> {
+        let inside: HashSet<usize> = self.gates.iter().map(|lit| lit.code()).collect();
+        let and_gates: Vec<AndGate<L>> = aig
+            .and_gates
+            .iter()
+            .filter(|gate| inside.contains(&gate.output.code()))
+            .copied()
+            .collect();
+        let mut symbols: Vec<Symbol<'static>> = self
+            .leaves
+            .iter()
+            .enumerate()
+            .map(|(i, lit)| Symbol {
+                target: SymbolTarget::Input(i),
+                name: format!("n{}", lit.code()).into(),
+            })
+            .collect();
+        symbols.push(Symbol {
+            target: SymbolTarget::Output(0),
+            name: format!("n{}", self.root.code()).into(),
+        });
+
+        Aig {
+            max_var_index: aig.max_var_index,
+            inputs: self.leaves.clone(),
+            latches: vec![],
+            outputs: vec![self.root],
+            bad_state_properties: vec![],
+            invariant_constraints: vec![],
+            justice_properties: vec![],
+            fairness_constraints: vec![],
+            and_gates,
+            symbols,
+            comment: None,
+        }
+    }
+}
+
+/// Writes every MFFC to `<dir>/<n>.aig`, numbered from 1, and their boundaries
+/// to `<dir>/map.json`.
+pub fn write_partitions<L: Lit>(
+    aig: &Aig<L>,
+    mffcs: &[Mffc<L>],
+    dir: &Path,
+) -> anyhow::Result<Vec<PartitionBoundary>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let mut boundaries = Vec::with_capacity(mffcs.len());
+    for (index, mffc) in mffcs.iter().enumerate() {
+        let file = format!("{}.aig", index + 1);
+        let config = RenumberConfig::default().trim(true);
+        let (ordered, _) = Renumber::renumber_aig(config, &mffc.to_aig(aig))
+            .map_err(|err| anyhow::anyhow!("Failed to renumber partition {}: {:?}", file, err))?;
+
+        let path = dir.join(&file);
+        let output =
+            File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut writer = binary::Writer::<L>::new(DeferredWriter::from_write(output));
+        writer.write_ordered_aig(&ordered);
+        writer.flush_defer_err();
+        writer.check_io_error()?;
+
+        boundaries.push(PartitionBoundary {
+            file,
+            root: mffc.root.code(),
+            leaves: mffc.leaves.iter().map(|lit| lit.code()).collect(),
+            gates: mffc.gates.len(),
+        });
+    }
+    let map = File::create(dir.join("map.json"))?;
+    serde_json::to_writer_pretty(map, &boundaries)?;
+    Ok(boundaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aig(and_gates: &[(u32, u32, u32)], outputs: Vec<u32>) -> Aig<u32> {
+        Aig {
+            max_var_index: 10,
+            inputs: vec![2, 4, 6],
+            latches: vec![],
+            outputs,
+            bad_state_properties: vec![],
+            invariant_constraints: vec![],
+            justice_properties: vec![],
+            fairness_constraints: vec![],
+            and_gates: and_gates
+                .iter()
+                .map(|&(output, a, b)| AndGate {
+                    inputs: [a, b],
+                    output,
+                })
+                .collect(),
+            symbols: vec![],
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn shared_gate_is_a_leaf() {
+        // g8 = a & b is shared by g10 and the second output, g12 only feeds g14
+        let aig = aig(
+            &[(8, 2, 4), (10, 8, 6), (12, 3, 7), (14, 10, 12)],
+            vec![14, 8],
+        );
+        let config = MffcConfig {
+            min_size: 1,
+            max_partitions: None,
+            ..MffcConfig::default()
+        };
+        let cones = mffcs(&aig, &config);
+        let roots: Vec<u32> = cones.iter().map(|mffc| mffc.root).collect();
+        assert_eq!(roots, vec![8, 14]);
+        let top = &cones[1];
+        let mut gates = top.gates.clone();
+        gates.sort_unstable();
+        assert_eq!(gates, vec![10, 12, 14]);
+        assert_eq!(top.leaves, vec![2, 6, 8]);
+
+        let part = top.to_aig(&aig);
+        assert_eq!(part.and_gates.len(), 3);
+        assert_eq!(part.inputs, vec![2, 6, 8]);
+    }
+
+    #[test]
+    fn size_bounds_and_critical_filter() {
+        let aig = aig(
+            &[(8, 2, 4), (10, 8, 6), (12, 3, 7), (14, 10, 12)],
+            vec![14, 8],
+        );
+        let config = MffcConfig {
+            min_size: 2,
+            max_size: Some(2),
+            max_partitions: None,
+            critical_only: false,
+        };
+        assert!(mffcs(&aig, &config).is_empty());
+
+        // g14's cone is too large, so the gates below it become roots
+        let config = MffcConfig {
+            min_size: 1,
+            max_size: Some(1),
+            max_partitions: None,
+            critical_only: false,
+        };
+        let roots: Vec<u32> = mffcs(&aig, &config).iter().map(|mffc| mffc.root).collect();
+        assert_eq!(roots, vec![8, 10, 12]);
+
+        // The critical path is a, b -> g8 -> g10 -> g14, g12 has slack
+        let config = MffcConfig {
+            critical_only: true,
+            ..config
+        };
+        let roots: Vec<u32> = mffcs(&aig, &config).iter().map(|mffc| mffc.root).collect();
+        assert_eq!(roots, vec![8, 10]);
+    }
+
+    #[test]
+    fn partitions_are_disjoint() {
+        // g8 = a & b, g10 = g8 & c, g12 = g10 & !a, g14 = g12 & !c, all single fanout
+        let aig = aig(&[(8, 2, 4), (10, 8, 6), (12, 10, 3), (14, 12, 7)], vec![14]);
+        let config = MffcConfig {
+            min_size: 1,
+            max_partitions: None,
+            ..MffcConfig::default()
+        };
+        let cones = mffcs(&aig, &config);
+        assert_eq!(cones.len(), 1);
+        assert_eq!(cones[0].root, 14);
+        assert_eq!(cones[0].gates.len(), 4);
+
+        // With g14's cone too large, g12's cone covers g10 and g8
+        let config = MffcConfig {
+            max_size: Some(3),
+            ..config
+        };
+        let cones = mffcs(&aig, &config);
+        assert_eq!(cones.len(), 1);
+        assert_eq!(cones[0].root, 12);
+
+        // Only the last partitions in topological order are kept
+        let aig = self::aig(&[(8, 2, 4), (10, 4, 6), (12, 2, 6)], vec![8, 10, 12]);
+        let config = MffcConfig {
+            min_size: 1,
+            max_partitions: Some(2),
+            ..MffcConfig::default()
+        };
+        let roots: Vec<u32> = mffcs(&aig, &config).iter().map(|mffc| mffc.root).collect();
+        assert_eq!(roots, vec![10, 12]);
+    }
+}
@@ -0,0 +1,457 @@
+//! Substituting optimized partitions back into the AIG they were cut from.
+//!
+//! A partition is described by its root literal and its leaves, as written by
+//! [`write_partitions`](crate::mffc::write_partitions). The replacement AIG has one
+//! output and one input per leaf, matched by the `n<code>` input names partitions
+//! carry, or by position when it has no such names. All replacements are spliced
+//! in at once, so boundaries keep referring to the original literals, and a final
+//! [`Renumber`] with structural hashing and trimming drops the replaced logic.
+//!
+//! Both checking and splicing look gates up through a [`GateIndex`], built once
+//! per AIG and shared by all partitions.
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::{
+    aig::{
+        Aig, AigStructureError, AndGate, OrderedAig, Renumber, RenumberConfig, Symbol, SymbolTarget,
+    },
+    cec::{cec, CecConfig, CecError, CecResult},
+    Lit,
+};
+
+#[derive(Error, Debug)]
+pub enum ReplaceError {
+    #[error("root {root} is not an AND gate of the AIG")]
+    NotAGate { root: usize },
+    #[error("replacement for root {root} must have one output and no latches")]
+    Interface { root: usize },
+    #[error("replacement for root {root} has {inputs} inputs for {leaves} leaves")]
+    InputCount {
+        root: usize,
+        inputs: usize,
+        leaves: usize,
+    },
+    #[error("the cone of root {root} reaches {code}, which is not one of its leaves")]
+    MissingLeaf { root: usize, code: usize },
+    #[error("replacement for root {root} reads undefined literal {code}")]
+    Undefined { root: usize, code: usize },
+    #[error("invalid AIG after replacement: {0}")]
+    Structure(String),
+    #[error(transparent)]
+    Cec(#[from] CecError),
+}
+
+/// The AND gates of an [`Aig`] indexed by output variable.
+pub struct GateIndex<'a, L> {
+    aig: &'a Aig<L>,
+    // Index into `and_gates` of the gate driving each variable
+    gate_of: Vec<Option<usize>>,
+}
+
+impl<'a, L: Lit> GateIndex<'a, L> {
+    pub fn new(aig: &'a Aig<L>) -> Self {
+        let vars = aig
+            .and_gates
+            .iter()
+            .map(|gate| gate.output.code() >> 1)
+            .max()
+            .map_or(0, |var| var + 1)
+            .max(aig.max_var_index + 1);
+        let mut gate_of = vec![None; vars];
+        for (index, gate) in aig.and_gates.iter().enumerate() {
+            gate_of[gate.output.code() >> 1] = Some(index);
+        }
+        GateIndex { aig, gate_of }
+    }
+
+    /// The gate driving the variable of `code`, if it is an AND gate.
+    pub fn gate(&self, code: usize) -> Option<&'a AndGate<L>> {
+        let index = (*self.gate_of.get(code >> 1)?)?;
+        Some(&self.aig.and_gates[index])
+    }
+}
+
+pub struct Replacement<L> {
+    pub root: L,
+    pub leaves: Vec<L>,
+    pub aig: Aig<L>,
+}
+
+impl<L: Lit> Replacement<L> {
+    fn check_interface(&self, index: &GateIndex<L>) -> Result<(), ReplaceError> {
+        let root = self.root.code();
+        if index.gate(root).is_none() {
+            return Err(ReplaceError::NotAGate { root });
+        }
+        if self.aig.outputs.len() != 1 || !self.aig.latches.is_empty() {
+            return Err(ReplaceError::Interface { root });
+        }
+        if self.aig.inputs.len() != self.leaves.len() {
+            return Err(ReplaceError::InputCount {
+                root,
+                inputs: self.aig.inputs.len(),
+                leaves: self.leaves.len(),
+            });
+        }
+        Ok(())
+    }
+
+    // Leaf driving each replacement input
+    fn input_leaves(&self) -> Vec<L> {
+        let by_name: HashMap<String, L> = self
+            .leaves
+            .iter()
+            .map(|&leaf| (format!("n{}", leaf.code()), leaf))
+            .collect();
+        let mut named: Vec<Option<L>> = vec![None; self.aig.inputs.len()];
+        for symbol in &self.aig.symbols {
+            if let SymbolTarget::Input(i) = symbol.target {
+                if let (Some(slot), Some(&leaf)) =
+                    (named.get_mut(i), by_name.get(symbol.name.as_ref()))
+                {
+                    *slot = Some(leaf);
+                }
+            }
+        }
+        let distinct: HashSet<usize> = named.iter().flatten().map(|leaf| leaf.code()).collect();
+        if named.iter().all(Option::is_some) && distinct.len() == named.len() {
+            named.into_iter().flatten().collect()
+        } else {
+            self.leaves.clone()
+        }
+    }
+
+    /// Checks the replacement against the logic between the root and the leaves
+    /// in the indexed AIG.
+    pub fn check(
+        &self,
+        index: &GateIndex<L>,
+        config: &CecConfig,
+    ) -> Result<CecResult, ReplaceError> {
+        self.check_interface(index)?;
+        let leaf_name = |leaf: L| format!("n{}", leaf.code());
+        let interface = |leaves: &[L]| -> Vec<Symbol<'static>> {
+            leaves
+                .iter()
+                .enumerate()
+                .map(|(i, &leaf)| Symbol {
+                    target: SymbolTarget::Input(i),
+                    name: leaf_name(leaf).into(),
+                })
+                .collect()
+        };
+
+        // The original cone, stopping at the leaves
+        let leaves: HashSet<usize> = self.leaves.iter().map(|leaf| leaf.code() & !1).collect();
+        let mut cone = vec![];
+        let mut seen = HashSet::new();
+        let mut stack = vec![self.root.code() & !1];
+        while let Some(code) = stack.pop() {
+            if leaves.contains(&code) || !seen.insert(code) {
+                continue;
+            }
+            match index.gate(code) {
+                Some(&gate) => {
+                    cone.push(gate);
+                    stack.extend(gate.inputs.map(|lit| lit.code() & !1));
+                }
+                None if code == 0 => {}
+                None => {
+                    return Err(ReplaceError::MissingLeaf {
+                        root: self.root.code(),
+                        code,
+                    })
+                }
+            }
+        }
+        let original = Aig {
+            max_var_index: index.aig.max_var_index,
+            inputs: self.leaves.clone(),
+            latches: vec![],
+            outputs: vec![self.root],
+            bad_state_properties: vec![],
+            invariant_constraints: vec![],
+            justice_properties: vec![],
+            fairness_constraints: vec![],
+            and_gates: cone,
+            symbols: interface(&self.leaves),
+            comment: None,
+        };
+
+        let mut replacement = self.aig.clone();
+        replacement.symbols = interface(&self.input_leaves());
+        Ok(cec(&original, &replacement, config)?)
+    }
+}
+
+/// Splices every replacement into the indexed AIG and renumbers the result.
+/// Inputs, outputs, latches and their symbols are kept.
+pub fn replace_subcircuits<L: Lit>(
+    index: &GateIndex<L>,
+    replacements: &[Replacement<L>],
+) -> Result<OrderedAig<L>, ReplaceError> {
+    let aig = index.aig;
+    let mut next_var = aig
+        .inputs
+        .iter()
+        .chain(aig.latches.iter().map(|latch| &latch.state))
+        .chain(aig.and_gates.iter().map(|gate| &gate.output))
+        .map(|lit| lit.code() >> 1)
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    // Root variable to the literal now driving it
+    let mut redirect: HashMap<usize, usize> = HashMap::new();
+    let mut new_gates: Vec<[usize; 3]> = vec![];
+    for replacement in replacements {
+        replacement.check_interface(index)?;
+        let mut map: HashMap<usize, usize> = HashMap::new();
+        map.insert(0, 0);
+        for (input, leaf) in replacement
+            .aig
+            .inputs
+            .iter()
+            .zip(replacement.input_leaves())
+        {
+            map.insert(input.code() >> 1, leaf.code());
+        }
+        for gate in &replacement.aig.and_gates {
+            map.insert(gate.output.code() >> 1, next_var * 2);
+            next_var += 1;
+        }
+        let translate = |lit: L| match map.get(&(lit.code() >> 1)) {
+            Some(&code) => Ok(code ^ (lit.code() & 1)),
+            None => Err(ReplaceError::Undefined {
+                root: replacement.root.code(),
+                code: lit.code(),
+            }),
+        };
+        for gate in &replacement.aig.and_gates {
+            new_gates.push([
+                translate(gate.output)?,
+                translate(gate.inputs[0])?,
+                translate(gate.inputs[1])?,
+            ]);
+        }
+        let output = translate(replacement.aig.outputs[0])? ^ (replacement.root.code() & 1);
+        redirect.insert(replacement.root.code() >> 1, output);
+    }
+
+    // A replacement may use another partition's root as a leaf
+    let resolve = |code: usize| {
+        let mut code = code;
+        let mut steps = 0;
+        while let Some(&target) = redirect.get(&(code >> 1)) {
+            code = target ^ (code & 1);
+            steps += 1;
+            if steps > redirect.len() {
+                return Err(ReplaceError::Structure(format!(
+                    "replacements form a cycle through {}",
+                    code
+                )));
+            }
+        }
+        Ok(code)
+    };
+    let lit = |code: usize| resolve(code).map(L::from_code);
+    let lits = |lits: &[L]| {
+        lits.iter()
+            .map(|l| lit(l.code()))
+            .collect::<Result<Vec<L>, _>>()
+    };
+
+    let mut and_gates = Vec::with_capacity(aig.and_gates.len() + new_gates.len());
+    for gate in &aig.and_gates {
+        if redirect.contains_key(&(gate.output.code() >> 1)) {
+            continue;
+        }
+        and_gates.push(AndGate {
+            inputs: [lit(gate.inputs[0].code())?, lit(gate.inputs[1].code())?],
+            output: gate.output,
+        });
+    }
+    for [output, a, b] in new_gates {
+        and_gates.push(AndGate {
+            inputs: [lit(a)?, lit(b)?],
+            output: L::from_code(output),
+        });
+    }
+
+    let mut latches = aig.latches.clone();
+    for latch in &mut latches {
+        latch.next_state = lit(latch.next_state.code())?;
+    }
+    let spliced = Aig {
+        max_var_index: next_var - 1,
+        inputs: aig.inputs.clone(),
+        latches,
+        outputs: lits(&aig.outputs)?,
+        bad_state_properties: lits(&aig.bad_state_properties)?,
+        invariant_constraints: lits(&aig.invariant_constraints)?,
+        justice_properties: aig
+            .justice_properties
+            .iter()
+            .map(|property| lits(property))
+            .collect::<Result<_, _>>()?,
+        fairness_constraints: lits(&aig.fairness_constraints)?,
+        and_gates,
+        symbols: aig.symbols.clone(),
+        comment: aig.comment.clone(),
+    };
+
+    let config = RenumberConfig::default()
+        .trim(true)
+        .structural_hash(true)
+        .const_fold(true);
+    let (ordered, _) = Renumber::renumber_aig(config, &spliced)
+        .map_err(|err: AigStructureError<L>| ReplaceError::Structure(format!("{:?}", err)))?;
+    Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // y = (a & b) & c, built as g8 = a & b, g10 = g8 & c
+    fn original() -> Aig<u32> {
+        Aig {
+            max_var_index: 5,
+            inputs: vec![2, 4, 6],
+            latches: vec![],
+            outputs: vec![10],
+            bad_state_properties: vec![],
+            invariant_constraints: vec![],
+            justice_properties: vec![],
+            fairness_constraints: vec![],
+            and_gates: vec![
+                AndGate {
+                    inputs: [2, 4],
+                    output: 8,
+                },
+                AndGate {
+                    inputs: [8, 6],
+                    output: 10,
+                },
+            ],
+            symbols: vec![Symbol {
+                target: SymbolTarget::Output(0),
+                name: "y".into(),
+            }],
+            comment: None,
+        }
+    }
+
+    // a & (b & c) over inputs named after the leaves in reverse order
+    fn rebalanced() -> Aig<u32> {
+        Aig {
+            max_var_index: 5,
+            inputs: vec![2, 4, 6],
+            outputs: vec![10],
+            and_gates: vec![
+                AndGate {
+                    inputs: [4, 2],
+                    output: 8,
+                },
+                AndGate {
+                    inputs: [8, 6],
+                    output: 10,
+                },
+            ],
+            symbols: ["n6", "n4", "n2"]
+                .iter()
+                .enumerate()
+                .map(|(i, name)| Symbol {
+                    target: SymbolTarget::Input(i),
+                    name: name.to_string().into(),
+                })
+                .collect(),
+            ..original()
+        }
+    }
+
+    #[test]
+    fn replacement_is_checked_and_spliced() {
+        let aig = original();
+        let index = GateIndex::new(&aig);
+        let replacement = Replacement {
+            root: 10,
+            leaves: vec![2, 4, 6],
+            aig: rebalanced(),
+        };
+        assert_eq!(
+            replacement.check(&index, &CecConfig::default()).unwrap(),
+            CecResult::Equivalent
+        );
+
+        let ordered = replace_subcircuits(&index, &[replacement]).unwrap();
+        assert_eq!(ordered.input_count, 3);
+        assert_eq!(ordered.and_gates.len(), 2);
+        assert_eq!(ordered.symbols[0].name, "y");
+        // c & b feeds the output gate now, with a as its other input
+        let [inner, outer] = [ordered.and_gates[0], ordered.and_gates[1]];
+        assert!(inner.inputs.contains(&6) && inner.inputs.contains(&4));
+        assert!(outer.inputs.contains(&2));
+    }
+
+    #[test]
+    fn wrong_replacement_is_refuted() {
+        let mut wrong = rebalanced();
+        wrong.outputs = vec![11];
+        let replacement = Replacement {
+            root: 10,
+            leaves: vec![2, 4, 6],
+            aig: wrong,
+        };
+        let aig = original();
+        assert!(matches!(
+            replacement
+                .check(&GateIndex::new(&aig), &CecConfig::default())
+                .unwrap(),
+            CecResult::NotEquivalent(_)
+        ));
+    }
+
+    #[test]
+    fn root_must_be_a_gate() {
+        let aig = original();
+        let index = GateIndex::new(&aig);
+        assert_eq!(index.gate(11).unwrap().output, 10);
+        assert!(index.gate(6).is_none());
+        let replacement = Replacement {
+            root: 6,
+            leaves: vec![2, 4],
+            aig: rebalanced(),
+        };
+        assert!(matches!(
+            replace_subcircuits(&index, &[replacement]),
+            Err(ReplaceError::NotAGate { root: 6 })
+        ));
+    }
+
+    #[test]
+    fn incomplete_leaves_are_errors() {
+        let aig = original();
+        let index = GateIndex::new(&aig);
+        // The cone of g10 also needs b
+        let mut partial = rebalanced();
+        partial.inputs = vec![2, 6];
+        partial.symbols.truncate(2);
+        let replacement = Replacement {
+            root: 10,
+            leaves: vec![2, 6],
+            aig: partial,
+        };
+        assert!(matches!(
+            replacement.check(&index, &CecConfig::default()),
+            Err(ReplaceError::MissingLeaf { root: 10, code: 4 })
+        ));
+        // and the replacement's gate reads the undefined input 4
+        assert!(matches!(
+            replace_subcircuits(&index, &[replacement]),
+            Err(ReplaceError::Undefined { root: 10, code: 4 })
+        ));
+    }
+}
@@ -0,0 +1,466 @@
+//! A small CDCL SAT solver for the checks this crate runs on AIGs.
+//!
+//! Clauses use DIMACS literals, `v` or `-v` for variable `v >= 1`. The solver
+//! does two-watched-literal propagation, first UIP learning, VSIDS decisions with
+//! phase saving and Luby restarts. Learned clauses are kept forever, which is fine
+//! for miters and other one-shot queries but not for long incremental use.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SatResult {
+    Sat,
+    Unsat,
+    /// The conflict limit was reached.
+    Unknown,
+}
+
+const TRUE: i8 = 1;
+const FALSE: i8 = -1;
+const UNASSIGNED: i8 = 0;
+
+// Internal literals are `2 * var + negated` with 0-based variables
+fn var(lit: u32) -> usize {
+    (lit >> 1) as usize
+}
+
+fn value_of(values: &[i8], lit: u32) -> i8 {
+    let value = values[var(lit)];
+    if lit & 1 == 1 {
+        -value
+    } else {
+        value
+    }
+}
+
+// Binary max-heap of variables ordered by activity
+#[derive(Default)]
+struct VarHeap {
+    heap: Vec<usize>,
+    position: Vec<Option<usize>>,
+}
+
+impl VarHeap {
+    fn contains(&self, var: usize) -> bool {
+        self.position[var].is_some()
+    }
+
+    fn insert(&mut self, var: usize, activity: &[f64]) {
+        if self.contains(var) {
+            return;
+        }
+        self.position[var] = Some(self.heap.len());
+        self.heap.push(var);
+        self.sift_up(self.heap.len() - 1, activity);
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.position[top] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.position[last] = Some(0);
+            self.sift_down(0, activity);
+        }
+        Some(top)
+    }
+
+    fn sift_up(&mut self, mut index: usize, activity: &[f64]) {
+        let var = self.heap[index];
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if activity[self.heap[parent]] >= activity[var] {
+                break;
+            }
+            self.heap[index] = self.heap[parent];
+            self.position[self.heap[index]] = Some(index);
+            index = parent;
+        }
+        self.heap[index] = var;
+        self.position[var] = Some(index);
+    }
+
+    fn sift_down(&mut self, mut index: usize, activity: &[f64]) {
+        let var = self.heap[index];
+        loop {
+            let mut child = 2 * index + 1;
+            if child >= self.heap.len() {
+                break;
+            }
+            if child + 1 < self.heap.len()
+                && activity[self.heap[child + 1]] > activity[self.heap[child]]
+            {
+                child += 1;
+            }
+            if activity[self.heap[child]] <= activity[var] {
+                break;
+            }
+            self.heap[index] = self.heap[child];
+            self.position[self.heap[index]] = Some(index);
+            index = child;
+        }
+        self.heap[index] = var;
+        self.position[var] = Some(index);
+    }
+}
+
+// Restart interval multipliers 1, 1, 2, 1, 1, 2, 4, ...
+fn luby(mut x: u64) -> u64 {
+    let (mut size, mut seq) = (1, 0);
+    while size < x + 1 {
+        seq += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != x {
+        size = (size - 1) >> 1;
+        seq -= 1;
+        x %= size;
+    }
+    1 << seq
+}
+
+#[derive(Default)]
+pub struct Solver {
+    clauses: Vec<Vec<u32>>,
+    // Clauses watching each literal, visited when it becomes false
+    watches: Vec<Vec<usize>>,
+    values: Vec<i8>,
+    level: Vec<usize>,
+    reason: Vec<Option<usize>>,
+    phase: Vec<bool>,
+    activity: Vec<f64>,
+    activity_inc: f64,
+    heap: VarHeap,
+    trail: Vec<u32>,
+    trail_lim: Vec<usize>,
+    queue_head: usize,
+    seen: Vec<bool>,
+    model: Vec<bool>,
+    unsat: bool,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Solver {
+            activity_inc: 1.0,
+            ..Default::default()
+        }
+    }
+
+    /// Number of variables seen so far.
+    pub fn var_count(&self) -> usize {
+        self.values.len()
+    }
+
+    fn ensure_var(&mut self, var: usize) {
+        while self.values.len() <= var {
+            let new = self.values.len();
+            self.values.push(UNASSIGNED);
+            self.level.push(0);
+            self.reason.push(None);
+            self.phase.push(false);
+            self.activity.push(0.0);
+            self.seen.push(false);
+            self.watches.push(vec![]);
+            self.watches.push(vec![]);
+            self.heap.position.push(None);
+            self.heap.insert(new, &self.activity);
+        }
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    fn enqueue(&mut self, lit: u32, reason: Option<usize>) {
+        let v = var(lit);
+        self.values[v] = if lit & 1 == 1 { FALSE } else { TRUE };
+        self.level[v] = self.decision_level();
+        self.reason[v] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Adds a clause of DIMACS literals. Must not be called while solving.
+    pub fn add_clause(&mut self, clause: &[i32]) {
+        if self.unsat {
+            return;
+        }
+        let mut lits: Vec<u32> = clause
+            .iter()
+            .map(|&lit| {
+                assert_ne!(lit, 0, "0 is not a DIMACS literal");
+                let v = lit.unsigned_abs() as usize - 1;
+                self.ensure_var(v);
+                (v as u32) << 1 | (lit < 0) as u32
+            })
+            .collect();
+        lits.sort_unstable();
+        lits.dedup();
+        if lits.windows(2).any(|pair| pair[0] ^ 1 == pair[1]) {
+            return;
+        }
+        if lits.iter().any(|&lit| value_of(&self.values, lit) == TRUE) {
+            return;
+        }
+        lits.retain(|&lit| value_of(&self.values, lit) == UNASSIGNED);
+
+        match lits.len() {
+            0 => self.unsat = true,
+            1 => {
+                self.enqueue(lits[0], None);
+                if self.propagate().is_some() {
+                    self.unsat = true;
+                }
+            }
+            _ => {
+                let index = self.clauses.len();
+                self.watches[lits[0] as usize].push(index);
+                self.watches[lits[1] as usize].push(index);
+                self.clauses.push(lits);
+            }
+        }
+    }
+
+    // Returns a conflicting clause, if any
+    fn propagate(&mut self) -> Option<usize> {
+        while self.queue_head < self.trail.len() {
+            let false_lit = self.trail[self.queue_head] ^ 1;
+            self.queue_head += 1;
+
+            let mut watchers = std::mem::take(&mut self.watches[false_lit as usize]);
+            let (mut i, mut j) = (0, 0);
+            let mut conflict = None;
+            while i < watchers.len() {
+                let index = watchers[i];
+                i += 1;
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                if value_of(&self.values, clause[0]) == TRUE {
+                    watchers[j] = index;
+                    j += 1;
+                    continue;
+                }
+                if let Some(k) =
+                    (2..clause.len()).find(|&k| value_of(&self.values, clause[k]) != FALSE)
+                {
+                    clause.swap(1, k);
+                    self.watches[clause[1] as usize].push(index);
+                    continue;
+                }
+
+                watchers[j] = index;
+                j += 1;
+                let first = clause[0];
+                if value_of(&self.values, first) == FALSE {
+                    conflict = Some(index);
+                    while i < watchers.len() {
+                        watchers[j] = watchers[i];
+                        j += 1;
+                        i += 1;
+                    }
+                } else {
+                    self.enqueue(first, Some(index));
+                }
+            }
+            watchers.truncate(j);
+            self.watches[false_lit as usize] = watchers;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    fn bump(&mut self, v: usize) {
+        self.activity[v] += self.activity_inc;
+        if self.activity[v] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.activity_inc *= 1e-100;
+        }
+        if let Some(index) = self.heap.position[v] {
+            self.heap.sift_up(index, &self.activity);
+        }
+    }
+
+    // First UIP clause with the asserting literal first, and the level to backtrack to
+    fn analyze(&mut self, mut conflict: usize) -> (Vec<u32>, usize) {
+        let mut learnt = vec![0];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut skip_first = false;
+        loop {
+            let start = skip_first as usize;
+            for k in start..self.clauses[conflict].len() {
+                let lit = self.clauses[conflict][k];
+                let v = var(lit);
+                if !self.seen[v] && self.level[v] > 0 {
+                    self.seen[v] = true;
+                    self.bump(v);
+                    if self.level[v] >= self.decision_level() {
+                        pending += 1;
+                    } else {
+                        learnt.push(lit);
+                    }
+                }
+            }
+
+            loop {
+                index -= 1;
+                if self.seen[var(self.trail[index])] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            self.seen[var(lit)] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = lit ^ 1;
+                break;
+            }
+            conflict = self.reason[var(lit)].expect("implied literal without reason");
+            skip_first = true;
+        }
+
+        for &lit in &learnt[1..] {
+            self.seen[var(lit)] = false;
+        }
+        let mut backtrack_level = 0;
+        if learnt.len() > 1 {
+            let (max, _) = learnt
+                .iter()
+                .enumerate()
+                .skip(1)
+                .max_by_key(|(_, &lit)| self.level[var(lit)])
+                .unwrap();
+            learnt.swap(1, max);
+            backtrack_level = self.level[var(learnt[1])];
+        }
+        (learnt, backtrack_level)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        let start = self.trail_lim[level];
+        for k in (start..self.trail.len()).rev() {
+            let lit = self.trail[k];
+            let v = var(lit);
+            self.values[v] = UNASSIGNED;
+            self.reason[v] = None;
+            self.phase[v] = lit & 1 == 0;
+            self.heap.insert(v, &self.activity);
+        }
+        self.trail.truncate(start);
+        self.trail_lim.truncate(level);
+        self.queue_head = self.trail.len();
+    }
+
+    /// Solves the clauses added so far, giving up with [`SatResult::Unknown`] after
+    /// `conflict_limit` conflicts.
+    pub fn solve(&mut self, conflict_limit: Option<u64>) -> SatResult {
+        if self.unsat {
+            return SatResult::Unsat;
+        }
+        let (mut conflicts, mut restarts, mut since_restart) = (0, 0, 0);
+        loop {
+            if let Some(conflict) = self.propagate() {
+                conflicts += 1;
+                since_restart += 1;
+                if self.decision_level() == 0 {
+                    self.unsat = true;
+                    return SatResult::Unsat;
+                }
+                let (learnt, backtrack_level) = self.analyze(conflict);
+                self.backtrack(backtrack_level);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let index = self.clauses.len();
+                    self.watches[learnt[0] as usize].push(index);
+                    self.watches[learnt[1] as usize].push(index);
+                    let asserting = learnt[0];
+                    self.clauses.push(learnt);
+                    self.enqueue(asserting, Some(index));
+                }
+                self.activity_inc /= 0.95;
+
+                if conflict_limit.is_some_and(|limit| conflicts >= limit) {
+                    self.backtrack(0);
+                    return SatResult::Unknown;
+                }
+                if since_restart >= 100 * luby(restarts) {
+                    restarts += 1;
+                    since_restart = 0;
+                    self.backtrack(0);
+                }
+            } else {
+                let decision = loop {
+                    match self.heap.pop(&self.activity) {
+                        Some(v) if self.values[v] != UNASSIGNED => continue,
+                        other => break other,
+                    }
+                };
+                let Some(v) = decision else {
+                    self.model = self.values.iter().map(|&value| value == TRUE).collect();
+                    self.backtrack(0);
+                    return SatResult::Sat;
+                };
+                self.trail_lim.push(self.trail.len());
+                self.enqueue((v as u32) << 1 | !self.phase[v] as u32, None);
+            }
+        }
+    }
+
+    /// Value of DIMACS variable `var` in the model of the last satisfiable call.
+    pub fn model_value(&self, var: i32) -> bool {
+        self.model[var as usize - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pigeonhole_is_unsat() {
+        // 4 pigeons in 3 holes, variable 3 * p + h + 1 puts pigeon p in hole h
+        let mut solver = Solver::new();
+        let var = |p: i32, h: i32| 3 * p + h + 1;
+        for p in 0..4 {
+            solver.add_clause(&[var(p, 0), var(p, 1), var(p, 2)]);
+        }
+        for h in 0..3 {
+            for p in 0..4 {
+                for q in p + 1..4 {
+                    solver.add_clause(&[-var(p, h), -var(q, h)]);
+                }
+            }
+        }
+        assert_eq!(solver.solve(None), SatResult::Unsat);
+    }
+
+    #[test]
+    fn model_satisfies_clauses() {
+        let clauses: Vec<Vec<i32>> = vec![
+            vec![1, 2],
+            vec![-1, 3],
+            vec![-2, -3],
+            vec![-3, 4],
+            vec![2, -4],
+        ];
+        let mut solver = Solver::new();
+        for clause in &clauses {
+            solver.add_clause(clause);
+        }
+        assert_eq!(solver.solve(None), SatResult::Sat);
+        for clause in &clauses {
+            assert!(clause
+                .iter()
+                .any(|&lit| solver.model_value(lit.abs()) == (lit > 0)));
+        }
+    }
+}
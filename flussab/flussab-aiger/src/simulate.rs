@@ -0,0 +1,305 @@
+//! Bit-parallel simulation of [`OrderedAig`]s.
+//!
+//! Every variable holds one bit per input pattern, packed into 64-bit words, so a
+//! single pass over the AND gates evaluates 64 patterns per word. Latch outputs
+//! are treated as free inputs, which makes this a simulation of the combinational
+//! transition logic.
+use thiserror::Error;
+
+use crate::{aig::OrderedAig, Lit};
+
+/// Input patterns to simulate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Patterns {
+    /// `words * 64` pseudo-random patterns derived from `seed`.
+    Random { words: usize, seed: u64 },
+    /// All `2^n` assignments of the `n` inputs and latches.
+    Exhaustive,
+}
+
+/// Largest number of inputs and latches [`Patterns::Exhaustive`] accepts.
+pub const MAX_EXHAUSTIVE_INPUTS: usize = 24;
+
+#[derive(Error, Debug)]
+pub enum SimulationError {
+    #[error(
+        "exhaustive simulation of {inputs} inputs, at most {MAX_EXHAUSTIVE_INPUTS} are supported"
+    )]
+    TooManyInputs { inputs: usize },
+    #[error("AIGs differ in their interface: {0} vs {1} inputs and latches, {2} vs {3} outputs")]
+    InterfaceMismatch(usize, usize, usize, usize),
+}
+
+/// Value of every variable for each simulated pattern.
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    /// Number of 64-bit words per variable.
+    pub words: usize,
+    /// Number of valid patterns, the bits beyond it in the last word are zero.
+    pub patterns: usize,
+    values: Vec<u64>,
+    outputs: Vec<usize>,
+    first_gate: usize,
+    gates: usize,
+}
+
+/// Switching activity of a single AND gate.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NodeActivity {
+    /// Patterns for which the gate is 1.
+    pub ones: usize,
+    /// Value changes between consecutive patterns.
+    pub toggles: usize,
+    /// `ones / patterns`.
+    pub probability: f64,
+    /// `toggles / (patterns - 1)`.
+    pub toggle_rate: f64,
+}
+
+/// An output on which two AIGs differ.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Mismatch {
+    pub output: usize,
+    /// First pattern for which the outputs differ.
+    pub pattern: usize,
+}
+
+// SplitMix64, so that patterns only depend on the seed and the input position
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn input_words(index: usize, words: usize, patterns: Patterns) -> impl Iterator<Item = u64> {
+    const MASKS: [u64; 6] = [
+        0xaaaa_aaaa_aaaa_aaaa,
+        0xcccc_cccc_cccc_cccc,
+        0xf0f0_f0f0_f0f0_f0f0,
+        0xff00_ff00_ff00_ff00,
+        0xffff_0000_ffff_0000,
+        0xffff_ffff_0000_0000,
+    ];
+    let mut state = match patterns {
+        Patterns::Random { seed, .. } => seed ^ (index as u64).wrapping_mul(0xd1b5_4a32_d192_ed03),
+        Patterns::Exhaustive => 0,
+    };
+    (0..words).map(move |w| match patterns {
+        Patterns::Random { .. } => split_mix(&mut state),
+        Patterns::Exhaustive if index < 6 => MASKS[index],
+        Patterns::Exhaustive => 0u64.wrapping_sub(((w >> (index - 6)) & 1) as u64),
+    })
+}
+
+impl Simulation {
+    pub fn new<L: Lit>(aig: &OrderedAig<L>, patterns: Patterns) -> Result<Self, SimulationError> {
+        let leaves = aig.input_count + aig.latches.len();
+        let (words, count) = match patterns {
+            Patterns::Random { words, .. } => (words, words * 64),
+            Patterns::Exhaustive if leaves > MAX_EXHAUSTIVE_INPUTS => {
+                return Err(SimulationError::TooManyInputs { inputs: leaves })
+            }
+            Patterns::Exhaustive => ((1usize << leaves).div_ceil(64), 1 << leaves),
+        };
+
+        let first_gate = leaves + 1;
+        let mut values = vec![0; (first_gate + aig.and_gates.len()) * words];
+        for index in 0..leaves {
+            let var = index + 1;
+            for (slot, word) in values[var * words..(var + 1) * words]
+                .iter_mut()
+                .zip(input_words(index, words, patterns))
+            {
+                *slot = word;
+            }
+        }
+
+        let mut sim = Simulation {
+            words,
+            patterns: count,
+            values,
+            outputs: aig.outputs.iter().map(|lit| lit.code()).collect(),
+            first_gate,
+            gates: aig.and_gates.len(),
+        };
+        for (gate, and_gate) in aig.and_gates.iter().enumerate() {
+            let [a, b] = and_gate.inputs.map(|lit| lit.code());
+            let var = first_gate + gate;
+            for w in 0..words {
+                let value = sim.word(a, w) & sim.word(b, w);
+                sim.values[var * words + w] = value;
+            }
+        }
+        sim.mask_tail();
+        Ok(sim)
+    }
+
+    // Keeps the bits of patterns beyond `self.patterns` at zero
+    fn mask_tail(&mut self) {
+        let tail = self.patterns % 64;
+        if tail != 0 && self.words > 0 {
+            let mask = (1u64 << tail) - 1;
+            for var in 0..self.values.len() / self.words {
+                self.values[var * self.words + self.words - 1] &= mask;
+            }
+        }
+    }
+
+    fn word(&self, code: usize, w: usize) -> u64 {
+        let value = self.values[(code >> 1) * self.words + w];
+        if code & 1 == 1 {
+            !value
+        } else {
+            value
+        }
+    }
+
+    /// Simulated values of a literal, one bit per pattern.
+    pub fn lit_values<L: Lit>(&self, lit: L) -> Vec<u64> {
+        self.code_values(lit.code())
+    }
+
+    fn code_values(&self, code: usize) -> Vec<u64> {
+        let mut values: Vec<u64> = (0..self.words).map(|w| self.word(code, w)).collect();
+        let tail = self.patterns % 64;
+        if tail != 0 {
+            if let Some(last) = values.last_mut() {
+                *last &= (1u64 << tail) - 1;
+            }
+        }
+        values
+    }
+
+    /// Signature of every output, the values of its literal.
+    pub fn output_signatures(&self) -> Vec<Vec<u64>> {
+        self.outputs
+            .iter()
+            .map(|&code| self.code_values(code))
+            .collect()
+    }
+
+    /// Switching activity of every AND gate, in gate order.
+    pub fn activity(&self) -> Vec<NodeActivity> {
+        let tail = self.patterns % 64;
+        (0..self.gates)
+            .map(|gate| {
+                let values = &self.values[(self.first_gate + gate) * self.words..][..self.words];
+                let ones = values.iter().map(|w| w.count_ones() as usize).sum();
+                let mut toggles = 0;
+                let mut previous = None;
+                for (w, &word) in values.iter().enumerate() {
+                    let bits = if w + 1 == self.words && tail != 0 {
+                        tail
+                    } else {
+                        64
+                    };
+                    // Bit i differs from bit i - 1, the first one from the previous word
+                    let shifted = (word << 1) | previous.map_or(word & 1, |p: u64| p >> 63);
+                    let changes = word ^ shifted;
+                    let mask = if bits == 64 { !0 } else { (1u64 << bits) - 1 };
+                    toggles += (changes & mask).count_ones() as usize;
+                    previous = Some(word);
+                }
+                NodeActivity {
+                    ones,
+                    toggles,
+                    probability: ones as f64 / self.patterns.max(1) as f64,
+                    toggle_rate: toggles as f64 / self.patterns.saturating_sub(1).max(1) as f64,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Simulates two AIGs on the same patterns and returns the outputs on which they
+/// differ. Inputs and latches are matched by position.
+pub fn compare<A: Lit, B: Lit>(
+    a: &OrderedAig<A>,
+    b: &OrderedAig<B>,
+    patterns: Patterns,
+) -> Result<Vec<Mismatch>, SimulationError> {
+    let leaves = (
+        a.input_count + a.latches.len(),
+        b.input_count + b.latches.len(),
+    );
+    if leaves.0 != leaves.1 || a.outputs.len() != b.outputs.len() {
+        return Err(SimulationError::InterfaceMismatch(
+            leaves.0,
+            leaves.1,
+            a.outputs.len(),
+            b.outputs.len(),
+        ));
+    }
+    let sim_a = Simulation::new(a, patterns)?;
+    let sim_b = Simulation::new(b, patterns)?;
+
+    let mut mismatches = vec![];
+    for (output, (sig_a, sig_b)) in sim_a
+        .output_signatures()
+        .iter()
+        .zip(sim_b.output_signatures())
+        .enumerate()
+    {
+        if let Some((w, diff)) = sig_a
+            .iter()
+            .zip(&sig_b)
+            .map(|(x, y)| x ^ y)
+            .enumerate()
+            .find(|&(_, diff)| diff != 0)
+        {
+            mismatches.push(Mismatch {
+                output,
+                pattern: w * 64 + diff.trailing_zeros() as usize,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aig::OrderedAndGate;
+
+    // out0 = a & b, out1 = !(a & b), from inputs a and b
+    fn and2(negate_second: bool) -> OrderedAig<u32> {
+        OrderedAig {
+            max_var_index: 3,
+            input_count: 2,
+            and_gates: vec![OrderedAndGate { inputs: [4, 2] }],
+            outputs: vec![6, if negate_second { 7 } else { 6 }],
+            ..OrderedAig::default()
+        }
+    }
+
+    #[test]
+    fn exhaustive_signatures_are_truth_tables() {
+        let sim = Simulation::new(&and2(true), Patterns::Exhaustive).unwrap();
+        assert_eq!(sim.patterns, 4);
+        assert_eq!(sim.output_signatures(), vec![vec![0b1000], vec![0b0111]]);
+
+        let activity = sim.activity();
+        assert_eq!(activity[0].ones, 1);
+        assert_eq!(activity[0].toggles, 1);
+        assert_eq!(activity[0].probability, 0.25);
+    }
+
+    #[test]
+    fn compare_reports_first_differing_pattern() {
+        let patterns = Patterns::Random { words: 4, seed: 1 };
+        assert!(compare(&and2(true), &and2(true), patterns)
+            .unwrap()
+            .is_empty());
+
+        let mismatches = compare(&and2(true), &and2(false), Patterns::Exhaustive).unwrap();
+        assert_eq!(
+            mismatches,
+            vec![Mismatch {
+                output: 1,
+                pattern: 0
+            }]
+        );
+    }
+}
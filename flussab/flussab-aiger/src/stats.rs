@@ -0,0 +1,132 @@
+//! Network statistics of an [`OrderedAig`], the counterpart of ABC's `print_stats`.
+use std::{collections::BTreeMap, fmt};
+
+use serde::Serialize;
+
+use crate::{aig::OrderedAig, Lit};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AigStats {
+    pub inputs: usize,
+    pub outputs: usize,
+    pub latches: usize,
+    pub and_gates: usize,
+    /// AND gates on the longest path from an input or latch to an output or next state.
+    pub levels: usize,
+    /// Complemented AND gate inputs, outputs and next states.
+    pub inverted_edges: usize,
+    /// Longest such path counting every inverter as a gate too, the unit delay of
+    /// an AND/INV network.
+    pub critical_path: usize,
+    pub max_fanout: usize,
+    pub average_fanout: f64,
+    /// Number of inputs, latches and AND gates with each fanout. Outputs and next
+    /// states count as fanouts.
+    pub fanout_histogram: BTreeMap<usize, usize>,
+}
+
+impl fmt::Display for AigStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "i/o = {}/{}  lat = {}  and = {}  lev = {}",
+            self.inputs, self.outputs, self.latches, self.and_gates, self.levels
+        )?;
+        writeln!(
+            f,
+            "inv = {}  crit = {}  fanout max = {}  avg = {:.2}",
+            self.inverted_edges, self.critical_path, self.max_fanout, self.average_fanout
+        )?;
+        write!(f, "fanout histogram:")?;
+        for (fanout, count) in &self.fanout_histogram {
+            write!(f, " {}:{}", fanout, count)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn stats<L: Lit>(aig: &OrderedAig<L>) -> AigStats {
+    let first_gate = aig.input_count + aig.latches.len() + 1;
+    let vars = first_gate + aig.and_gates.len();
+    let mut level = vec![0; vars];
+    // Depth of the positive literal with inverters as gates
+    let mut depth = vec![0; vars];
+    let mut fanout = vec![0; vars];
+    let mut inverted_edges = 0;
+
+    let lit_depth = |depth: &[usize], lit: L| depth[lit.code() >> 1] + (lit.code() & 1);
+    for (k, gate) in aig.and_gates.iter().enumerate() {
+        let var = first_gate + k;
+        for lit in gate.inputs {
+            fanout[lit.code() >> 1] += 1;
+            inverted_edges += lit.code() & 1;
+        }
+        let [a, b] = gate.inputs;
+        level[var] = 1 + level[a.code() >> 1].max(level[b.code() >> 1]);
+        depth[var] = 1 + lit_depth(&depth, a).max(lit_depth(&depth, b));
+    }
+
+    let sinks = aig
+        .outputs
+        .iter()
+        .chain(aig.latches.iter().map(|latch| &latch.next_state));
+    let (mut levels, mut critical_path) = (0, 0);
+    for &lit in sinks {
+        fanout[lit.code() >> 1] += 1;
+        inverted_edges += lit.code() & 1;
+        levels = levels.max(level[lit.code() >> 1]);
+        critical_path = critical_path.max(lit_depth(&depth, lit));
+    }
+
+    // The constant is not a node
+    let nodes = &fanout[1..];
+    let mut fanout_histogram = BTreeMap::new();
+    for &count in nodes {
+        *fanout_histogram.entry(count).or_insert(0) += 1;
+    }
+    let edges: usize = nodes.iter().sum();
+
+    AigStats {
+        inputs: aig.input_count,
+        outputs: aig.outputs.len(),
+        latches: aig.latches.len(),
+        and_gates: aig.and_gates.len(),
+        levels,
+        inverted_edges,
+        critical_path,
+        max_fanout: nodes.iter().copied().max().unwrap_or(0),
+        average_fanout: edges as f64 / nodes.len().max(1) as f64,
+        fanout_histogram,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aig::OrderedAndGate;
+
+    #[test]
+    fn or_of_and() {
+        // g4 = a & b, g5 = !g4 & !c, out = !g5 = (a & b) | c
+        let aig = OrderedAig {
+            max_var_index: 5,
+            input_count: 3,
+            and_gates: vec![
+                OrderedAndGate { inputs: [4, 2] },
+                OrderedAndGate { inputs: [9, 7] },
+            ],
+            outputs: vec![11],
+            ..OrderedAig::<u32>::default()
+        };
+        let stats = stats(&aig);
+        assert_eq!(stats.and_gates, 2);
+        assert_eq!(stats.levels, 2);
+        assert_eq!(stats.inverted_edges, 3);
+        assert_eq!(stats.critical_path, 4);
+        assert_eq!(stats.max_fanout, 1);
+        assert_eq!(stats.fanout_histogram, BTreeMap::from([(1, 5)]));
+        assert!(stats
+            .to_string()
+            .starts_with("i/o = 3/1  lat = 0  and = 2  lev = 2\n"));
+    }
+}
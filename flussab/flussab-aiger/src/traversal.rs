@@ -4,6 +4,8 @@ use egraph_serialize::*;
 use anyhow::Context;
 use crate::aig::*;
 use crate::Lit;
+use std::borrow::Cow;
+use std::path::Path;
 #[derive(Deserialize, Debug)]
 pub struct Node {
     pub op: String,
@@ -18,6 +20,123 @@ pub struct Graph {
     pub nodes: FxHashMap<String, Node>,
     pub root_eclasses: Vec<String>,
 }
+/// Latches of a sequential AIG whose latch outputs went through the egraph as
+/// pseudo-primary inputs and whose next-state functions went through as extra
+/// outputs. aig_2_egraph writes it next to the egraph, egraph2aig reads it back
+/// to reconstruct the latches.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatchMap {
+    /// Number of real outputs, the next-state functions follow them in latch order.
+    pub outputs: usize,
+    pub latches: Vec<LatchInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LatchInfo {
+    /// Name of the pseudo-primary input standing for the latch output.
+    pub name: String,
+    pub initialization: Option<bool>,
+}
+
+impl LatchMap {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let json_str = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read latch map {}", path.display()))?;
+        serde_json::from_str(&json_str).with_context(|| format!("Failed to parse latch map {}", path.display()))
+    }
+}
+
+impl<L: Lit> Aig<L> {
+    /// Turns the pseudo-primary inputs and trailing outputs described by `latch_map`
+    /// back into latches. A latch whose output no logic reads gets a fresh variable.
+    pub fn restore_latches(&mut self, latch_map: &LatchMap) -> anyhow::Result<()> {
+        let expected = latch_map.outputs + latch_map.latches.len();
+        if self.outputs.len() != expected {
+            anyhow::bail!(
+                "Expected {} outputs and {} next-state functions, found {} outputs",
+                latch_map.outputs,
+                latch_map.latches.len(),
+                self.outputs.len()
+            );
+        }
+
+        let mut input_names: FxHashMap<&str, usize> = FxHashMap::default();
+        for symbol in &self.symbols {
+            if let SymbolTarget::Input(index) = symbol.target {
+                input_names.insert(&symbol.name, index);
+            }
+        }
+        let mut next_var = self
+            .inputs
+            .iter()
+            .chain(self.and_gates.iter().map(|gate| &gate.output))
+            .map(|lit| lit.code() / 2)
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        let mut latch_inputs = FxHashSet::default();
+        let mut states = Vec::with_capacity(latch_map.latches.len());
+        for latch in &latch_map.latches {
+            let state = match input_names.get(latch.name.as_str()) {
+                Some(&index) => {
+                    latch_inputs.insert(index);
+                    self.inputs[index]
+                }
+                None => {
+                    next_var += 1;
+                    L::from_code((next_var - 1) * 2)
+                }
+            };
+            states.push(state);
+        }
+
+        // Keep the remaining inputs in order and renumber their symbols
+        let mut new_index = vec![None; self.inputs.len()];
+        let mut inputs = Vec::with_capacity(self.inputs.len() - latch_inputs.len());
+        for (index, &input) in self.inputs.iter().enumerate() {
+            if !latch_inputs.contains(&index) {
+                new_index[index] = Some(inputs.len());
+                inputs.push(input);
+            }
+        }
+        self.inputs = inputs;
+
+        let next_states = self.outputs.split_off(latch_map.outputs);
+        self.latches = states
+            .into_iter()
+            .zip(next_states)
+            .zip(&latch_map.latches)
+            .map(|((state, next_state), latch)| Latch {
+                state,
+                next_state,
+                initialization: latch.initialization,
+            })
+            .collect();
+        self.max_var_index = self.max_var_index.max(next_var - 1);
+
+        let mut symbols: Vec<Symbol<'static>> = std::mem::take(&mut self.symbols)
+            .into_iter()
+            .filter_map(|symbol| match symbol.target {
+                SymbolTarget::Input(index) => new_index[index].map(|index| Symbol {
+                    target: SymbolTarget::Input(index),
+                    name: symbol.name,
+                }),
+                SymbolTarget::Output(index) if index >= latch_map.outputs => None,
+                _ => Some(symbol),
+            })
+            .collect();
+        for (index, latch) in latch_map.latches.iter().enumerate() {
+            symbols.push(Symbol {
+                target: SymbolTarget::Latch(index),
+                name: Cow::Owned(latch.name.clone()),
+            });
+        }
+        self.symbols = symbols;
+        Ok(())
+    }
+}
+
 pub fn parse_json_sd(json_str: &str) -> Graph {
     serde_json::from_str(json_str).expect("JSON was not well-formatted")
 }
@@ -51,6 +170,88 @@ pub fn generate_input_vec(graph: &Graph) -> Vec<String> {
     leaf_nodes
 }
 impl Graph {
+    /// Rewrites `^`, `mux` and `maj` nodes into `*` and `!` nodes, the only ops `to_aig`
+    /// understands.
+    ///
+    /// Each lowered node keeps its id and becomes the top AND gate of its expansion, so
+    /// references to it stay valid and a `!` never ends up pointing at another `!`.
+    pub fn lower_xmg_ops(&mut self) {
+        let mut ids: Vec<String> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| matches!(node.op.as_str(), "^" | "mux" | "maj"))
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+
+        for id in ids {
+            let mut fresh = 0;
+            let node = &self.nodes[&id];
+            let op = node.op.clone();
+            let children = node.children.clone();
+
+            let top = match (op.as_str(), children.as_slice()) {
+                // a ^ b = !(a * b) * (a + b)
+                ("^", [a, b]) => {
+                    let and_ab = self.add_lowered(&id, &mut fresh, "*", vec![a.clone(), b.clone()]);
+                    let nand_ab = self.negate_lowered(&id, &mut fresh, &and_ab);
+                    let or_ab = self.or_lowered(&id, &mut fresh, a, b);
+                    vec![nand_ab, or_ab]
+                }
+                // s ? t : e = (s + e) * (!s + t)
+                ("mux", [s, t, e]) => {
+                    let or_se = self.or_lowered(&id, &mut fresh, s, e);
+                    let not_s = self.negate_lowered(&id, &mut fresh, s);
+                    let or_nst = self.or_lowered(&id, &mut fresh, &not_s, t);
+                    vec![or_se, or_nst]
+                }
+                // maj(a, b, c) = (a + b) * (a + c) * (b + c)
+                ("maj", [a, b, c]) => {
+                    let or_ab = self.or_lowered(&id, &mut fresh, a, b);
+                    let or_ac = self.or_lowered(&id, &mut fresh, a, c);
+                    let or_bc = self.or_lowered(&id, &mut fresh, b, c);
+                    let and_ab_ac = self.add_lowered(&id, &mut fresh, "*", vec![or_ab, or_ac]);
+                    vec![and_ab_ac, or_bc]
+                }
+                _ => panic!("Node {} has op `{}` with {} children", id, op, children.len()),
+            };
+
+            let node = self.nodes.get_mut(&id).unwrap();
+            node.op = "*".to_string();
+            node.children = top;
+        }
+    }
+
+    fn add_lowered(&mut self, base: &str, fresh: &mut usize, op: &str, children: Vec<String>) -> String {
+        let id = format!("{}_lowered{}", base, fresh);
+        *fresh += 1;
+        self.nodes.insert(
+            id.clone(),
+            Node {
+                op: op.to_string(),
+                children,
+                eclass: id.clone(),
+                cost: 0.0,
+                order: None,
+            },
+        );
+        id
+    }
+
+    fn negate_lowered(&mut self, base: &str, fresh: &mut usize, id: &str) -> String {
+        match self.nodes.get(id) {
+            Some(node) if node.op == "!" => node.children[0].clone(),
+            _ => self.add_lowered(base, fresh, "!", vec![id.to_string()]),
+        }
+    }
+
+    fn or_lowered(&mut self, base: &str, fresh: &mut usize, a: &str, b: &str) -> String {
+        let not_a = self.negate_lowered(base, fresh, a);
+        let not_b = self.negate_lowered(base, fresh, b);
+        let nor = self.add_lowered(base, fresh, "*", vec![not_a, not_b]);
+        self.negate_lowered(base, fresh, &nor)
+    }
+
     pub fn to_aig<L: Lit>(&self) -> Aig<L> {
         Aig::from_graph(self)
     }
@@ -227,3 +428,145 @@ impl Graph {
     //     result
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(op: &str, children: &[&str], eclass: &str) -> Node {
+        Node {
+            op: op.to_string(),
+            children: children.iter().map(|c| c.to_string()).collect(),
+            eclass: eclass.to_string(),
+            cost: 1.0,
+            order: None,
+        }
+    }
+
+    #[test]
+    fn lower_xmg_ops_leaves_only_and_inv() {
+        let mut graph = Graph {
+            nodes: [
+                ("1", node("pi0", &[], "1")),
+                ("2", node("pi1", &[], "2")),
+                ("3", node("pi2", &[], "3")),
+                ("4", node("^", &["1", "2"], "4")),
+                ("5", node("mux", &["4", "2", "3"], "5")),
+                ("6", node("maj", &["1", "5", "3"], "6")),
+            ]
+            .into_iter()
+            .map(|(id, node)| (id.to_string(), node))
+            .collect(),
+            root_eclasses: vec!["6".to_string()],
+        };
+
+        graph.lower_xmg_ops();
+
+        for (id, node) in &graph.nodes {
+            assert!(matches!(node.op.as_str(), "*" | "!") || node.children.is_empty());
+            assert_eq!(id, &node.eclass);
+            if node.op == "!" {
+                assert_ne!(graph.nodes[&node.children[0]].op, "!");
+            }
+        }
+        assert_eq!(graph.nodes["4"].op, "*");
+
+        let input_vec = generate_input_vec(&graph);
+        let mut graph = graph.reorder(input_vec);
+        graph.filter_nodes_by_op();
+        let aig: Aig<u32> = graph.to_aig();
+        assert_eq!(aig.inputs.len(), 3);
+        assert_eq!(aig.outputs.len(), 1);
+    }
+
+    #[test]
+    fn every_output_points_at_its_own_root() {
+        // out0 = pi0 * pi1, out1 = !(pi1 * pi2), out2 = pi0 * pi1 * pi2
+        let graph = Graph {
+            nodes: [
+                ("1", node("pi0", &[], "1")),
+                ("2", node("pi1", &[], "2")),
+                ("3", node("pi2", &[], "3")),
+                ("4", node("*", &["1", "2"], "4")),
+                ("5", node("*", &["2", "3"], "5")),
+                ("6", node("!", &["5"], "6")),
+                ("7", node("*", &["4", "3"], "7")),
+            ]
+            .into_iter()
+            .map(|(id, node)| (id.to_string(), node))
+            .collect(),
+            root_eclasses: vec!["4".to_string(), "6".to_string(), "7".to_string()],
+        };
+        let input_vec = generate_input_vec(&graph);
+        let mut graph = graph.reorder(input_vec);
+        graph.filter_nodes_by_op();
+        let aig: Aig<u32> = graph.to_aig();
+        assert_eq!(aig.inputs.len(), 3);
+        assert_eq!(aig.and_gates.len(), 3);
+        assert_eq!(aig.outputs.len(), 3);
+
+        let gate = |inputs: [u32; 2]| {
+            aig.and_gates
+                .iter()
+                .find(|gate| gate.inputs.contains(&inputs[0]) && gate.inputs.contains(&inputs[1]))
+                .unwrap()
+                .output
+        };
+        let [pi0, pi1, pi2] = [aig.inputs[0], aig.inputs[1], aig.inputs[2]];
+        let and01 = gate([pi0, pi1]);
+        assert_eq!(aig.outputs[0], and01);
+        assert_eq!(aig.outputs[1], gate([pi1, pi2]) ^ 1);
+        assert_eq!(aig.outputs[2], gate([and01, pi2]));
+    }
+
+    #[test]
+    fn restore_latches_round_trips_a_toggle() {
+        // out = pi0 * lo0, next(lo0) = !lo0
+        let graph = Graph {
+            nodes: [
+                ("1", node("pi0", &[], "1")),
+                ("2", node("lo0", &[], "2")),
+                ("3", node("*", &["1", "2"], "3")),
+                ("4", node("!", &["2"], "4")),
+            ]
+            .into_iter()
+            .map(|(id, node)| (id.to_string(), node))
+            .collect(),
+            root_eclasses: vec!["3".to_string(), "4".to_string()],
+        };
+        let input_vec = generate_input_vec(&graph);
+        let mut graph = graph.reorder(input_vec);
+        graph.filter_nodes_by_op();
+        let mut aig: Aig<u32> = graph.to_aig();
+        assert_eq!(aig.outputs.len(), 2);
+
+        let latch_map = LatchMap {
+            outputs: 1,
+            latches: vec![LatchInfo {
+                name: "lo0".to_string(),
+                initialization: Some(false),
+            }],
+        };
+        aig.restore_latches(&latch_map).unwrap();
+
+        assert_eq!(aig.inputs.len(), 1);
+        assert_eq!(aig.outputs.len(), 1);
+        assert_eq!(aig.latches.len(), 1);
+        let latch = &aig.latches[0];
+        assert_eq!(latch.next_state, latch.state ^ 1);
+        assert_eq!(latch.initialization, Some(false));
+        let gate = &aig.and_gates[0];
+        assert_eq!(aig.outputs[0], gate.output);
+        assert!(gate.inputs.contains(&latch.state));
+        assert!(gate.inputs.contains(&aig.inputs[0]));
+        assert!(aig
+            .symbols
+            .iter()
+            .any(|symbol| symbol.target == SymbolTarget::Latch(0) && symbol.name == "lo0"));
+
+        let config = RenumberConfig::default().trim(true).structural_hash(true).const_fold(true);
+        let (ordered, _) = Renumber::renumber_aig(config, &aig).unwrap();
+        assert_eq!(ordered.latches.len(), 1);
+        assert_eq!(ordered.and_gates.len(), 1);
+    }
+}